@@ -51,4 +51,18 @@ BEGIN
             -- Future: 'automatic', 'on_demand'
         );
     END IF;
+END$$;
+
+-- Create conflictpolicy enum if it doesn't exist
+-- Decides how an upload rejected with 412 Precondition Failed is resolved
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'conflictpolicy') THEN
+        CREATE TYPE public.conflictpolicy AS ENUM (
+            'local_wins',
+            'remote_wins',
+            'merge',
+            'manual'
+        );
+    END IF;
 END$$; 
\ No newline at end of file
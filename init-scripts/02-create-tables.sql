@@ -11,7 +11,9 @@ CREATE TABLE IF NOT EXISTS public.libraries (
     deleted boolean DEFAULT false NOT NULL,
     item_version bigint DEFAULT 0,
     collection_version bigint DEFAULT 0,
+    search_version bigint DEFAULT 0,
     tag_version bigint DEFAULT 0,
+    fulltext_version bigint DEFAULT 0,
     gitlab timestamp with time zone,
     PRIMARY KEY (id, library_type)
 );
@@ -28,6 +30,7 @@ CREATE TABLE IF NOT EXISTS public.items (
     trashed boolean DEFAULT false NOT NULL,
     deleted boolean DEFAULT false NOT NULL,
     md5 varchar(32),
+    synced_data jsonb,
     modified timestamp with time zone DEFAULT NOW(),
     gitlab timestamp with time zone,
     PRIMARY KEY (key, library_id, library_type),
@@ -53,6 +56,7 @@ CREATE TABLE IF NOT EXISTS public.collections (
     data jsonb,
     meta jsonb,
     deleted boolean DEFAULT false NOT NULL,
+    synced_data jsonb,
     modified timestamp with time zone DEFAULT NOW(),
     gitlab timestamp with time zone,
     PRIMARY KEY (key, library_id, library_type),
@@ -68,6 +72,103 @@ BEGIN
     END IF;
 END$$;
 
+-- Add synced_data snapshot columns if they don't exist
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'items' AND column_name = 'synced_data' AND table_schema = 'public') THEN
+        ALTER TABLE public.items ADD COLUMN synced_data jsonb;
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'collections' AND column_name = 'synced_data' AND table_schema = 'public') THEN
+        ALTER TABLE public.collections ADD COLUMN synced_data jsonb;
+    END IF;
+END$$;
+
+-- Add search_version column to libraries if it doesn't exist
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'libraries' AND column_name = 'search_version' AND table_schema = 'public') THEN
+        ALTER TABLE public.libraries ADD COLUMN search_version bigint DEFAULT 0;
+    END IF;
+END$$;
+
+-- Add fulltext_version column to libraries if it doesn't exist
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'libraries' AND column_name = 'fulltext_version' AND table_schema = 'public') THEN
+        ALTER TABLE public.libraries ADD COLUMN fulltext_version bigint DEFAULT 0;
+    END IF;
+END$$;
+
+-- Saved searches table
+CREATE TABLE IF NOT EXISTS public.searches (
+    key varchar(8) NOT NULL,
+    version bigint DEFAULT 0 NOT NULL,
+    library_id bigint NOT NULL,
+    library_type public.library_type NOT NULL,
+    sync public.syncstatus DEFAULT 'new' NOT NULL,
+    data jsonb,
+    deleted boolean DEFAULT false NOT NULL,
+    modified timestamp with time zone DEFAULT NOW(),
+    PRIMARY KEY (key, library_id, library_type),
+    FOREIGN KEY (library_id, library_type) REFERENCES public.libraries(id, library_type)
+);
+
+-- Full-text content extracted by Zotero, one row per attachment item
+CREATE TABLE IF NOT EXISTS public.fulltext (
+    item_key varchar(8) NOT NULL,
+    library_id bigint NOT NULL,
+    library_type public.library_type NOT NULL,
+    version bigint DEFAULT 0 NOT NULL,
+    content text,
+    indexed_pages integer,
+    total_pages integer,
+    indexed_chars integer,
+    total_chars integer,
+    modified timestamp with time zone DEFAULT NOW(),
+    PRIMARY KEY (item_key, library_id, library_type),
+    FOREIGN KEY (item_key, library_id, library_type) REFERENCES public.items(key, library_id, library_type) ON DELETE CASCADE
+);
+
+-- Storage location and transfer state of attachment files, one row per attachment item
+CREATE TABLE IF NOT EXISTS public.attachments (
+    item_key varchar(8) NOT NULL,
+    library_id bigint NOT NULL,
+    library_type public.library_type NOT NULL,
+    storage_folder text NOT NULL,      -- bucket
+    storage_name text NOT NULL,        -- object key within the bucket
+    size bigint,
+    md5 varchar(32),                   -- MD5 of the stored file
+    mtime bigint,                      -- modification time reported by Zotero, in milliseconds
+    content_type text,
+    status VARCHAR(20) NOT NULL,       -- 'downloaded', 'uploaded', 'missing', 'download_failed', 'upload_failed'
+    last_error text,
+    attempts integer DEFAULT 0 NOT NULL,
+    transferred_at timestamp with time zone DEFAULT NOW(),
+    PRIMARY KEY (item_key, library_id, library_type),
+    FOREIGN KEY (item_key, library_id, library_type) REFERENCES public.items(key, library_id, library_type) ON DELETE CASCADE
+);
+
+-- Content-addressed attachment files and the items referring to them
+CREATE TABLE IF NOT EXISTS public.blobs (
+    md5 varchar(32) PRIMARY KEY,
+    size bigint,
+    refcount integer DEFAULT 0 NOT NULL,  -- maintained by the attachment_blobs trigger
+    created timestamp with time zone DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS public.attachment_blobs (
+    item_key varchar(8) NOT NULL,
+    library_id bigint NOT NULL,
+    library_type public.library_type NOT NULL,
+    md5 varchar(32) NOT NULL REFERENCES public.blobs(md5),
+    PRIMARY KEY (item_key, library_id, library_type),
+    FOREIGN KEY (item_key, library_id, library_type) REFERENCES public.items(key, library_id, library_type) ON DELETE CASCADE
+);
+
 -- Tags table
 CREATE TABLE IF NOT EXISTS public.tags (
     tag varchar(255) NOT NULL,
@@ -87,6 +188,7 @@ CREATE TABLE IF NOT EXISTS public.sync_libraries (
     incoming_sync public.syncmode DEFAULT 'disabled' NOT NULL,
     outgoing_sync public.syncmode DEFAULT 'disabled' NOT NULL,
     tags boolean DEFAULT false NOT NULL,
+    conflict_policy public.conflictpolicy DEFAULT 'manual' NOT NULL,
     PRIMARY KEY (library_id, library_type),
     FOREIGN KEY (library_id, library_type) REFERENCES public.libraries(id, library_type) ON DELETE CASCADE
 );
@@ -118,10 +220,19 @@ BEGIN
     END IF;
 END$$;
 
+-- Add conflict_policy column if it doesn't exist
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'sync_libraries' AND column_name = 'conflict_policy' AND table_schema = 'public') THEN
+        ALTER TABLE public.sync_libraries ADD COLUMN conflict_policy public.conflictpolicy DEFAULT 'manual' NOT NULL;
+    END IF;
+END$$;
+
 -- Sync queue for event-driven outgoing sync
 CREATE TABLE IF NOT EXISTS public.sync_queue (
     id BIGSERIAL PRIMARY KEY,
-    entity_type VARCHAR(20) NOT NULL,  -- 'item', 'collection'
+    entity_type VARCHAR(20) NOT NULL,  -- 'item', 'collection', 'search'
     entity_key VARCHAR(8) NOT NULL,
     library_id BIGINT NOT NULL,
     library_type public.library_type NOT NULL,
@@ -136,6 +247,39 @@ CREATE TABLE IF NOT EXISTS public.sync_queue (
     FOREIGN KEY (library_id, library_type) REFERENCES public.libraries(id, library_type) ON DELETE CASCADE
 );
 
+-- Upload conflicts that could not be resolved automatically
+CREATE TABLE IF NOT EXISTS public.sync_conflicts (
+    id BIGSERIAL PRIMARY KEY,
+    entity_type VARCHAR(20) NOT NULL,  -- 'item', 'collection', 'file' (attachment file)
+    entity_key VARCHAR(8) NOT NULL,
+    library_id BIGINT NOT NULL,
+    library_type public.library_type NOT NULL,
+    policy public.conflictpolicy NOT NULL,
+    base_data jsonb,                   -- last synced snapshot
+    local_data jsonb,
+    remote_data jsonb,                 -- NULL when the object was deleted in Zotero
+    remote_version bigint,
+    fields jsonb,                      -- conflicting fields with base/local/remote values
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    resolved_at TIMESTAMP WITH TIME ZONE,
+    FOREIGN KEY (library_id, library_type) REFERENCES public.libraries(id, library_type) ON DELETE CASCADE
+);
+
+-- Change history of items and collections, used for audit and point-in-time restore
+CREATE TABLE IF NOT EXISTS public.change_history (
+    id BIGSERIAL PRIMARY KEY,
+    entity_type VARCHAR(20) NOT NULL,  -- 'item', 'collection'
+    entity_key VARCHAR(8) NOT NULL,
+    library_id BIGINT NOT NULL,
+    library_type public.library_type NOT NULL,
+    operation VARCHAR(10) NOT NULL,    -- 'create', 'update', 'delete'
+    source VARCHAR(10) NOT NULL,       -- 'cloud', 'local', 'worker'
+    old_data jsonb,                    -- NULL when the object did not exist before
+    new_data jsonb,                    -- NULL when the object was deleted
+    changed_at TIMESTAMP WITH TIME ZONE DEFAULT clock_timestamp() NOT NULL,
+    FOREIGN KEY (library_id, library_type) REFERENCES public.libraries(id, library_type) ON DELETE CASCADE
+);
+
 -- Create indexes for better performance
 CREATE INDEX IF NOT EXISTS idx_items_library ON public.items(library_id, library_type);
 CREATE INDEX IF NOT EXISTS idx_items_sync ON public.items(sync);
@@ -144,7 +288,13 @@ CREATE INDEX IF NOT EXISTS idx_items_deleted ON public.items(deleted);
 CREATE INDEX IF NOT EXISTS idx_collections_library ON public.collections(library_id, library_type);
 CREATE INDEX IF NOT EXISTS idx_collections_sync ON public.collections(sync);
 CREATE INDEX IF NOT EXISTS idx_collections_deleted ON public.collections(deleted);
+CREATE INDEX IF NOT EXISTS idx_searches_library ON public.searches(library_id, library_type);
+CREATE INDEX IF NOT EXISTS idx_searches_sync ON public.searches(sync);
 CREATE INDEX IF NOT EXISTS idx_tags_library ON public.tags(library_id, library_type);
+CREATE INDEX IF NOT EXISTS idx_fulltext_library ON public.fulltext(library_id, library_type);
+CREATE INDEX IF NOT EXISTS idx_attachments_status ON public.attachments(library_id, library_type, status);
+CREATE INDEX IF NOT EXISTS idx_attachment_blobs_md5 ON public.attachment_blobs(md5);
+CREATE INDEX IF NOT EXISTS idx_fulltext_content ON public.fulltext USING GIN (to_tsvector('simple', coalesce(content, '')));
 
 -- Create constraint name referenced in Go code for tags
 -- Drop the constraint first if it exists, then add it.
@@ -162,6 +312,13 @@ CREATE INDEX IF NOT EXISTS idx_sync_queue_pending
 ON public.sync_queue (library_id, library_type, next_retry_at)
 WHERE processed_at IS NULL AND retry_count < max_retries;
 
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sync_conflicts_open
+ON public.sync_conflicts (entity_type, entity_key, library_id, library_type)
+WHERE resolved_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_change_history_entity
+ON public.change_history (library_id, library_type, entity_type, entity_key, changed_at);
+
 CREATE INDEX IF NOT EXISTS idx_sync_queue_processed
 ON public.sync_queue (processed_at)
 WHERE processed_at IS NOT NULL;
@@ -224,8 +381,10 @@ BEGIN
         DO UPDATE SET next_retry_at = NOW(), retry_count = 0, processed_at = NULL;
 
     ELSIF TG_OP = 'UPDATE' THEN
-        -- Only enqueue if meaningful data changed (not just sync status)
-        IF NEW.data IS DISTINCT FROM OLD.data OR NEW.deleted != OLD.deleted THEN
+        -- Only enqueue if meaningful data changed (not just sync status);
+        -- a new md5 on an item means its attachment file was replaced
+        IF NEW.data IS DISTINCT FROM OLD.data OR NEW.deleted != OLD.deleted
+           OR to_jsonb(NEW)->'md5' IS DISTINCT FROM to_jsonb(OLD)->'md5' THEN
             INSERT INTO public.sync_queue (entity_type, entity_key, library_id, library_type, operation)
             VALUES (TG_ARGV[0], v_entity_key, v_library_id, v_library_type, 'update')
             ON CONFLICT (entity_type, entity_key, library_id, library_type, operation)
@@ -247,4 +406,95 @@ CREATE TRIGGER items_sync_queue_trigger
 DROP TRIGGER IF EXISTS collections_sync_queue_trigger ON public.collections;
 CREATE TRIGGER collections_sync_queue_trigger
     AFTER INSERT OR UPDATE OR DELETE ON public.collections
-    FOR EACH ROW EXECUTE FUNCTION public.enqueue_sync('collection'); 
\ No newline at end of file
+    FOR EACH ROW EXECUTE FUNCTION public.enqueue_sync('collection'); 
+
+-- Trigger for searches table
+DROP TRIGGER IF EXISTS searches_sync_queue_trigger ON public.searches;
+CREATE TRIGGER searches_sync_queue_trigger
+    AFTER INSERT OR UPDATE OR DELETE ON public.searches
+    FOR EACH ROW EXECUTE FUNCTION public.enqueue_sync('search');
+
+-- Trigger function recording data changes into change_history
+-- The writer identifies itself through the postero.change_source setting;
+-- anything that does not (PostgREST, psql) is recorded as a local edit.
+-- Soft-deleted rows count as absent.
+CREATE OR REPLACE FUNCTION public.record_change_history()
+RETURNS TRIGGER AS $$
+DECLARE
+    v_old jsonb;
+    v_new jsonb;
+    v_row RECORD;
+    v_operation VARCHAR(10);
+BEGIN
+    IF TG_OP = 'INSERT' OR OLD.deleted THEN
+        v_old := NULL;
+    ELSE
+        v_old := OLD.data;
+    END IF;
+
+    IF TG_OP = 'DELETE' OR NEW.deleted THEN
+        v_new := NULL;
+    ELSE
+        v_new := NEW.data;
+    END IF;
+
+    -- Ignore sync bookkeeping (status, version, snapshot)
+    IF v_old IS NOT DISTINCT FROM v_new THEN
+        RETURN COALESCE(NEW, OLD);
+    END IF;
+
+    IF v_old IS NULL THEN
+        v_operation := 'create';
+    ELSIF v_new IS NULL THEN
+        v_operation := 'delete';
+    ELSE
+        v_operation := 'update';
+    END IF;
+
+    IF TG_OP = 'DELETE' THEN
+        v_row := OLD;
+    ELSE
+        v_row := NEW;
+    END IF;
+
+    INSERT INTO public.change_history (entity_type, entity_key, library_id, library_type, operation, source, old_data, new_data)
+    VALUES (
+        TG_ARGV[0], v_row.key, v_row.library_id, v_row.library_type, v_operation,
+        COALESCE(NULLIF(current_setting('postero.change_source', true), ''), 'local'),
+        v_old, v_new
+    );
+
+    RETURN COALESCE(NEW, OLD);
+END;
+$$ LANGUAGE plpgsql;
+
+-- History triggers for items and collections
+DROP TRIGGER IF EXISTS items_change_history_trigger ON public.items;
+CREATE TRIGGER items_change_history_trigger
+    AFTER INSERT OR UPDATE OR DELETE ON public.items
+    FOR EACH ROW EXECUTE FUNCTION public.record_change_history('item');
+
+DROP TRIGGER IF EXISTS collections_change_history_trigger ON public.collections;
+CREATE TRIGGER collections_change_history_trigger
+    AFTER INSERT OR UPDATE OR DELETE ON public.collections
+    FOR EACH ROW EXECUTE FUNCTION public.record_change_history('collection');
+
+-- Trigger function keeping blobs.refcount in step with attachment_blobs
+CREATE OR REPLACE FUNCTION public.update_blob_refcount()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        UPDATE public.blobs SET refcount = refcount - 1 WHERE md5 = OLD.md5;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE public.blobs SET refcount = refcount + 1 WHERE md5 = NEW.md5;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS attachment_blobs_refcount_trigger ON public.attachment_blobs;
+CREATE TRIGGER attachment_blobs_refcount_trigger
+    AFTER INSERT OR UPDATE OF md5 OR DELETE ON public.attachment_blobs
+    FOR EACH ROW EXECUTE FUNCTION public.update_blob_refcount();
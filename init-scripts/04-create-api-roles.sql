@@ -33,9 +33,15 @@ GRANT SELECT ON public.item_type_hier TO api_anon;
 -- Grant permissions on tables for api_user (full CRUD access)
 GRANT SELECT, INSERT, UPDATE, DELETE ON public.items TO api_user;
 GRANT SELECT, INSERT, UPDATE, DELETE ON public.collections TO api_user;
+GRANT SELECT, INSERT, UPDATE, DELETE ON public.searches TO api_user;
 GRANT SELECT, INSERT, UPDATE, DELETE ON public.tags TO api_user;
 GRANT SELECT, INSERT, UPDATE, DELETE ON public.libraries TO api_user;
 GRANT SELECT ON public.sync_libraries TO api_user;
+GRANT SELECT ON public.fulltext TO api_user;
+GRANT SELECT ON public.attachments TO api_user;
+GRANT SELECT ON public.attachment_blobs TO api_user;
+GRANT SELECT ON public.sync_conflicts TO api_user;
+GRANT SELECT ON public.change_history TO api_user;
 
 -- Grant access to materialized views
 GRANT SELECT ON public.collection_name_hier TO api_user;
@@ -74,12 +80,46 @@ COMMENT ON COLUMN public.items.library_id IS 'Library ID this item belongs to';
 COMMENT ON COLUMN public.items.library_type IS 'Library type (user or group)';
 COMMENT ON COLUMN public.items.data IS 'JSON data containing item metadata (title, creators, etc.)';
 COMMENT ON COLUMN public.items.meta IS 'JSON metadata about the item sync status and processing';
+COMMENT ON COLUMN public.items.synced_data IS 'Snapshot of data as it was when last in agreement with Zotero';
+COMMENT ON COLUMN public.items.md5 IS 'MD5 of the stored attachment file; changing it queues the file for upload';
 
 COMMENT ON TABLE public.collections IS 'Stores Zotero collections and their hierarchical relationships';
 COMMENT ON COLUMN public.collections.key IS 'Unique 8-character Zotero collection key';
 COMMENT ON COLUMN public.collections.library_id IS 'Library ID this collection belongs to';
 COMMENT ON COLUMN public.collections.library_type IS 'Library type (user or group)';
 COMMENT ON COLUMN public.collections.data IS 'JSON data containing collection metadata (name, parent, etc.)';
+COMMENT ON COLUMN public.collections.synced_data IS 'Snapshot of data as it was when last in agreement with Zotero';
+
+COMMENT ON TABLE public.searches IS 'Stores Zotero saved searches; rows may be written locally and are uploaded to Zotero';
+COMMENT ON COLUMN public.searches.key IS 'Unique 8-character Zotero search key';
+COMMENT ON COLUMN public.searches.data IS 'JSON data containing the search name and its conditions';
+
+COMMENT ON TABLE public.fulltext IS 'Full-text content Zotero extracted from attachment files';
+COMMENT ON COLUMN public.fulltext.item_key IS 'Key of the attachment item the content belongs to';
+COMMENT ON COLUMN public.fulltext.version IS 'Zotero full-text version of the item';
+
+COMMENT ON TABLE public.attachments IS 'Storage location and last transfer of each attachment file';
+COMMENT ON COLUMN public.attachments.storage_folder IS 'Bucket (or top-level folder) holding the file';
+COMMENT ON COLUMN public.attachments.storage_name IS 'Object key of the file within storage_folder';
+COMMENT ON COLUMN public.attachments.md5 IS 'MD5 of the stored file after the last successful transfer';
+COMMENT ON COLUMN public.attachments.status IS 'Last transfer: downloaded, uploaded, missing (no file in Zotero), download_failed or upload_failed';
+COMMENT ON COLUMN public.attachments.last_error IS 'Error of the last failed transfer';
+COMMENT ON COLUMN public.attachments.attempts IS 'Consecutive failed transfers; failed downloads are retried on every sync';
+
+COMMENT ON TABLE public.blobs IS 'Attachment files in content-addressed storage, stored once per MD5';
+COMMENT ON COLUMN public.blobs.refcount IS 'Number of attachment items referring to the blob; blobs at zero are deleted after the next sync';
+COMMENT ON TABLE public.attachment_blobs IS 'Blob holding the file of each attachment item in content-addressed storage';
+
+COMMENT ON TABLE public.sync_conflicts IS 'Uploads rejected by Zotero (412) that the library conflict policy could not resolve';
+COMMENT ON COLUMN public.sync_conflicts.entity_type IS 'item, collection or file (attachment file of an item)';
+COMMENT ON COLUMN public.sync_conflicts.base_data IS 'Last synced snapshot of the object, the common ancestor of the merge';
+COMMENT ON COLUMN public.sync_conflicts.fields IS 'Fields changed on both sides, with base, local and remote values';
+COMMENT ON COLUMN public.sync_conflicts.resolved_at IS 'Set once the conflict has been handled; open conflicts have NULL';
+
+COMMENT ON TABLE public.change_history IS 'Every data change to items and collections, with its source, for audit and point-in-time restore';
+COMMENT ON COLUMN public.change_history.source IS 'Who made the change: cloud (download from Zotero), local (direct edit) or worker (sync worker)';
+COMMENT ON COLUMN public.change_history.old_data IS 'Data before the change; NULL if the object did not exist or was deleted';
+COMMENT ON COLUMN public.change_history.new_data IS 'Data after the change; NULL if the object was deleted';
 
 COMMENT ON TABLE public.tags IS 'Stores tags associated with items in Zotero libraries';
 COMMENT ON COLUMN public.tags.tag IS 'The tag name/text';
@@ -98,6 +98,48 @@ SELECT
     c.meta as meta_data
 FROM public.collections c;
 
+-- Saved searches view with conditions exposed as JSONB
+CREATE OR REPLACE VIEW public.searches_view AS
+SELECT
+    s.key,
+    s.library_id,
+    s.library_type,
+    s.version,
+    s.sync,
+    s.deleted,
+    s.modified,
+    s.data->>'name' as name,
+    s.data->'conditions' as conditions,
+    -- Keep full JSON data for complete access
+    s.data as full_data
+FROM public.searches s;
+
+-- Attachment files with their storage location and transfer state
+CREATE OR REPLACE VIEW public.attachments_view AS
+SELECT
+    a.item_key as key,
+    a.library_id,
+    a.library_type,
+    i.data->>'parentItem' as parent_item,
+    i.data->>'title' as title,
+    i.data->>'filename' as filename,
+    i.data->>'linkMode' as link_mode,
+    a.storage_folder,
+    a.storage_name,
+    a.size,
+    a.md5,
+    a.mtime,
+    a.content_type,
+    a.status,
+    a.last_error,
+    a.attempts,
+    a.transferred_at,
+    -- Zotero holds a different file than the one last transferred
+    (a.status IN ('downloaded', 'uploaded') AND a.md5 IS DISTINCT FROM i.data->>'md5') as stale
+FROM public.attachments a
+JOIN public.items i
+  ON i.key = a.item_key AND i.library_id = a.library_id AND i.library_type = a.library_type;
+
 -- Enhanced tags view
 CREATE OR REPLACE VIEW public.tags_view AS
 SELECT
@@ -193,6 +235,8 @@ GRANT SELECT ON public.items_view TO api_anon, api_user;
 GRANT SELECT ON public.collections_view TO api_anon, api_user;
 GRANT SELECT ON public.libraries_view TO api_anon, api_user;
 GRANT SELECT ON public.tags_view TO api_anon, api_user;
+GRANT SELECT ON public.searches_view TO api_anon, api_user;
+GRANT SELECT ON public.attachments_view TO api_user;
 
 -- Grant execute permissions on functions
 GRANT EXECUTE ON FUNCTION public.get_collection_by_name(bigint, public.library_type, text, text) TO api_anon, api_user;
@@ -204,6 +248,8 @@ COMMENT ON VIEW public.libraries_view IS 'Unified view of libraries (both user a
 COMMENT ON VIEW public.items_view IS 'Flattened view of items with commonly used fields extracted from JSON data for easy API access';
 COMMENT ON VIEW public.collections_view IS 'Flattened view of collections with commonly used fields extracted from JSON data';
 COMMENT ON VIEW public.tags_view IS 'Simple view of tags with library association';
+COMMENT ON VIEW public.searches_view IS 'Saved searches with their conditions as JSONB';
+COMMENT ON VIEW public.attachments_view IS 'Attachment files with storage location, last transfer status and whether the stored copy is stale';
 
 COMMENT ON FUNCTION public.get_collection_by_name(bigint, public.library_type, text, text) IS 'Find a collection by name within a library, optionally scoped by parent collection';
 COMMENT ON FUNCTION public.get_item_by_oldid(bigint, public.library_type, text) IS 'Find an item by its old ID for backward compatibility';
@@ -3,8 +3,14 @@
 -- Enable RLS on main tables
 ALTER TABLE public.items ENABLE ROW LEVEL SECURITY;
 ALTER TABLE public.collections ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.searches ENABLE ROW LEVEL SECURITY;
 ALTER TABLE public.tags ENABLE ROW LEVEL SECURITY;
 ALTER TABLE public.libraries ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.sync_conflicts ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.change_history ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.attachments ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.attachment_blobs ENABLE ROW LEVEL SECURITY;
+ALTER TABLE public.fulltext ENABLE ROW LEVEL SECURITY;
 
 -- Create RLS policies for items table
 -- Policy: Users can only access items belonging to their library_id and library_type
@@ -76,6 +82,108 @@ USING (
     )
 );
 
+-- Create RLS policies for searches table
+DROP POLICY IF EXISTS searches_library_isolation ON public.searches;
+CREATE POLICY searches_library_isolation
+ON public.searches
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
+
+-- Create RLS policies for sync_conflicts table
+DROP POLICY IF EXISTS sync_conflicts_library_isolation ON public.sync_conflicts;
+CREATE POLICY sync_conflicts_library_isolation
+ON public.sync_conflicts
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
+
+-- Create RLS policies for change_history table
+DROP POLICY IF EXISTS change_history_library_isolation ON public.change_history;
+CREATE POLICY change_history_library_isolation
+ON public.change_history
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
+
+-- Create RLS policies for attachments table
+DROP POLICY IF EXISTS attachments_library_isolation ON public.attachments;
+CREATE POLICY attachments_library_isolation
+ON public.attachments
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
+
+-- Create RLS policies for attachment_blobs table
+DROP POLICY IF EXISTS attachment_blobs_library_isolation ON public.attachment_blobs;
+CREATE POLICY attachment_blobs_library_isolation
+ON public.attachment_blobs
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
+
+-- Create RLS policies for fulltext table
+DROP POLICY IF EXISTS fulltext_library_isolation ON public.fulltext;
+CREATE POLICY fulltext_library_isolation
+ON public.fulltext
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
+
 -- Create RLS policies for tags table
 DROP POLICY IF EXISTS tags_library_isolation ON public.tags;
 CREATE POLICY tags_library_isolation
@@ -157,11 +265,15 @@ SELECT * FROM public.tags_view;
 CREATE OR REPLACE VIEW public.secure_libraries_view AS
 SELECT * FROM public.libraries_view;
 
+CREATE OR REPLACE VIEW public.secure_attachments_view AS
+SELECT * FROM public.attachments_view;
+
 -- Grant permissions on secure views
 GRANT SELECT ON public.secure_items_view TO api_anon, api_user;
 GRANT SELECT ON public.secure_collections_view TO api_anon, api_user;
 GRANT SELECT ON public.secure_tags_view TO api_anon, api_user;
 GRANT SELECT ON public.secure_libraries_view TO api_anon, api_user;
+GRANT SELECT ON public.secure_attachments_view TO api_user;
 
 -- Function to set library context for API calls
 CREATE OR REPLACE FUNCTION public.set_library_context(p_library_id bigint, p_library_type public.library_type)
@@ -180,11 +292,18 @@ GRANT EXECUTE ON FUNCTION public.set_library_context(bigint, public.library_type
 -- Add comments for documentation
 COMMENT ON POLICY items_library_isolation ON public.items IS 'Ensures users can only access items from their authorized library';
 COMMENT ON POLICY collections_library_isolation ON public.collections IS 'Ensures users can only access collections from their authorized library';
+COMMENT ON POLICY searches_library_isolation ON public.searches IS 'Ensures users can only access saved searches from their authorized library';
 COMMENT ON POLICY tags_library_isolation ON public.tags IS 'Ensures users can only access tags from their authorized library';
+COMMENT ON POLICY sync_conflicts_library_isolation ON public.sync_conflicts IS 'Ensures users can only access sync conflicts from their authorized library';
+COMMENT ON POLICY change_history_library_isolation ON public.change_history IS 'Ensures users can only access change history from their authorized library';
+COMMENT ON POLICY attachments_library_isolation ON public.attachments IS 'Ensures users can only access attachment file state from their authorized library';
+COMMENT ON POLICY attachment_blobs_library_isolation ON public.attachment_blobs IS 'Ensures users can only access blob references from their authorized library';
+COMMENT ON POLICY fulltext_library_isolation ON public.fulltext IS 'Ensures users can only access full-text content from their authorized library';
 
 COMMENT ON VIEW public.secure_items_view IS 'RLS-protected view of items that automatically filters by user library access';
 COMMENT ON VIEW public.secure_collections_view IS 'RLS-protected view of collections that automatically filters by user library access';
 COMMENT ON VIEW public.secure_tags_view IS 'RLS-protected view of tags that automatically filters by user library access';
 COMMENT ON VIEW public.secure_libraries_view IS 'RLS-protected view of libraries that automatically filters by user library access';
+COMMENT ON VIEW public.secure_attachments_view IS 'RLS-protected view of attachment files that automatically filters by user library access';
 
 COMMENT ON FUNCTION public.set_library_context(bigint, public.library_type) IS 'Sets the current library context for RLS policies when JWT claims are not available'; 
\ No newline at end of file
@@ -0,0 +1,48 @@
+-- Migration: Saved searches
+-- Adds the search_version cursor to libraries, the searches table, and its API view
+
+-- Step 1: Add search_version column to libraries if it doesn't exist
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'libraries' AND column_name = 'search_version' AND table_schema = 'public') THEN
+        ALTER TABLE public.libraries ADD COLUMN search_version bigint DEFAULT 0;
+    END IF;
+END$$;
+
+-- Step 2: Create searches table if not exists
+CREATE TABLE IF NOT EXISTS public.searches (
+    key varchar(8) NOT NULL,
+    version bigint DEFAULT 0 NOT NULL,
+    library_id bigint NOT NULL,
+    library_type public.library_type NOT NULL,
+    sync public.syncstatus DEFAULT 'new' NOT NULL,
+    data jsonb,
+    deleted boolean DEFAULT false NOT NULL,
+    modified timestamp with time zone DEFAULT NOW(),
+    PRIMARY KEY (key, library_id, library_type),
+    FOREIGN KEY (library_id, library_type) REFERENCES public.libraries(id, library_type)
+);
+
+-- Step 3: Create indexes for searches
+CREATE INDEX IF NOT EXISTS idx_searches_library ON public.searches(library_id, library_type);
+CREATE INDEX IF NOT EXISTS idx_searches_sync ON public.searches(sync);
+
+-- Step 4: Create API view
+CREATE OR REPLACE VIEW public.searches_view AS
+SELECT
+    s.key,
+    s.library_id,
+    s.library_type,
+    s.version,
+    s.sync,
+    s.deleted,
+    s.modified,
+    s.data->>'name' as name,
+    s.data->'conditions' as conditions,
+    s.data as full_data
+FROM public.searches s;
+
+GRANT SELECT ON public.searches_view TO api_anon, api_user;
+
+COMMENT ON VIEW public.searches_view IS 'Saved searches with their conditions as JSONB';
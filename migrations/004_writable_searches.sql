@@ -0,0 +1,30 @@
+-- Migration: Locally writable saved searches
+-- Lets api_user write to the searches table and enqueues search changes for event-driven sync
+
+-- Step 1: Allow api_user to write saved searches
+GRANT SELECT, INSERT, UPDATE, DELETE ON public.searches TO api_user;
+
+-- Step 2: Restrict api_user to searches of its own library
+ALTER TABLE public.searches ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS searches_library_isolation ON public.searches;
+CREATE POLICY searches_library_isolation
+ON public.searches
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
+
+-- Step 3: Create trigger (drop first to handle re-running migration)
+DROP TRIGGER IF EXISTS searches_sync_queue_trigger ON public.searches;
+CREATE TRIGGER searches_sync_queue_trigger
+    AFTER INSERT OR UPDATE OR DELETE ON public.searches
+    FOR EACH ROW EXECUTE FUNCTION public.enqueue_sync('search');
@@ -0,0 +1,53 @@
+-- Migration: Full-text content
+-- Adds the fulltext_version cursor to libraries and the fulltext table linked to items
+
+-- Step 1: Add fulltext_version column to libraries if it doesn't exist
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'libraries' AND column_name = 'fulltext_version' AND table_schema = 'public') THEN
+        ALTER TABLE public.libraries ADD COLUMN fulltext_version bigint DEFAULT 0;
+    END IF;
+END$$;
+
+-- Step 2: Create fulltext table if not exists
+CREATE TABLE IF NOT EXISTS public.fulltext (
+    item_key varchar(8) NOT NULL,
+    library_id bigint NOT NULL,
+    library_type public.library_type NOT NULL,
+    version bigint DEFAULT 0 NOT NULL,
+    content text,
+    indexed_pages integer,
+    total_pages integer,
+    indexed_chars integer,
+    total_chars integer,
+    modified timestamp with time zone DEFAULT NOW(),
+    PRIMARY KEY (item_key, library_id, library_type),
+    FOREIGN KEY (item_key, library_id, library_type) REFERENCES public.items(key, library_id, library_type) ON DELETE CASCADE
+);
+
+-- Step 3: Create indexes for fulltext
+CREATE INDEX IF NOT EXISTS idx_fulltext_library ON public.fulltext(library_id, library_type);
+CREATE INDEX IF NOT EXISTS idx_fulltext_content ON public.fulltext USING GIN (to_tsvector('simple', coalesce(content, '')));
+
+-- Step 4: Read access for the API
+GRANT SELECT ON public.fulltext TO api_user;
+
+-- Step 5: Restrict api_user to full-text content of its own library
+ALTER TABLE public.fulltext ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS fulltext_library_isolation ON public.fulltext;
+CREATE POLICY fulltext_library_isolation
+ON public.fulltext
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
@@ -0,0 +1,68 @@
+-- Migration: Conflict resolution for rejected uploads
+-- Adds a per-library conflict policy and the sync_conflicts table
+
+-- Step 1: Create conflictpolicy enum
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'conflictpolicy') THEN
+        CREATE TYPE public.conflictpolicy AS ENUM (
+            'local_wins',
+            'remote_wins',
+            'merge',
+            'manual'
+        );
+    END IF;
+END$$;
+
+-- Step 2: Add conflict_policy column to sync_libraries
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'sync_libraries' AND column_name = 'conflict_policy' AND table_schema = 'public') THEN
+        ALTER TABLE public.sync_libraries ADD COLUMN conflict_policy public.conflictpolicy DEFAULT 'manual' NOT NULL;
+    END IF;
+END$$;
+
+-- Step 3: Create sync_conflicts table
+CREATE TABLE IF NOT EXISTS public.sync_conflicts (
+    id BIGSERIAL PRIMARY KEY,
+    entity_type VARCHAR(20) NOT NULL,  -- 'item', 'collection'
+    entity_key VARCHAR(8) NOT NULL,
+    library_id BIGINT NOT NULL,
+    library_type public.library_type NOT NULL,
+    policy public.conflictpolicy NOT NULL,
+    base_data jsonb,                   -- last synced snapshot
+    local_data jsonb,
+    remote_data jsonb,                 -- NULL when the object was deleted in Zotero
+    remote_version bigint,
+    fields jsonb,                      -- conflicting fields with base/local/remote values
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    resolved_at TIMESTAMP WITH TIME ZONE,
+    FOREIGN KEY (library_id, library_type) REFERENCES public.libraries(id, library_type) ON DELETE CASCADE
+);
+
+-- Step 4: At most one open conflict per object
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sync_conflicts_open
+ON public.sync_conflicts (entity_type, entity_key, library_id, library_type)
+WHERE resolved_at IS NULL;
+
+-- Step 5: Read access for the API, restricted to the caller's library
+GRANT SELECT ON public.sync_conflicts TO api_user;
+
+ALTER TABLE public.sync_conflicts ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS sync_conflicts_library_isolation ON public.sync_conflicts;
+CREATE POLICY sync_conflicts_library_isolation
+ON public.sync_conflicts
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
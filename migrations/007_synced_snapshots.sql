@@ -0,0 +1,20 @@
+-- Migration: Last-synced snapshots
+-- Keeps a copy of each item and collection as it was when last in agreement with Zotero,
+-- for three-way diffs and conflict resolution
+
+-- Step 1: Add synced_data snapshot columns
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'items' AND column_name = 'synced_data' AND table_schema = 'public') THEN
+        ALTER TABLE public.items ADD COLUMN synced_data jsonb;
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM information_schema.columns
+                   WHERE table_name = 'collections' AND column_name = 'synced_data' AND table_schema = 'public') THEN
+        ALTER TABLE public.collections ADD COLUMN synced_data jsonb;
+    END IF;
+END$$;
+
+-- Step 2: Seed snapshots from rows that are currently in agreement with Zotero
+UPDATE public.items SET synced_data = data WHERE sync = 'synced' AND synced_data IS NULL;
+UPDATE public.collections SET synced_data = data WHERE sync = 'synced' AND synced_data IS NULL;
@@ -0,0 +1,106 @@
+-- Migration: Change history
+-- Records every data change to items and collections with its source for audit and restore
+
+-- Step 1: Create change_history table
+CREATE TABLE IF NOT EXISTS public.change_history (
+    id BIGSERIAL PRIMARY KEY,
+    entity_type VARCHAR(20) NOT NULL,  -- 'item', 'collection'
+    entity_key VARCHAR(8) NOT NULL,
+    library_id BIGINT NOT NULL,
+    library_type public.library_type NOT NULL,
+    operation VARCHAR(10) NOT NULL,    -- 'create', 'update', 'delete'
+    source VARCHAR(10) NOT NULL,       -- 'cloud', 'local', 'worker'
+    old_data jsonb,                    -- NULL when the object did not exist before
+    new_data jsonb,                    -- NULL when the object was deleted
+    changed_at TIMESTAMP WITH TIME ZONE DEFAULT clock_timestamp() NOT NULL,
+    FOREIGN KEY (library_id, library_type) REFERENCES public.libraries(id, library_type) ON DELETE CASCADE
+);
+
+-- Step 2: Create index for point-in-time lookups
+CREATE INDEX IF NOT EXISTS idx_change_history_entity
+ON public.change_history (library_id, library_type, entity_type, entity_key, changed_at);
+
+-- Step 3: Create trigger function and triggers
+-- Writers identify themselves through postero.change_source; anything else is a local edit.
+-- Soft-deleted rows count as absent.
+CREATE OR REPLACE FUNCTION public.record_change_history()
+RETURNS TRIGGER AS $$
+DECLARE
+    v_old jsonb;
+    v_new jsonb;
+    v_row RECORD;
+    v_operation VARCHAR(10);
+BEGIN
+    IF TG_OP = 'INSERT' OR OLD.deleted THEN
+        v_old := NULL;
+    ELSE
+        v_old := OLD.data;
+    END IF;
+
+    IF TG_OP = 'DELETE' OR NEW.deleted THEN
+        v_new := NULL;
+    ELSE
+        v_new := NEW.data;
+    END IF;
+
+    -- Ignore sync bookkeeping (status, version, snapshot)
+    IF v_old IS NOT DISTINCT FROM v_new THEN
+        RETURN COALESCE(NEW, OLD);
+    END IF;
+
+    IF v_old IS NULL THEN
+        v_operation := 'create';
+    ELSIF v_new IS NULL THEN
+        v_operation := 'delete';
+    ELSE
+        v_operation := 'update';
+    END IF;
+
+    IF TG_OP = 'DELETE' THEN
+        v_row := OLD;
+    ELSE
+        v_row := NEW;
+    END IF;
+
+    INSERT INTO public.change_history (entity_type, entity_key, library_id, library_type, operation, source, old_data, new_data)
+    VALUES (
+        TG_ARGV[0], v_row.key, v_row.library_id, v_row.library_type, v_operation,
+        COALESCE(NULLIF(current_setting('postero.change_source', true), ''), 'local'),
+        v_old, v_new
+    );
+
+    RETURN COALESCE(NEW, OLD);
+END;
+$$ LANGUAGE plpgsql;
+
+-- History triggers for items and collections
+DROP TRIGGER IF EXISTS items_change_history_trigger ON public.items;
+CREATE TRIGGER items_change_history_trigger
+    AFTER INSERT OR UPDATE OR DELETE ON public.items
+    FOR EACH ROW EXECUTE FUNCTION public.record_change_history('item');
+
+DROP TRIGGER IF EXISTS collections_change_history_trigger ON public.collections;
+CREATE TRIGGER collections_change_history_trigger
+    AFTER INSERT OR UPDATE OR DELETE ON public.collections
+    FOR EACH ROW EXECUTE FUNCTION public.record_change_history('collection');
+
+-- Step 4: Read access for the API, restricted to the caller's library
+GRANT SELECT ON public.change_history TO api_user;
+
+ALTER TABLE public.change_history ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS change_history_library_isolation ON public.change_history;
+CREATE POLICY change_history_library_isolation
+ON public.change_history
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
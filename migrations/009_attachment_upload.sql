@@ -0,0 +1,66 @@
+-- Migration: Attachment file upload
+-- Queues items for sync when only their attachment file (md5) changed
+
+-- Step 1: Re-create trigger function to react to md5 changes
+CREATE OR REPLACE FUNCTION public.enqueue_sync()
+RETURNS TRIGGER AS $$
+DECLARE
+    v_outgoing_sync public.syncmode;
+    v_library_id BIGINT;
+    v_library_type public.library_type;
+    v_entity_key VARCHAR(8);
+BEGIN
+    -- Determine library info from NEW or OLD record
+    IF TG_OP = 'DELETE' THEN
+        v_library_id := OLD.library_id;
+        v_library_type := OLD.library_type;
+        v_entity_key := OLD.key;
+    ELSE
+        v_library_id := NEW.library_id;
+        v_library_type := NEW.library_type;
+        v_entity_key := NEW.key;
+    END IF;
+
+    -- Check if event-driven sync is enabled for this library
+    SELECT outgoing_sync INTO v_outgoing_sync
+    FROM public.sync_libraries
+    WHERE library_id = v_library_id
+      AND library_type = v_library_type;
+
+    -- Only enqueue if event_driven mode is enabled
+    IF v_outgoing_sync IS NULL OR v_outgoing_sync != 'event_driven' THEN
+        RETURN COALESCE(NEW, OLD);
+    END IF;
+
+    -- Enqueue based on operation type
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO public.sync_queue (entity_type, entity_key, library_id, library_type, operation)
+        VALUES (TG_ARGV[0], v_entity_key, v_library_id, v_library_type, 'delete')
+        ON CONFLICT (entity_type, entity_key, library_id, library_type, operation)
+        DO UPDATE SET next_retry_at = NOW(), retry_count = 0, processed_at = NULL;
+
+    ELSIF TG_OP = 'INSERT' THEN
+        INSERT INTO public.sync_queue (entity_type, entity_key, library_id, library_type, operation)
+        VALUES (TG_ARGV[0], v_entity_key, v_library_id, v_library_type, 'create')
+        ON CONFLICT (entity_type, entity_key, library_id, library_type, operation)
+        DO UPDATE SET next_retry_at = NOW(), retry_count = 0, processed_at = NULL;
+
+    ELSIF TG_OP = 'UPDATE' THEN
+        -- Only enqueue if meaningful data changed (not just sync status);
+        -- a new md5 on an item means its attachment file was replaced
+        IF NEW.data IS DISTINCT FROM OLD.data OR NEW.deleted != OLD.deleted
+           OR to_jsonb(NEW)->'md5' IS DISTINCT FROM to_jsonb(OLD)->'md5' THEN
+            INSERT INTO public.sync_queue (entity_type, entity_key, library_id, library_type, operation)
+            VALUES (TG_ARGV[0], v_entity_key, v_library_id, v_library_type, 'update')
+            ON CONFLICT (entity_type, entity_key, library_id, library_type, operation)
+            DO UPDATE SET next_retry_at = NOW(), retry_count = 0, processed_at = NULL;
+        END IF;
+    END IF;
+
+    RETURN COALESCE(NEW, OLD);
+END;
+$$ LANGUAGE plpgsql;
+
+-- Step 2: Document file conflicts
+COMMENT ON COLUMN public.items.md5 IS 'MD5 of the stored attachment file; changing it queues the file for upload';
+COMMENT ON COLUMN public.sync_conflicts.entity_type IS 'item, collection or file (attachment file of an item)';
@@ -0,0 +1,88 @@
+-- Migration: Attachment file state
+-- Adds the attachments table recording storage location and last transfer of each file, and its API view
+
+-- Step 1: Create attachments table
+CREATE TABLE IF NOT EXISTS public.attachments (
+    item_key varchar(8) NOT NULL,
+    library_id bigint NOT NULL,
+    library_type public.library_type NOT NULL,
+    storage_folder text NOT NULL,      -- bucket
+    storage_name text NOT NULL,        -- object key within the bucket
+    size bigint,
+    md5 varchar(32),                   -- MD5 of the stored file
+    mtime bigint,                      -- modification time reported by Zotero, in milliseconds
+    content_type text,
+    status VARCHAR(20) NOT NULL,       -- 'downloaded', 'uploaded', 'missing', 'download_failed', 'upload_failed'
+    last_error text,
+    attempts integer DEFAULT 0 NOT NULL,
+    transferred_at timestamp with time zone DEFAULT NOW(),
+    PRIMARY KEY (item_key, library_id, library_type),
+    FOREIGN KEY (item_key, library_id, library_type) REFERENCES public.items(key, library_id, library_type) ON DELETE CASCADE
+);
+
+-- Step 2: Create index for retrying failed downloads
+CREATE INDEX IF NOT EXISTS idx_attachments_status ON public.attachments(library_id, library_type, status);
+
+-- Step 3: Create API view
+CREATE OR REPLACE VIEW public.attachments_view AS
+SELECT
+    a.item_key as key,
+    a.library_id,
+    a.library_type,
+    i.data->>'parentItem' as parent_item,
+    i.data->>'title' as title,
+    i.data->>'filename' as filename,
+    i.data->>'linkMode' as link_mode,
+    a.storage_folder,
+    a.storage_name,
+    a.size,
+    a.md5,
+    a.mtime,
+    a.content_type,
+    a.status,
+    a.last_error,
+    a.attempts,
+    a.transferred_at,
+    -- Zotero holds a different file than the one last transferred
+    (a.status IN ('downloaded', 'uploaded') AND a.md5 IS DISTINCT FROM i.data->>'md5') as stale
+FROM public.attachments a
+JOIN public.items i
+  ON i.key = a.item_key AND i.library_id = a.library_id AND i.library_type = a.library_type;
+
+GRANT SELECT ON public.attachments_view TO api_user;
+
+CREATE OR REPLACE VIEW public.secure_attachments_view AS
+SELECT * FROM public.attachments_view;
+
+GRANT SELECT ON public.secure_attachments_view TO api_user;
+
+-- Step 4: Read access for the API, restricted to the caller's library
+GRANT SELECT ON public.attachments TO api_user;
+
+ALTER TABLE public.attachments ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS attachments_library_isolation ON public.attachments;
+CREATE POLICY attachments_library_isolation
+ON public.attachments
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
+
+-- Step 5: Documentation
+COMMENT ON TABLE public.attachments IS 'Storage location and last transfer of each attachment file';
+COMMENT ON COLUMN public.attachments.storage_folder IS 'Bucket (or top-level folder) holding the file';
+COMMENT ON COLUMN public.attachments.storage_name IS 'Object key of the file within storage_folder';
+COMMENT ON COLUMN public.attachments.md5 IS 'MD5 of the stored file after the last successful transfer';
+COMMENT ON COLUMN public.attachments.status IS 'Last transfer: downloaded, uploaded, missing (no file in Zotero), download_failed or upload_failed';
+COMMENT ON COLUMN public.attachments.last_error IS 'Error of the last failed transfer';
+COMMENT ON COLUMN public.attachments.attempts IS 'Consecutive failed transfers; failed downloads are retried on every sync';
+COMMENT ON VIEW public.attachments_view IS 'Attachment files with storage location, last transfer status and whether the stored copy is stale';
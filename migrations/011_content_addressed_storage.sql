@@ -0,0 +1,67 @@
+-- Migration: Content-addressed attachment storage
+-- Adds the blobs table with reference counts and the attachment_blobs reference table
+
+-- Step 1: Create blob tables
+CREATE TABLE IF NOT EXISTS public.blobs (
+    md5 varchar(32) PRIMARY KEY,
+    size bigint,
+    refcount integer DEFAULT 0 NOT NULL,  -- maintained by the attachment_blobs trigger
+    created timestamp with time zone DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS public.attachment_blobs (
+    item_key varchar(8) NOT NULL,
+    library_id bigint NOT NULL,
+    library_type public.library_type NOT NULL,
+    md5 varchar(32) NOT NULL REFERENCES public.blobs(md5),
+    PRIMARY KEY (item_key, library_id, library_type),
+    FOREIGN KEY (item_key, library_id, library_type) REFERENCES public.items(key, library_id, library_type) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_attachment_blobs_md5 ON public.attachment_blobs(md5);
+
+-- Step 2: Keep reference counts in step with attachment_blobs
+CREATE OR REPLACE FUNCTION public.update_blob_refcount()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        UPDATE public.blobs SET refcount = refcount - 1 WHERE md5 = OLD.md5;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE public.blobs SET refcount = refcount + 1 WHERE md5 = NEW.md5;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS attachment_blobs_refcount_trigger ON public.attachment_blobs;
+CREATE TRIGGER attachment_blobs_refcount_trigger
+    AFTER INSERT OR UPDATE OF md5 OR DELETE ON public.attachment_blobs
+    FOR EACH ROW EXECUTE FUNCTION public.update_blob_refcount();
+
+-- Step 3: Read access for the API, restricted to the caller's library
+GRANT SELECT ON public.attachment_blobs TO api_user;
+
+ALTER TABLE public.attachment_blobs ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS attachment_blobs_library_isolation ON public.attachment_blobs;
+CREATE POLICY attachment_blobs_library_isolation
+ON public.attachment_blobs
+FOR ALL
+TO api_user
+USING (
+    library_id = COALESCE(
+        current_setting('request.jwt.claims.library_id', true)::bigint,
+        current_setting('app.current_library_id', true)::bigint
+    ) AND
+    library_type = COALESCE(
+        current_setting('request.jwt.claims.library_type', true)::public.library_type,
+        current_setting('app.current_library_type', true)::public.library_type
+    )
+);
+
+-- Step 4: Documentation
+COMMENT ON TABLE public.blobs IS 'Attachment files in content-addressed storage, stored once per MD5';
+COMMENT ON COLUMN public.blobs.refcount IS 'Number of attachment items referring to the blob; blobs at zero are deleted after the next sync';
+COMMENT ON TABLE public.attachment_blobs IS 'Blob holding the file of each attachment item in content-addressed storage';
@@ -0,0 +1,142 @@
+use postero::{
+    config::Config,
+    filesystem,
+    zotero::{AttachmentDownloads, AttachmentLayout, ApiClaims},
+    Error,
+    Result,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use clap::{Arg, Command};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn, error};
+
+struct AppState {
+    downloads: AttachmentDownloads,
+    jwt_secret: String,
+}
+
+#[derive(Deserialize)]
+struct DownloadQuery {
+    /// Token for plain links, which cannot carry an `Authorization` header
+    token: Option<String>,
+}
+
+/// Redirect to a presigned URL of an attachment's file
+async fn download(
+    State(state): State<Arc<AppState>>,
+    Path(item_key): Path<String>,
+    Query(query): Query<DownloadQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let Some(token) = bearer.or(query.token.as_deref()) else {
+        return (StatusCode::UNAUTHORIZED, "Missing token").into_response();
+    };
+
+    let claims = match ApiClaims::verify(token, &state.jwt_secret) {
+        Ok(claims) => claims,
+        Err(e) => return (StatusCode::UNAUTHORIZED, e.to_string()).into_response(),
+    };
+
+    match state.downloads.presigned_url(&claims, &item_key).await {
+        Ok(url) => {
+            info!("Download of {} by {} library {}", item_key, claims.library_type, claims.library_id);
+            ([(header::CACHE_CONTROL, "no-store")], Redirect::temporary(&url)).into_response()
+        }
+        Err(Error::NotFound(_)) => (StatusCode::NOT_FOUND, "Not found").into_response(),
+        Err(e @ Error::Validation(_)) => {
+            warn!("Download of {} refused: {}", item_key, e);
+            (StatusCode::FORBIDDEN, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!("Cannot presign download of {}: {}", item_key, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let matches = Command::new("postero-file-server")
+        .version("1.0")
+        .about("Redirects API clients to presigned attachment downloads")
+        .arg(
+            Arg::new("config")
+                .short('c')
+                .long("config")
+                .value_name("FILE")
+                .help("Configuration file path")
+        )
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .value_name("ADDRESS")
+                .help("Address to listen on (default: [file_server] listen, or 0.0.0.0:3001)")
+        )
+        .get_matches();
+
+    // Load configuration
+    let config_file = matches.get_one::<String>("config")
+        .map(|s| s.as_str())
+        .unwrap_or("postero.toml");
+
+    let config = Config::load(config_file)?;
+    let server_config = config.file_server.as_ref()
+        .ok_or_else(|| Error::InvalidData("The file server needs a [file_server] section".to_string()))?;
+
+    // Initialize logging
+    let log_level = match config.loglevel() {
+        "debug" => tracing::Level::DEBUG,
+        "warn" => tracing::Level::WARN,
+        "error" => tracing::Level::ERROR,
+        _ => tracing::Level::INFO,
+    };
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(log_level)
+        .with_target(false)
+        .finish();
+
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("Failed to set tracing subscriber");
+
+    // Connect to database; the server only reads
+    let db = PgPool::connect(&config.db.dsn).await?;
+    sqlx::query("SELECT 1").fetch_one(&db).await?;
+    info!("Database connection established");
+
+    let fs = filesystem::from_config(&config).await?;
+    let layout = AttachmentLayout::from_config(&config)?;
+
+    let expires_in = Duration::from_secs(server_config.url_expiry.unwrap_or(300));
+    let state = Arc::new(AppState {
+        downloads: AttachmentDownloads::new(db, config.db.schema.clone(), fs, layout, expires_in),
+        jwt_secret: server_config.jwt_secret.clone(),
+    });
+
+    let app = Router::new()
+        .route("/items/:key/file", get(download))
+        .with_state(state);
+
+    let listen = matches.get_one::<String>("listen")
+        .map(|s| s.as_str())
+        .or(server_config.listen.as_deref())
+        .unwrap_or("0.0.0.0:3001");
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    info!("File server listening on {}", listen);
+
+    axum::serve(listener, app).await?;
+    Ok(())
+}
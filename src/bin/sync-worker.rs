@@ -1,15 +1,13 @@
 use postero::{
     config::Config,
-    filesystem::S3FileSystem,
-    zotero::{ZoteroClient, sync_worker::{SyncWorker, SyncWorkerConfig}},
+    filesystem,
+    zotero::{ZoteroClient, ChangeSource, AttachmentLayout, ZoteroWebDav, sync_worker::{SyncWorker, SyncWorkerConfig}},
     Result,
 };
 use clap::{Arg, Command};
-use sqlx::PgPool;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{info, error};
-use tracing_subscriber;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -78,31 +76,29 @@ async fn main() -> Result<()> {
         .expect("Failed to set tracing subscriber");
 
     // Connect to database
-    let db = PgPool::connect(&config.db.dsn).await?;
+    let db = ChangeSource::Worker.connect(&config.db.dsn).await?;
 
     // Test database connection
     sqlx::query("SELECT 1").fetch_one(&db).await?;
     info!("Database connection established");
 
     // Initialize filesystem
-    let fs = Arc::new(
-        S3FileSystem::new(
-            &config.s3.endpoint,
-            &config.s3.access_key_id,
-            &config.s3.secret_access_key,
-            config.s3.use_ssl,
-        ).await?
-    ) as Arc<dyn postero::filesystem::FileSystem>;
+    let fs = filesystem::from_config(&config).await?;
+    let layout = AttachmentLayout::from_config(&config)?;
+    layout.ensure_bucket(fs.as_ref()).await?;
 
     // Create Zotero client
-    let client = Arc::new(ZoteroClient::new(
+    let mut client = ZoteroClient::new(
         &config.endpoint,
         &config.apikey,
         db.clone(),
         fs.clone(),
         &config.db.schema,
         config.new_group_active(),
-    ).await?);
+    ).await?;
+    client.set_attachment_layout(layout);
+    client.set_webdav(config.zotero_webdav.as_ref().map(ZoteroWebDav::from_config).transpose()?);
+    let client = Arc::new(client);
 
     info!("Zotero client initialized");
 
@@ -1,15 +1,14 @@
 use postero::{
     config::Config,
-    filesystem::S3FileSystem,
-    zotero::{ZoteroClient, SyncMode},
+    filesystem,
+    zotero::{ZoteroClient, SyncMode, LibraryType, ChangeSource, History, SyncConflicts, Resolution, AttachmentGc, AttachmentLayout, LayoutMigration, StorageVerifier, AttachmentVersions, ZoteroWebDav},
     Result,
     zotero::Library,
 };
-use clap::{Arg, Command};
+use clap::{Arg, ArgGroup, Command};
 use sqlx::PgPool;
 use std::sync::Arc;
-use tracing::{info, error};
-use tracing_subscriber;
+use tracing::{info, warn, error};
 
 /// Direction override for CLI --direction flag
 #[derive(Debug, Clone, Copy)]
@@ -43,9 +42,10 @@ async fn sync_data(
     config: &Config,
     db: &PgPool,
     fs: Arc<dyn postero::filesystem::FileSystem>,
+    layout: AttachmentLayout,
     direction_override: Option<DirectionOverride>,
 ) -> Result<()> {
-    let zotero = ZoteroClient::new(
+    let mut zotero = ZoteroClient::new(
         &config.endpoint,
         &config.apikey,
         db.clone(),
@@ -53,6 +53,8 @@ async fn sync_data(
         &config.db.schema,
         config.new_group_active(),
     ).await?;
+    zotero.set_attachment_layout(layout);
+    zotero.set_webdav(config.zotero_webdav.as_ref().map(ZoteroWebDav::from_config).transpose()?);
 
     info!("Current key: {:?}", zotero.current_key());
 
@@ -191,12 +193,15 @@ async fn sync_data(
                 // Preserve local sync state
                 new_library.collection_version = library.collection_version;
                 new_library.item_version = library.item_version;
+                new_library.search_version = library.search_version;
                 new_library.tag_version = library.tag_version;
+                new_library.fulltext_version = library.fulltext_version;
                 new_library.deleted = library.deleted;
                 new_library.active = library.active;
                 new_library.incoming_sync = library.incoming_sync;
                 new_library.outgoing_sync = library.outgoing_sync;
                 new_library.sync_tags = library.sync_tags;
+                new_library.conflict_policy = library.conflict_policy;
                 
                 // Set up database connection for update
                 new_library.db = Some(db.clone());
@@ -219,6 +224,268 @@ async fn sync_data(
     Ok(())
 }
 
+/// Restore a library or item from the change history and mark it for upload
+async fn restore(config: &Config, db: &PgPool, matches: &clap::ArgMatches) -> Result<()> {
+    let library_id = *matches.get_one::<i64>("library").expect("required by clap");
+    let library_type: LibraryType = matches.get_one::<String>("library-type")
+        .expect("has default")
+        .parse()
+        .map_err(postero::Error::InvalidData)?;
+    let at = *matches.get_one::<chrono::DateTime<chrono::Utc>>("at").expect("required by clap");
+
+    let history = History::new(db.clone(), config.db.schema.clone());
+
+    match matches.get_one::<String>("item") {
+        Some(item_key) => {
+            if history.restore_item(library_id, library_type, item_key, at).await? {
+                info!("Restored item {} of {} library {} to {}", item_key, library_type, library_id, at);
+            } else {
+                info!("Item {} of {} library {} has not changed since {}", item_key, library_type, library_id, at);
+            }
+        }
+        None => {
+            let restored = history.restore_library(library_id, library_type, at).await?;
+            info!("Restored {} objects of {} library {} to {}", restored, library_type, library_id, at);
+        }
+    }
+
+    info!("Run an outgoing sync to send the restored state to Zotero");
+    Ok(())
+}
+
+/// List unresolved sync conflicts, or resolve one of them
+async fn conflicts(config: &Config, db: &PgPool, matches: &clap::ArgMatches) -> Result<()> {
+    let conflicts = SyncConflicts::new(db.clone(), config.db.schema.clone());
+
+    match matches.subcommand() {
+        Some(("resolve", resolve_matches)) => {
+            let id = *resolve_matches.get_one::<i64>("id").expect("required by clap");
+            let resolution = match resolve_matches.get_one::<String>("data") {
+                Some(path) => {
+                    let content = std::fs::read_to_string(path)?;
+                    Resolution::Edited(serde_json::from_str(&content)?)
+                }
+                None => match resolve_matches.get_one::<String>("take").map(String::as_str) {
+                    Some("local") => Resolution::Local,
+                    _ => Resolution::Remote,
+                },
+            };
+
+            conflicts.resolve(id, resolution).await?;
+            info!("Run a sync to apply the resolution in Zotero and storage");
+        }
+        Some(("list", list_matches)) => {
+            let library = match list_matches.get_one::<i64>("library") {
+                Some(library_id) => {
+                    let library_type: LibraryType = list_matches.get_one::<String>("library-type")
+                        .expect("has default")
+                        .parse()
+                        .map_err(postero::Error::InvalidData)?;
+                    Some((*library_id, library_type))
+                }
+                None => None,
+            };
+
+            for conflict in conflicts.list(library).await? {
+                let details = if conflict.remote_deleted {
+                    "deleted in Zotero".to_string()
+                } else {
+                    conflict.fields.join(",")
+                };
+                println!(
+                    "{}\t{}\t{}\t{}/{}\t{}\t{}",
+                    conflict.id, conflict.entity_type, conflict.entity_key, conflict.library_type,
+                    conflict.library_id, conflict.created_at.to_rfc3339(), details
+                );
+            }
+        }
+        _ => unreachable!(), // clap requires a subcommand
+    }
+
+    Ok(())
+}
+
+async fn gc(
+    config: &Config,
+    db: &PgPool,
+    fs: Arc<dyn postero::filesystem::FileSystem>,
+    layout: AttachmentLayout,
+    matches: &clap::ArgMatches,
+) -> Result<()> {
+    let dry_run = matches.get_flag("dry-run");
+
+    let report = AttachmentGc::new(db.clone(), config.db.schema.clone(), fs, layout)
+        .run(dry_run)
+        .await?;
+
+    if dry_run {
+        info!(
+            "Dry run: {} of {} stored files are orphaned ({} bytes), nothing removed",
+            report.orphans.len(), report.scanned, report.orphaned_bytes()
+        );
+    } else {
+        info!(
+            "Removed {} orphaned files of {} stored ({} bytes)",
+            report.removed, report.scanned, report.orphaned_bytes()
+        );
+    }
+
+    Ok(())
+}
+
+/// Move attachment files stored in the legacy layout into the configured one
+async fn migrate_layout(
+    config: &Config,
+    db: &PgPool,
+    fs: Arc<dyn postero::filesystem::FileSystem>,
+    layout: AttachmentLayout,
+    matches: &clap::ArgMatches,
+) -> Result<()> {
+    let dry_run = matches.get_flag("dry-run");
+
+    info!("Migrating attachments into {}/{}", layout.bucket(), layout.key_layout());
+    let report = LayoutMigration::new(db.clone(), config.db.schema.clone(), fs, layout)
+        .run(dry_run)
+        .await?;
+
+    if dry_run {
+        info!(
+            "Dry run: {} of {} attachments would be moved, {} have no legacy file",
+            report.moved, report.scanned, report.missing
+        );
+    } else {
+        info!(
+            "Moved {} of {} attachments ({} bytes), {} have no legacy file, {} failed",
+            report.moved, report.scanned, report.bytes, report.missing, report.failed
+        );
+    }
+
+    if report.failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Check stored attachment files against their MD5 and, with `--repair`,
+/// download damaged ones again
+async fn verify(
+    config: &Config,
+    db: &PgPool,
+    fs: Arc<dyn postero::filesystem::FileSystem>,
+    layout: AttachmentLayout,
+    matches: &clap::ArgMatches,
+) -> Result<()> {
+    let library = match matches.get_one::<i64>("library") {
+        Some(library_id) => {
+            let library_type: LibraryType = matches.get_one::<String>("library-type")
+                .expect("has default")
+                .parse()
+                .map_err(postero::Error::InvalidData)?;
+            Some((*library_id, library_type))
+        }
+        None => None,
+    };
+
+    let client = if matches.get_flag("repair") {
+        let mut zotero = ZoteroClient::new(
+            &config.endpoint,
+            &config.apikey,
+            db.clone(),
+            fs.clone(),
+            &config.db.schema,
+            config.new_group_active(),
+        ).await?;
+        zotero.set_attachment_layout(layout.clone());
+        zotero.set_webdav(config.zotero_webdav.as_ref().map(ZoteroWebDav::from_config).transpose()?);
+        Some(zotero)
+    } else {
+        None
+    };
+
+    let report = StorageVerifier::new(db.clone(), config.db.schema.clone(), fs, layout)
+        .run(library, client.as_ref())
+        .await?;
+
+    info!(
+        "Checked {} attachment files ({} bytes), {} damaged, {} repaired",
+        report.checked, report.bytes, report.damaged.len(), report.repaired()
+    );
+
+    if report.damaged.iter().any(|d| !d.repaired) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// List the stored versions of an attachment file, or restore one of them
+async fn file_versions(
+    config: &Config,
+    db: &PgPool,
+    fs: Arc<dyn postero::filesystem::FileSystem>,
+    layout: AttachmentLayout,
+    matches: &clap::ArgMatches,
+) -> Result<()> {
+    let library_id = *matches.get_one::<i64>("library").expect("required by clap");
+    let library_type: LibraryType = matches.get_one::<String>("library-type")
+        .expect("has default")
+        .parse()
+        .map_err(postero::Error::InvalidData)?;
+    let item_key = matches.get_one::<String>("item").expect("required by clap");
+
+    let versions = AttachmentVersions::new(db.clone(), config.db.schema.clone(), fs, layout);
+
+    match matches.get_one::<String>("restore") {
+        Some(version_id) => {
+            versions.restore(library_id, library_type, item_key, version_id).await?;
+            info!("Run an outgoing sync to send the restored file to Zotero");
+        }
+        None => {
+            for version in versions.list(library_id, library_type, item_key).await? {
+                let state = match (version.is_latest, version.is_delete_marker) {
+                    (_, true) => "deleted",
+                    (true, false) => "current",
+                    (false, false) => "",
+                };
+                println!("{}\t{}\t{}\t{}", version.version_id, version.modified.to_rfc3339(), version.size, state);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Re-encrypt stored attachment files with the active `[encryption]` key
+async fn rekey(config: &Config, layout: AttachmentLayout, matches: &clap::ArgMatches) -> Result<()> {
+    let dry_run = matches.get_flag("dry-run");
+
+    let encryption = config.encryption.as_ref()
+        .ok_or_else(|| postero::Error::InvalidData("[encryption] is not configured".to_string()))?;
+    let backend = filesystem::backend_from_config(config).await?;
+    let fs = postero::filesystem::EncryptedFileSystem::from_config(backend, encryption)?;
+
+    let report = fs.rekey(layout.bucket(), "", dry_run).await?;
+
+    if dry_run {
+        info!(
+            "Dry run: {} of {} stored files are not encrypted with key {}",
+            report.stale, report.scanned, fs.keyring().active()
+        );
+    } else {
+        info!(
+            "Re-encrypted {} of {} stored files with key {}",
+            report.rekeyed, report.scanned, fs.keyring().active()
+        );
+    }
+    if report.plaintext > 0 {
+        warn!(
+            "{} stored files are not encrypted and were skipped; set allow_plaintext to encrypt them",
+            report.plaintext
+        );
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let matches = Command::new("postero-sync")
@@ -252,6 +519,181 @@ async fn main() -> Result<()> {
                 .help("Sync direction override: incoming, outgoing, or both")
                 .value_parser(["incoming", "outgoing", "both"])
         )
+        .subcommand(
+            Command::new("restore")
+                .about("Restore a library or a single item to its state at a given time")
+                .arg(
+                    Arg::new("library")
+                        .long("library")
+                        .value_name("ID")
+                        .help("ID of the library to restore")
+                        .required(true)
+                        .value_parser(clap::value_parser!(i64))
+                )
+                .arg(
+                    Arg::new("library-type")
+                        .long("library-type")
+                        .value_name("TYPE")
+                        .help("Library type: user or group")
+                        .value_parser(["user", "group"])
+                        .default_value("group")
+                )
+                .arg(
+                    Arg::new("item")
+                        .long("item")
+                        .value_name("KEY")
+                        .help("Only restore this item")
+                )
+                .arg(
+                    Arg::new("at")
+                        .long("at")
+                        .value_name("TIMESTAMP")
+                        .help("Point in time to restore, RFC 3339 (e.g. 2024-05-01T12:00:00Z)")
+                        .required(true)
+                        .value_parser(|s: &str| {
+                            chrono::DateTime::parse_from_rfc3339(s)
+                                .map(|t| t.with_timezone(&chrono::Utc))
+                                .map_err(|e| e.to_string())
+                        })
+                )
+        )
+        .subcommand(
+            Command::new("conflicts")
+                .about("Review and resolve sync conflicts the conflict policy left for manual review")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("List unresolved conflicts")
+                        .arg(
+                            Arg::new("library")
+                                .long("library")
+                                .value_name("ID")
+                                .help("Only list the conflicts of this library")
+                                .value_parser(clap::value_parser!(i64))
+                        )
+                        .arg(
+                            Arg::new("library-type")
+                                .long("library-type")
+                                .value_name("TYPE")
+                                .help("Library type: user or group")
+                                .value_parser(["user", "group"])
+                                .default_value("group")
+                        )
+                )
+                .subcommand(
+                    Command::new("resolve")
+                        .about("Resolve a conflict and release its object for the next sync")
+                        .arg(
+                            Arg::new("id")
+                                .value_name("ID")
+                                .help("ID of the conflict, as listed")
+                                .required(true)
+                                .value_parser(clap::value_parser!(i64))
+                        )
+                        .arg(
+                            Arg::new("take")
+                                .long("take")
+                                .value_name("SIDE")
+                                .help("Keep the local or the remote version")
+                                .value_parser(["local", "remote"])
+                        )
+                        .arg(
+                            Arg::new("data")
+                                .long("data")
+                                .value_name("FILE")
+                                .help("Upload the JSON object in FILE instead, e.g. a hand-merged copy")
+                        )
+                        .group(ArgGroup::new("resolution").args(["take", "data"]).required(true))
+                )
+        )
+        .subcommand(
+            Command::new("gc")
+                .about("Remove stored attachment files that no longer belong to an item")
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Only report orphaned files")
+                )
+        )
+        .subcommand(
+            Command::new("migrate-layout")
+                .about("Move attachment files from the attachments/{item_key}/{filename} layout into the configured one")
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Only report the files that would be moved")
+                )
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check stored attachment files for missing, truncated or corrupt objects")
+                .arg(
+                    Arg::new("library")
+                        .long("library")
+                        .value_name("ID")
+                        .help("Only check the attachments of this library")
+                        .value_parser(clap::value_parser!(i64))
+                )
+                .arg(
+                    Arg::new("library-type")
+                        .long("library-type")
+                        .value_name("TYPE")
+                        .help("Library type: user or group")
+                        .value_parser(["user", "group"])
+                        .default_value("group")
+                )
+                .arg(
+                    Arg::new("repair")
+                        .long("repair")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Download damaged files again from Zotero")
+                )
+        )
+        .subcommand(
+            Command::new("file-versions")
+                .about("List the stored versions of an attachment file, or restore one")
+                .arg(
+                    Arg::new("library")
+                        .long("library")
+                        .value_name("ID")
+                        .help("ID of the attachment's library")
+                        .required(true)
+                        .value_parser(clap::value_parser!(i64))
+                )
+                .arg(
+                    Arg::new("library-type")
+                        .long("library-type")
+                        .value_name("TYPE")
+                        .help("Library type: user or group")
+                        .value_parser(["user", "group"])
+                        .default_value("group")
+                )
+                .arg(
+                    Arg::new("item")
+                        .long("item")
+                        .value_name("KEY")
+                        .help("Key of the attachment item")
+                        .required(true)
+                )
+                .arg(
+                    Arg::new("restore")
+                        .long("restore")
+                        .value_name("VERSION")
+                        .help("Make this version the current file and mark it for upload")
+                )
+        )
+        .subcommand(
+            Command::new("rekey")
+                .about("Re-encrypt stored attachment files with the active encryption key")
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Only count the files that would be re-encrypted")
+                )
+        )
         .get_matches();
 
     // Load configuration
@@ -291,21 +733,44 @@ async fn main() -> Result<()> {
         .expect("Failed to set tracing subscriber");
 
     // Connect to database
-    let db = PgPool::connect(&config.db.dsn).await?;
+    let db = ChangeSource::Cloud.connect(&config.db.dsn).await?;
 
     // Test database connection
     sqlx::query("SELECT 1").fetch_one(&db).await?;
     info!("Database connection established");
 
+    match matches.subcommand() {
+        Some(("restore", restore_matches)) => return restore(&config, &db, restore_matches).await,
+        Some(("conflicts", conflicts_matches)) => return conflicts(&config, &db, conflicts_matches).await,
+        _ => {}
+    }
+
+    let layout = AttachmentLayout::from_config(&config)?;
+    if let Some(("rekey", rekey_matches)) = matches.subcommand() {
+        return rekey(&config, layout, rekey_matches).await;
+    }
+
     // Initialize filesystem
-    let fs = Arc::new(
-        S3FileSystem::new(
-            &config.s3.endpoint,
-            &config.s3.access_key_id,
-            &config.s3.secret_access_key,
-            config.s3.use_ssl,
-        ).await?
-    ) as Arc<dyn postero::filesystem::FileSystem>;
+    let fs = filesystem::from_config(&config).await?;
+
+    match matches.subcommand() {
+        Some(("gc", gc_matches)) => {
+            layout.ensure_bucket(fs.as_ref()).await?;
+            return gc(&config, &db, fs, layout, gc_matches).await;
+        }
+        Some(("migrate-layout", migrate_matches)) => {
+            // Files are moved into the bucket, which may not exist yet
+            layout.ensure_bucket(fs.as_ref()).await?;
+            return migrate_layout(&config, &db, fs, layout, migrate_matches).await;
+        }
+        Some(("verify", verify_matches)) => return verify(&config, &db, fs, layout, verify_matches).await,
+        Some(("file-versions", versions_matches)) => {
+            return file_versions(&config, &db, fs, layout, versions_matches).await;
+        }
+        _ => {}
+    }
+
+    layout.ensure_bucket(fs.as_ref()).await?;
 
     // Parse direction override
     let direction_override = matches.get_one::<String>("direction").map(|d| {
@@ -324,7 +789,7 @@ async fn main() -> Result<()> {
     info!("Starting sync process");
 
     // Run sync
-    if let Err(e) = sync_data(&config, &db, fs, direction_override).await {
+    if let Err(e) = sync_data(&config, &db, fs, layout, direction_override).await {
         error!("Sync failed: {}", e);
         std::process::exit(1);
     }
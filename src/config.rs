@@ -1,4 +1,5 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use crate::{Result};
@@ -34,6 +35,73 @@ pub struct S3Config {
     pub use_ssl: bool,
 }
 
+/// Connection to a WebDAV server
+#[derive(Debug, Deserialize)]
+pub struct WebDavConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// Attachment storage backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageType {
+    /// Directory tree on a local or mounted volume
+    Local,
+    /// S3-compatible object storage, configured in `[s3]`
+    #[default]
+    S3,
+    /// Kept in memory and discarded on exit, for dry runs
+    Memory,
+    /// WebDAV server, configured in `[webdav]`
+    WebDav,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StorageConfig {
+    #[serde(rename = "type", default)]
+    pub storage_type: StorageType,
+    /// Root directory for `local` storage
+    pub path: Option<String>,
+    /// Bucket (or top-level folder) holding attachment files
+    pub bucket: Option<String>,
+    /// Object key template; see [`crate::zotero::layout`]
+    pub key_layout: Option<String>,
+    /// Store each distinct file once, addressed by its MD5
+    pub content_addressed: Option<bool>,
+    /// Create the bucket with versioning, keeping replaced files
+    pub versioning: Option<bool>,
+    /// Create the bucket with object lock (and versioning)
+    pub object_locking: Option<bool>,
+    /// Default retention of stored versions with object lock, in days
+    pub retention_days: Option<u32>,
+}
+
+/// Client-side encryption of attachment files
+#[derive(Debug, Deserialize)]
+pub struct EncryptionConfig {
+    /// ID of the key new files are encrypted with
+    pub key_id: String,
+    /// Hex-encoded 256-bit keys by ID
+    pub keys: Option<HashMap<String, String>>,
+    /// TOML file of further keys, `id = "hex key"` per line
+    pub key_file: Option<String>,
+    /// Read files stored before encryption was enabled
+    pub allow_plaintext: Option<bool>,
+}
+
+/// Service redirecting API clients to presigned attachment downloads
+#[derive(Debug, Deserialize)]
+pub struct FileServerConfig {
+    /// Address to listen on; `0.0.0.0:3001` if unset
+    pub listen: Option<String>,
+    /// Secret the API's tokens are signed with, as PostgREST's `jwt-secret`
+    pub jwt_secret: String,
+    /// Lifetime of the presigned URLs in seconds; 300 if unset
+    pub url_expiry: Option<u64>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     #[serde(alias = "Service", alias = "service")]
@@ -60,8 +128,21 @@ pub struct Config {
     pub group_cache_expiration: Option<String>,
     #[serde(alias = "gitlab")]
     pub gitlab: Option<GitlabConfig>,
+    #[serde(alias = "storage")]
+    pub storage: Option<StorageConfig>,
     #[serde(alias = "s3")]
-    pub s3: S3Config,
+    pub s3: Option<S3Config>,
+    #[serde(alias = "encryption")]
+    pub encryption: Option<EncryptionConfig>,
+    /// Server for `[storage] type = "webdav"`
+    #[serde(alias = "webdav")]
+    pub webdav: Option<WebDavConfig>,
+    /// WebDAV server the Zotero clients sync personal library files with,
+    /// as set in Zotero's Sync preferences
+    #[serde(alias = "zotero_webdav")]
+    pub zotero_webdav: Option<WebDavConfig>,
+    #[serde(alias = "file_server")]
+    pub file_server: Option<FileServerConfig>,
 }
 
 impl Config {
@@ -86,4 +167,9 @@ impl Config {
     pub fn loglevel(&self) -> &str {
         self.loglevel.as_deref().unwrap_or("info")
     }
+
+    /// Selected storage backend; S3 unless `[storage]` says otherwise
+    pub fn storage_type(&self) -> StorageType {
+        self.storage.as_ref().map(|s| s.storage_type).unwrap_or_default()
+    }
 } 
\ No newline at end of file
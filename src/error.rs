@@ -21,7 +21,7 @@ pub enum Error {
     UrlParse(#[from] url::ParseError),
 
     #[error("S3 error: {0}")]
-    S3(#[from] aws_sdk_s3::Error),
+    S3(Box<aws_sdk_s3::Error>),
 
     #[error("Not found: {0}")]
     NotFound(String),
@@ -48,6 +48,12 @@ pub enum Error {
     RateLimit { retry_after: Option<u64> },
 }
 
+impl From<aws_sdk_s3::Error> for Error {
+    fn from(err: aws_sdk_s3::Error) -> Self {
+        Error::S3(Box::new(err))
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 impl Error {
@@ -65,6 +71,11 @@ impl Error {
     pub fn is_not_found(&self) -> bool {
         matches!(self, Error::NotFound(_))
     }
+
+    /// Zotero rejected a write because the library or object changed (412)
+    pub fn is_precondition_failed(&self) -> bool {
+        matches!(self, Error::Api { code: 412, .. })
+    }
 }
 
 impl Error {
@@ -0,0 +1,648 @@
+//! Client-side encryption of stored files.
+//!
+//! [`EncryptedFileSystem`] wraps another backend and encrypts every file with
+//! AES-256-GCM before it is written. Files are sealed in chunks so they can
+//! be streamed; each object starts with a header naming the key it was
+//! encrypted with, so older keys keep working after the active key changes.
+//!
+//! Object layout:
+//!
+//! ```text
+//! "PSTENC" | version (1) | key id length (1) | key id | nonce prefix (7) | chunk size (4, BE)
+//! chunk 0 | chunk 1 | ... | last chunk
+//! ```
+//!
+//! Every chunk holds `chunk size` bytes of plaintext plus a 16 byte tag, except
+//! the last, which may be shorter. Chunk nonces are the nonce prefix, the
+//! chunk's index and a flag marking the last chunk, so reordered, dropped or
+//! truncated chunks fail to open. The associated data of every chunk is the
+//! header followed by the object's path, `folder/name`, so an object copied
+//! or moved to another path outside this wrapper fails to open. Version 1
+//! objects bound only the header; they are still read, and `rekey` rewrites
+//! them.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use tracing::{info, warn};
+
+use crate::{Error, Result};
+use crate::config::EncryptionConfig;
+use super::{FileSystem, FilePutOptions, FileGetOptions, FileStatOptions, FilePresignOptions, FolderCreateOptions, FileInfo, FileReader, FileVersion, MD5_METADATA};
+
+const MAGIC: &[u8; 6] = b"PSTENC";
+const VERSION: u8 = 2;
+/// Oldest version still read; its associated data is the header alone
+const MIN_VERSION: u8 = 1;
+const NONCE_PREFIX_LEN: usize = 7;
+const TAG_LEN: usize = 16;
+/// Plaintext bytes per chunk of newly written objects
+const CHUNK_SIZE: u32 = 64 * 1024;
+/// Largest chunk size accepted when reading, to bound memory use
+const MAX_CHUNK_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Encryption keys by ID, and the one used for new objects
+#[derive(Clone)]
+pub struct Keyring {
+    active: String,
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+impl Keyring {
+    /// Keyring of hex-encoded 256-bit keys; `active` must be one of them
+    pub fn new(active: &str, keys: &HashMap<String, String>) -> Result<Self> {
+        let mut ciphers = HashMap::with_capacity(keys.len());
+        for (id, hex_key) in keys {
+            if id.is_empty() || id.len() > u8::MAX as usize {
+                return Err(Error::InvalidData(format!("Encryption key id {:?} must have 1 to 255 bytes", id)));
+            }
+            let bytes = hex::decode(hex_key.trim())
+                .map_err(|e| Error::InvalidData(format!("Encryption key {} is not hex: {}", id, e)))?;
+            if bytes.len() != 32 {
+                return Err(Error::InvalidData(format!("Encryption key {} must be 32 bytes, not {}", id, bytes.len())));
+            }
+            ciphers.insert(id.clone(), Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)));
+        }
+
+        if !ciphers.contains_key(active) {
+            return Err(Error::InvalidData(format!("Active encryption key {} is not configured", active)));
+        }
+
+        Ok(Self { active: active.to_string(), keys: ciphers })
+    }
+
+    /// Keyring from `[encryption]`: the keys listed inline and those in
+    /// `key_file`, a TOML table of key IDs to hex keys
+    pub fn from_config(config: &EncryptionConfig) -> Result<Self> {
+        let mut keys = config.keys.clone().unwrap_or_default();
+        if let Some(key_file) = &config.key_file {
+            let content = std::fs::read_to_string(key_file)?;
+            let file_keys: HashMap<String, String> = toml::from_str(&content)?;
+            keys.extend(file_keys);
+        }
+        Self::new(&config.key_id, &keys)
+    }
+
+    /// ID of the key new objects are encrypted with
+    pub fn active(&self) -> &str {
+        &self.active
+    }
+
+    fn cipher(&self, id: &str) -> Result<&Aes256Gcm> {
+        self.keys.get(id)
+            .ok_or_else(|| Error::InvalidData(format!("Unknown encryption key {}", id)))
+    }
+}
+
+impl std::fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut ids: Vec<&String> = self.keys.keys().collect();
+        ids.sort();
+        f.debug_struct("Keyring")
+            .field("active", &self.active)
+            .field("keys", &ids)
+            .finish()
+    }
+}
+
+/// Parsed object header
+struct Header {
+    version: u8,
+    key_id: String,
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    chunk_size: u32,
+    /// The header as stored, used as associated data
+    bytes: Vec<u8>,
+}
+
+impl Header {
+    fn new(key_id: &str) -> Self {
+        let nonce_prefix: [u8; NONCE_PREFIX_LEN] = rand::random();
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 2 + key_id.len() + NONCE_PREFIX_LEN + 4);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(key_id.len() as u8);
+        bytes.extend_from_slice(key_id.as_bytes());
+        bytes.extend_from_slice(&nonce_prefix);
+        bytes.extend_from_slice(&CHUNK_SIZE.to_be_bytes());
+
+        Self { version: VERSION, key_id: key_id.to_string(), nonce_prefix, chunk_size: CHUNK_SIZE, bytes }
+    }
+
+    /// Read the header from the start of an object; `Ok(Err(bytes))` returns
+    /// the bytes consumed if the object is not encrypted
+    async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> Result<std::result::Result<Header, Vec<u8>>> {
+        let mut bytes = read_up_to(reader, MAGIC.len() + 2).await?;
+        if bytes.len() < MAGIC.len() + 2 || &bytes[..MAGIC.len()] != MAGIC {
+            return Ok(Err(bytes));
+        }
+        let version = bytes[MAGIC.len()];
+        if !(MIN_VERSION..=VERSION).contains(&version) {
+            return Err(Error::InvalidData(format!("Unsupported encryption version {}", version)));
+        }
+
+        let id_len = bytes[MAGIC.len() + 1] as usize;
+        let mut rest = vec![0u8; id_len + NONCE_PREFIX_LEN + 4];
+        reader.read_exact(&mut rest).await
+            .map_err(|e| Error::InvalidData(format!("Truncated encryption header: {}", e)))?;
+        bytes.extend_from_slice(&rest);
+
+        let key_id = String::from_utf8(rest[..id_len].to_vec())
+            .map_err(|_| Error::InvalidData("Encryption key id is not UTF-8".to_string()))?;
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        nonce_prefix.copy_from_slice(&rest[id_len..id_len + NONCE_PREFIX_LEN]);
+        let chunk_size = u32::from_be_bytes(rest[id_len + NONCE_PREFIX_LEN..].try_into().expect("4 bytes"));
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(Error::InvalidData(format!("Invalid encryption chunk size {}", chunk_size)));
+        }
+
+        Ok(Ok(Header { version, key_id, nonce_prefix, chunk_size, bytes }))
+    }
+
+    /// Associated data of the chunks of the object at `folder`/`name`
+    fn aad(&self, folder: &str, name: &str) -> Vec<u8> {
+        let mut aad = self.bytes.clone();
+        if self.version >= 2 {
+            aad.extend_from_slice(object_path(folder, name).as_bytes());
+        }
+        aad
+    }
+
+    /// Plaintext size of an object of `stored` bytes
+    fn plaintext_size(&self, stored: u64) -> u64 {
+        let body = stored.saturating_sub(self.bytes.len() as u64);
+        let sealed_chunk = self.chunk_size as u64 + TAG_LEN as u64;
+        let chunks = body.div_ceil(sealed_chunk).max(1);
+        body.saturating_sub(chunks * TAG_LEN as u64)
+    }
+}
+
+/// Path an object is bound to; the same whether a subfolder is part of
+/// `folder` or of `name`
+fn object_path(folder: &str, name: &str) -> String {
+    format!("{}/{}", folder.trim_end_matches('/'), name.trim_start_matches('/'))
+}
+
+async fn read_up_to<R: AsyncRead + Unpin>(reader: &mut R, len: usize) -> Result<Vec<u8>> {
+    let mut bytes = vec![0u8; len];
+    let mut filled = 0;
+    while filled < len {
+        let read = reader.read(&mut bytes[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    bytes.truncate(filled);
+    Ok(bytes)
+}
+
+/// Seals or opens a stream chunk by chunk
+struct CipherReader<R> {
+    inner: R,
+    cipher: Aes256Gcm,
+    header: Header,
+    /// Associated data of every chunk
+    aad: Vec<u8>,
+    seal: bool,
+    /// Input bytes per chunk: plaintext when sealing, ciphertext when opening
+    chunk_len: usize,
+    counter: u32,
+    /// Bytes read from `inner`
+    bytes_in: u64,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    output_pos: usize,
+    eof: bool,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> CipherReader<R> {
+    /// Encrypt `inner` for `aad`; the header is emitted first
+    fn seal(inner: R, cipher: Aes256Gcm, header: Header, aad: Vec<u8>) -> Self {
+        let chunk_len = header.chunk_size as usize;
+        let output = header.bytes.clone();
+        Self::with(inner, cipher, header, aad, true, chunk_len, output)
+    }
+
+    /// Decrypt `inner`, positioned after the header
+    fn open(inner: R, cipher: Aes256Gcm, header: Header, aad: Vec<u8>) -> Self {
+        let chunk_len = header.chunk_size as usize + TAG_LEN;
+        Self::with(inner, cipher, header, aad, false, chunk_len, Vec::new())
+    }
+
+    fn with(inner: R, cipher: Aes256Gcm, header: Header, aad: Vec<u8>, seal: bool, chunk_len: usize, output: Vec<u8>) -> Self {
+        Self {
+            inner,
+            cipher,
+            header,
+            aad,
+            seal,
+            chunk_len,
+            counter: 0,
+            bytes_in: 0,
+            input: Vec::with_capacity(chunk_len + 1),
+            output,
+            output_pos: 0,
+            eof: false,
+            done: false,
+        }
+    }
+
+    fn process(&mut self, chunk: &[u8], last: bool) -> std::io::Result<Vec<u8>> {
+        let mut nonce = [0u8; 12];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(&self.header.nonce_prefix);
+        nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&self.counter.to_be_bytes());
+        nonce[11] = last as u8;
+
+        self.counter = self.counter.checked_add(1)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "Too many encrypted chunks"))?;
+
+        let payload = Payload { msg: chunk, aad: &self.aad };
+        let nonce = Nonce::from_slice(&nonce);
+        if self.seal {
+            self.cipher.encrypt(nonce, payload)
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Cannot encrypt chunk"))
+        } else {
+            self.cipher.decrypt(nonce, payload)
+                .map_err(|_| std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "Encrypted file is corrupt, truncated or was written with a different key",
+                ))
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CipherReader<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.output_pos < this.output.len() {
+                let available = &this.output[this.output_pos..];
+                let len = available.len().min(buf.remaining());
+                buf.put_slice(&available[..len]);
+                this.output_pos += len;
+                return Poll::Ready(Ok(()));
+            }
+            if this.done {
+                return Poll::Ready(Ok(()));
+            }
+
+            // One byte beyond a full chunk tells whether the chunk is the last
+            while !this.eof && this.input.len() <= this.chunk_len {
+                let mut scratch = [0u8; 8192];
+                let want = (this.chunk_len + 1 - this.input.len()).min(scratch.len());
+                let mut read_buf = ReadBuf::new(&mut scratch[..want]);
+                match Pin::new(&mut this.inner).poll_read(cx, &mut read_buf) {
+                    Poll::Ready(Ok(())) if read_buf.filled().is_empty() => this.eof = true,
+                    Poll::Ready(Ok(())) => {
+                        this.bytes_in += read_buf.filled().len() as u64;
+                        this.input.extend_from_slice(read_buf.filled());
+                    }
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            let last = this.eof && this.input.len() <= this.chunk_len;
+            let take = if last { this.input.len() } else { this.chunk_len };
+            if !this.seal && take < TAG_LEN {
+                return Poll::Ready(Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Encrypted file is truncated")));
+            }
+
+            let chunk: Vec<u8> = this.input.drain(..take).collect();
+            this.output = this.process(&chunk, last)?;
+            this.output_pos = 0;
+            this.done = last;
+        }
+    }
+}
+
+/// Outcome of re-encrypting stored files with the active key
+#[derive(Debug, Default)]
+pub struct RekeyReport {
+    /// Number of stored files examined
+    pub scanned: usize,
+    /// Files encrypted with another key or an older format, or not at all
+    pub stale: usize,
+    /// Files rewritten with the active key; zero in a dry run
+    pub rekeyed: usize,
+    /// Unencrypted files left alone because plaintext is not allowed
+    pub plaintext: usize,
+}
+
+/// Encrypts files before they reach the wrapped backend.
+///
+/// Reads, stats and hashes see plaintext. Sizes from `file_list` and
+/// `file_versions` are those of the stored objects.
+#[derive(Debug)]
+pub struct EncryptedFileSystem {
+    inner: Arc<dyn FileSystem>,
+    keyring: Keyring,
+    /// Read objects without an encryption header as they are
+    allow_plaintext: bool,
+}
+
+impl EncryptedFileSystem {
+    pub fn new(inner: Arc<dyn FileSystem>, keyring: Keyring) -> Self {
+        Self { inner, keyring, allow_plaintext: false }
+    }
+
+    /// Also read unencrypted objects, e.g. those stored before encryption
+    /// was enabled; `rekey` encrypts them
+    pub fn with_plaintext(mut self, allow: bool) -> Self {
+        self.allow_plaintext = allow;
+        self
+    }
+
+    pub fn from_config(inner: Arc<dyn FileSystem>, config: &EncryptionConfig) -> Result<Self> {
+        let keyring = Keyring::from_config(config)?;
+        Ok(Self::new(inner, keyring).with_plaintext(config.allow_plaintext.unwrap_or(false)))
+    }
+
+    pub fn keyring(&self) -> &Keyring {
+        &self.keyring
+    }
+
+    /// Open a stored object and read its header; an unencrypted object is
+    /// returned from its start
+    async fn open(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<(FileReader, Option<Header>)> {
+        let mut reader = self.inner.file_reader(folder, name, opts).await?;
+        match Header::read(&mut reader).await? {
+            Ok(header) => Ok((reader, Some(header))),
+            Err(consumed) if self.allow_plaintext => {
+                Ok((Box::new(Cursor::new(consumed).chain(reader)), None))
+            }
+            Err(_) => Err(Error::InvalidData(format!("{}/{} is not encrypted", folder, name))),
+        }
+    }
+
+    /// Header of a stored object, `None` if it is not encrypted
+    async fn header(&self, folder: &str, name: &str) -> Result<Option<Header>> {
+        let mut reader = self.inner.file_reader(folder, name, FileGetOptions::default()).await?;
+        Ok(Header::read(&mut reader).await?.ok())
+    }
+
+    /// ID of the key a stored object is encrypted with, `None` if it is not
+    /// encrypted
+    pub async fn key_id(&self, folder: &str, name: &str) -> Result<Option<String>> {
+        Ok(self.header(folder, name).await?.map(|header| header.key_id))
+    }
+
+    /// Rewrite the files in `folder` starting with `prefix` that are not
+    /// encrypted with the active key in the current format; a dry run only
+    /// counts them.
+    ///
+    /// Unencrypted files are only rewritten if plaintext is allowed, as they
+    /// cannot be read otherwise; the others are counted and skipped. Each
+    /// file is held in memory while it is rewritten.
+    pub async fn rekey(&self, folder: &str, prefix: &str, dry_run: bool) -> Result<RekeyReport> {
+        let files = self.inner.file_list(folder, prefix).await?;
+        let mut report = RekeyReport { scanned: files.len(), ..Default::default() };
+
+        for file in files {
+            match self.header(folder, &file.name).await? {
+                Some(header) if header.version == VERSION && header.key_id == self.keyring.active() => continue,
+                None if !self.allow_plaintext => {
+                    warn!("Skipping {}/{}: it is not encrypted and plaintext is not allowed", folder, file.name);
+                    report.plaintext += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            report.stale += 1;
+            if dry_run {
+                continue;
+            }
+
+            let options = self.inner.file_stat(folder, &file.name, FileStatOptions::default()).await?.put_options();
+            let data = self.file_get(folder, &file.name, FileGetOptions::default()).await?;
+            self.file_put(folder, &file.name, &data, options).await?;
+            info!("Re-encrypted {}/{} with key {}", folder, file.name, self.keyring.active());
+            report.rekeyed += 1;
+        }
+
+        Ok(report)
+    }
+}
+
+#[async_trait]
+impl FileSystem for EncryptedFileSystem {
+    async fn folder_exists(&self, folder: &str) -> Result<bool> {
+        self.inner.folder_exists(folder).await
+    }
+
+    async fn folder_create(&self, folder: &str, opts: FolderCreateOptions) -> Result<()> {
+        self.inner.folder_create(folder, opts).await
+    }
+
+    async fn file_exists(&self, folder: &str, name: &str) -> Result<bool> {
+        self.inner.file_exists(folder, name).await
+    }
+
+    async fn file_get(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<Vec<u8>> {
+        let mut reader = self.file_reader(folder, name, opts).await?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
+    async fn file_put(&self, folder: &str, name: &str, data: &[u8], opts: FilePutOptions) -> Result<()> {
+        let mut reader = data;
+        self.file_put_stream(folder, name, &mut reader, opts).await?;
+        Ok(())
+    }
+
+    async fn file_write_bytes(&self, folder: &str, name: &str, data: Vec<u8>, opts: FilePutOptions) -> Result<()> {
+        self.file_put(folder, name, &data, opts).await
+    }
+
+    async fn file_read_bytes(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<Vec<u8>> {
+        self.file_get(folder, name, opts).await
+    }
+
+    async fn file_stat(&self, folder: &str, name: &str, opts: FileStatOptions) -> Result<FileInfo> {
+        let mut info = self.inner.file_stat(folder, name, opts).await?;
+        let (_, header) = self.open(folder, name, FileGetOptions::default()).await?;
+        if let Some(header) = header {
+            info.size = header.plaintext_size(info.size);
+        }
+        Ok(info)
+    }
+
+    async fn file_reader(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<FileReader> {
+        match self.open(folder, name, opts).await? {
+            (reader, Some(header)) => {
+                let cipher = self.keyring.cipher(&header.key_id)?.clone();
+                let aad = header.aad(folder, name);
+                Ok(Box::new(CipherReader::open(reader, cipher, header, aad)))
+            }
+            (reader, None) => Ok(reader),
+        }
+    }
+
+    async fn file_put_stream(
+        &self,
+        folder: &str,
+        name: &str,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        opts: FilePutOptions,
+    ) -> Result<u64> {
+        let header = Header::new(self.keyring.active());
+        let cipher = self.keyring.cipher(&header.key_id)?.clone();
+        let aad = header.aad(folder, name);
+        let mut sealed = CipherReader::seal(reader, cipher, header, aad);
+        // The plaintext's hash would let anyone reading the bucket confirm
+        // a guess of the contents
+        let mut opts = opts;
+        opts.metadata.remove(MD5_METADATA);
+        self.inner.file_put_stream(folder, name, &mut sealed, opts).await?;
+        Ok(sealed.bytes_in)
+    }
+
+    async fn file_list(&self, folder: &str, prefix: &str) -> Result<Vec<FileInfo>> {
+        self.inner.file_list(folder, prefix).await
+    }
+
+    async fn file_delete(&self, folder: &str, name: &str) -> Result<()> {
+        self.inner.file_delete(folder, name).await
+    }
+
+    async fn file_delete_prefix(&self, folder: &str, prefix: &str) -> Result<u64> {
+        self.inner.file_delete_prefix(folder, prefix).await
+    }
+
+    async fn file_versions(&self, folder: &str, name: &str) -> Result<Vec<FileVersion>> {
+        self.inner.file_versions(folder, name).await
+    }
+
+    async fn file_restore_version(&self, folder: &str, name: &str, version_id: &str) -> Result<()> {
+        self.inner.file_restore_version(folder, name, version_id).await
+    }
+
+    /// Refused: the URL would hand out the ciphertext
+    async fn file_presigned_url(&self, _folder: &str, _name: &str, _opts: FilePresignOptions) -> Result<String> {
+        Err(Error::InvalidData("Encrypted files cannot be downloaded through presigned URLs".to_string()))
+    }
+
+    fn protocol(&self) -> &str {
+        self.inner.protocol()
+    }
+}
+
+impl std::fmt::Display for EncryptedFileSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EncryptedFileSystem(key: {}, inner: {:?})", self.keyring.active(), self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::InMemoryFileSystem;
+
+    fn keyring(active: &str) -> Keyring {
+        let keys = HashMap::from([
+            ("old".to_string(), "11".repeat(32)),
+            ("new".to_string(), "22".repeat(32)),
+        ]);
+        Keyring::new(active, &keys).unwrap()
+    }
+
+    fn encrypted(inner: &Arc<InMemoryFileSystem>, active: &str) -> EncryptedFileSystem {
+        EncryptedFileSystem::new(inner.clone(), keyring(active))
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let inner = Arc::new(InMemoryFileSystem::new());
+        let fs = encrypted(&inner, "new");
+        // Several chunks, the last one short
+        let data: Vec<u8> = (0..CHUNK_SIZE as usize * 2 + 100).map(|i| i as u8).collect();
+
+        fs.file_put("bucket", "dir/file.pdf", &data, FilePutOptions::default()).await.unwrap();
+
+        let stored = inner.contents("bucket", "dir/file.pdf").unwrap();
+        assert!(stored.starts_with(MAGIC));
+        assert_eq!(stored.len(), Header::new("new").bytes.len() + data.len() + 3 * TAG_LEN);
+        assert_eq!(fs.file_get("bucket", "dir/file.pdf", FileGetOptions::default()).await.unwrap(), data);
+        assert_eq!(fs.file_stat("bucket", "dir/file.pdf", FileStatOptions::default()).await.unwrap().size, data.len() as u64);
+
+        // The same object, addressed with the subfolder as part of the folder
+        inner.insert("bucket/dir", "file.pdf", stored);
+        assert_eq!(fs.file_get("bucket/dir", "file.pdf", FileGetOptions::default()).await.unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn empty_file_round_trip() {
+        let inner = Arc::new(InMemoryFileSystem::new());
+        let fs = encrypted(&inner, "new");
+        fs.file_put("bucket", "empty", b"", FilePutOptions::default()).await.unwrap();
+        assert!(fs.file_get("bucket", "empty", FileGetOptions::default()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn tampering_is_detected() {
+        let inner = Arc::new(InMemoryFileSystem::new());
+        let fs = encrypted(&inner, "new");
+        fs.file_put("bucket", "file", b"secret contents", FilePutOptions::default()).await.unwrap();
+        let stored = inner.contents("bucket", "file").unwrap();
+
+        // Flipped ciphertext bit
+        let mut flipped = stored.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        inner.insert("bucket", "file", flipped);
+        assert!(fs.file_get("bucket", "file", FileGetOptions::default()).await.is_err());
+
+        // Truncated object
+        inner.insert("bucket", "file", stored[..stored.len() - 1].to_vec());
+        assert!(fs.file_get("bucket", "file", FileGetOptions::default()).await.is_err());
+
+        // Object copied to another path
+        inner.insert("bucket", "other", stored.clone());
+        assert!(fs.file_get("bucket", "other", FileGetOptions::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn plaintext_needs_permission() {
+        let inner = Arc::new(InMemoryFileSystem::new());
+        inner.insert("bucket", "plain", "not encrypted");
+
+        let strict = encrypted(&inner, "new");
+        assert!(matches!(
+            strict.file_get("bucket", "plain", FileGetOptions::default()).await,
+            Err(Error::InvalidData(_))
+        ));
+
+        let lenient = encrypted(&inner, "new").with_plaintext(true);
+        assert_eq!(lenient.file_get("bucket", "plain", FileGetOptions::default()).await.unwrap(), b"not encrypted");
+    }
+
+    #[tokio::test]
+    async fn rekey_rewrites_stale_objects_and_skips_plaintext() {
+        let inner = Arc::new(InMemoryFileSystem::new());
+        encrypted(&inner, "old").file_put("bucket", "a", b"first", FilePutOptions::default()).await.unwrap();
+        encrypted(&inner, "new").file_put("bucket", "b", b"second", FilePutOptions::default()).await.unwrap();
+        inner.insert("bucket", "c", "plain");
+
+        let fs = encrypted(&inner, "new");
+        let report = fs.rekey("bucket", "", true).await.unwrap();
+        assert_eq!((report.scanned, report.stale, report.rekeyed, report.plaintext), (3, 1, 0, 1));
+
+        let report = fs.rekey("bucket", "", false).await.unwrap();
+        assert_eq!((report.scanned, report.stale, report.rekeyed, report.plaintext), (3, 1, 1, 1));
+        assert_eq!(fs.key_id("bucket", "a").await.unwrap().as_deref(), Some("new"));
+        assert_eq!(fs.file_get("bucket", "a", FileGetOptions::default()).await.unwrap(), b"first");
+        assert_eq!(inner.contents("bucket", "c").as_deref(), Some(b"plain".as_slice()));
+
+        let report = fs.with_plaintext(true).rekey("bucket", "", false).await.unwrap();
+        assert_eq!((report.stale, report.rekeyed, report.plaintext), (1, 1, 0));
+    }
+}
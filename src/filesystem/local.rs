@@ -0,0 +1,273 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncRead;
+use crate::{Error, Result};
+use super::{FileSystem, FilePutOptions, FileGetOptions, FileStatOptions, FilePresignOptions, FolderCreateOptions, FileInfo, FileReader, FileVersion, UNVERSIONED};
+
+/// Stores files below a root directory, one subdirectory per folder
+#[derive(Debug)]
+pub struct LocalFileSystem {
+    root: PathBuf,
+}
+
+impl LocalFileSystem {
+    pub async fn new(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root).await?;
+
+        Ok(Self { root })
+    }
+
+    /// Resolve a folder (and optional name) below the root, rejecting paths
+    /// that would escape it
+    fn resolve(&self, folder: &str, name: Option<&str>) -> Result<PathBuf> {
+        let mut path = self.root.clone();
+
+        for part in std::iter::once(folder).chain(name) {
+            for component in Path::new(part).components() {
+                match component {
+                    Component::Normal(segment) => path.push(segment),
+                    Component::CurDir => {}
+                    _ => return Err(Error::Validation(format!("Invalid storage path: {}", part))),
+                }
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// Remove directories left empty below `folder` after deleting `path`
+    async fn prune_empty_dirs(&self, folder: &Path, path: &Path) {
+        let mut dir = path.parent();
+        while let Some(current) = dir {
+            if current == folder || !current.starts_with(folder) || fs::remove_dir(current).await.is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+    }
+
+    fn not_found(err: std::io::Error, path: &Path) -> Error {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            Error::NotFound(path.display().to_string())
+        } else {
+            Error::Io(err)
+        }
+    }
+}
+
+#[async_trait]
+impl FileSystem for LocalFileSystem {
+    async fn folder_exists(&self, folder: &str) -> Result<bool> {
+        let path = self.resolve(folder, None)?;
+        match fs::metadata(&path).await {
+            Ok(metadata) => Ok(metadata.is_dir()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(Error::Io(err)),
+        }
+    }
+
+    async fn folder_create(&self, folder: &str, opts: FolderCreateOptions) -> Result<()> {
+        if opts.versioning || opts.object_locking {
+            return Err(Error::InvalidData("Local storage does not keep file versions".to_string()));
+        }
+        fs::create_dir_all(self.resolve(folder, None)?).await?;
+        Ok(())
+    }
+
+    async fn file_exists(&self, folder: &str, name: &str) -> Result<bool> {
+        let path = self.resolve(folder, Some(name))?;
+        match fs::metadata(&path).await {
+            Ok(metadata) => Ok(metadata.is_file()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(Error::Io(err)),
+        }
+    }
+
+    async fn file_get(&self, folder: &str, name: &str, _opts: FileGetOptions) -> Result<Vec<u8>> {
+        let path = self.resolve(folder, Some(name))?;
+        fs::read(&path).await.map_err(|e| Self::not_found(e, &path))
+    }
+
+    async fn file_put(&self, folder: &str, name: &str, data: &[u8], opts: FilePutOptions) -> Result<()> {
+        let mut reader = data;
+        self.file_put_stream(folder, name, &mut reader, opts).await?;
+        Ok(())
+    }
+
+    async fn file_write_bytes(&self, folder: &str, name: &str, data: Vec<u8>, opts: FilePutOptions) -> Result<()> {
+        self.file_put(folder, name, &data, opts).await
+    }
+
+    async fn file_read_bytes(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<Vec<u8>> {
+        self.file_get(folder, name, opts).await
+    }
+
+    async fn file_stat(&self, folder: &str, name: &str, _opts: FileStatOptions) -> Result<FileInfo> {
+        let path = self.resolve(folder, Some(name))?;
+        let metadata = fs::metadata(&path).await.map_err(|e| Self::not_found(e, &path))?;
+
+        let modified = metadata.modified()
+            .map(chrono::DateTime::<chrono::Utc>::from)
+            .unwrap_or_default();
+
+        Ok(FileInfo {
+            name: name.to_string(),
+            size: metadata.len(),
+            modified,
+            is_dir: metadata.is_dir(),
+            content_type: None,
+            metadata: HashMap::new(),
+        })
+    }
+
+    async fn file_reader(&self, folder: &str, name: &str, _opts: FileGetOptions) -> Result<FileReader> {
+        let path = self.resolve(folder, Some(name))?;
+        let file = fs::File::open(&path).await.map_err(|e| Self::not_found(e, &path))?;
+        Ok(Box::new(file))
+    }
+
+    async fn file_put_stream(
+        &self,
+        folder: &str,
+        name: &str,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        _opts: FilePutOptions,
+    ) -> Result<u64> {
+        let path = self.resolve(folder, Some(name))?;
+        let parent = path.parent().unwrap_or(&self.root);
+        fs::create_dir_all(parent).await?;
+
+        // Write next to the target and rename, so readers never see a partial file
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("file");
+        let temp = parent.join(format!(".{}.{:016x}.tmp", file_name, rand::random::<u64>()));
+
+        let written = async {
+            let mut file = fs::File::create(&temp).await?;
+            let written = tokio::io::copy(reader, &mut file).await?;
+            file.sync_all().await?;
+            fs::rename(&temp, &path).await?;
+            Ok::<_, std::io::Error>(written)
+        }
+        .await;
+
+        match written {
+            Ok(written) => Ok(written),
+            Err(err) => {
+                let _ = fs::remove_file(&temp).await;
+                Err(Error::Io(err))
+            }
+        }
+    }
+
+    async fn file_list(&self, folder: &str, prefix: &str) -> Result<Vec<FileInfo>> {
+        let base = self.resolve(folder, None)?;
+        let mut files = Vec::new();
+        let mut pending = vec![base.clone()];
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(Error::Io(err)),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let metadata = entry.metadata().await?;
+                let path = entry.path();
+                if metadata.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+
+                // Skip writes still in progress
+                let file_name = entry.file_name();
+                let file_name = file_name.to_string_lossy();
+                if file_name.starts_with('.') && file_name.ends_with(".tmp") {
+                    continue;
+                }
+
+                let Ok(relative) = path.strip_prefix(&base) else { continue };
+                let name = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if !name.starts_with(prefix) {
+                    continue;
+                }
+
+                files.push(FileInfo {
+                    name,
+                    size: metadata.len(),
+                    modified: metadata.modified()
+                        .map(chrono::DateTime::<chrono::Utc>::from)
+                        .unwrap_or_default(),
+                    is_dir: false,
+                    content_type: None,
+                    metadata: HashMap::new(),
+                });
+            }
+        }
+
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    async fn file_delete(&self, folder: &str, name: &str) -> Result<()> {
+        let path = self.resolve(folder, Some(name))?;
+        match fs::remove_file(&path).await {
+            Ok(()) => {
+                self.prune_empty_dirs(&self.resolve(folder, None)?, &path).await;
+                Ok(())
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(Error::Io(err)),
+        }
+    }
+
+    async fn file_delete_prefix(&self, folder: &str, prefix: &str) -> Result<u64> {
+        let files = self.file_list(folder, prefix).await?;
+        for file in &files {
+            self.file_delete(folder, &file.name).await?;
+        }
+        Ok(files.len() as u64)
+    }
+
+    async fn file_versions(&self, folder: &str, name: &str) -> Result<Vec<FileVersion>> {
+        match self.file_stat(folder, name, FileStatOptions::default()).await {
+            Ok(info) => Ok(vec![FileVersion {
+                version_id: UNVERSIONED.to_string(),
+                size: info.size,
+                modified: info.modified,
+                is_latest: true,
+                is_delete_marker: false,
+            }]),
+            Err(Error::NotFound(_)) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn file_restore_version(&self, folder: &str, name: &str, version_id: &str) -> Result<()> {
+        if version_id == UNVERSIONED && self.file_exists(folder, name).await? {
+            return Ok(());
+        }
+        Err(Error::NotFound(format!("Version {} of {}/{}", version_id, folder, name)))
+    }
+
+    async fn file_presigned_url(&self, _folder: &str, _name: &str, _opts: FilePresignOptions) -> Result<String> {
+        Err(Error::InvalidData("Local storage cannot issue presigned URLs".to_string()))
+    }
+
+    fn protocol(&self) -> &str {
+        "file"
+    }
+}
+
+impl std::fmt::Display for LocalFileSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LocalFileSystem(root: {})", self.root.display())
+    }
+}
@@ -0,0 +1,514 @@
+use async_trait::async_trait;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Mutex;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use crate::{Error, Result};
+use super::{FileSystem, FilePutOptions, FileGetOptions, FileStatOptions, FilePresignOptions, FolderCreateOptions, FileInfo, FileReader, FileVersion, UNVERSIONED};
+
+/// Trait method a recorded call or injected failure refers to.
+///
+/// Streaming reads and writes count as `FileGet` and `FilePut`, restoring a
+/// version as `FilePut`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOp {
+    FolderExists,
+    FolderCreate,
+    FileExists,
+    FileGet,
+    FilePut,
+    FileStat,
+    FileList,
+    FileDelete,
+    FileVersions,
+}
+
+/// Kind of error an injected failure produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// [`Error::NotFound`], as for a missing file
+    NotFound,
+    /// Permission denied I/O error
+    Permission,
+    /// Timed-out I/O error that may succeed when retried
+    Transient,
+}
+
+impl FailureKind {
+    fn error(self, op: FileOp, path: &str) -> Error {
+        match self {
+            FailureKind::NotFound => Error::NotFound(path.to_string()),
+            FailureKind::Permission => Error::Io(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("Injected permission failure in {:?} for {}", op, path),
+            )),
+            FailureKind::Transient => Error::Io(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("Injected transient failure in {:?} for {}", op, path),
+            )),
+        }
+    }
+}
+
+/// A call received by [`InMemoryFileSystem`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCall {
+    pub op: FileOp,
+    pub folder: String,
+    pub name: Option<String>,
+}
+
+#[derive(Debug)]
+struct Failure {
+    op: FileOp,
+    kind: FailureKind,
+    /// Fails only the next matching call when true
+    once: bool,
+}
+
+#[derive(Debug, Clone)]
+struct StoredFile {
+    data: Vec<u8>,
+    modified: chrono::DateTime<chrono::Utc>,
+    version_id: String,
+    /// Content type and metadata it was written with
+    options: FilePutOptions,
+}
+
+/// Earlier version of a file in a versioned folder
+#[derive(Debug, Clone)]
+struct OldVersion {
+    version_id: String,
+    modified: chrono::DateTime<chrono::Utc>,
+    /// `None` for a deletion
+    data: Option<Vec<u8>>,
+    options: FilePutOptions,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    folders: BTreeSet<String>,
+    /// Top-level folders created with versioning
+    versioned: BTreeSet<String>,
+    files: BTreeMap<(String, String), StoredFile>,
+    /// Earlier versions per file, oldest first
+    history: BTreeMap<(String, String), Vec<OldVersion>>,
+    failures: Vec<Failure>,
+    calls: Vec<FileCall>,
+}
+
+impl State {
+    fn is_versioned(&self, folder: &str) -> bool {
+        self.versioned.contains(folder.split('/').next().unwrap_or(folder))
+    }
+
+    /// Replace a file, keeping the previous version in a versioned folder
+    fn put(&mut self, folder: &str, name: &str, data: Vec<u8>, options: FilePutOptions) {
+        let versioned = self.is_versioned(folder);
+        let key = (folder.to_string(), name.to_string());
+        self.folders.insert(folder.to_string());
+
+        let version_id = if versioned {
+            if let Some(previous) = self.files.remove(&key) {
+                self.history.entry(key.clone()).or_default().push(OldVersion {
+                    version_id: previous.version_id,
+                    modified: previous.modified,
+                    data: Some(previous.data),
+                    options: previous.options,
+                });
+            }
+            uuid::Uuid::new_v4().simple().to_string()
+        } else {
+            UNVERSIONED.to_string()
+        };
+
+        self.files.insert(key, StoredFile { data, modified: chrono::Utc::now(), version_id, options });
+    }
+
+    /// Remove a file, leaving a deletion on top of its versions in a
+    /// versioned folder
+    fn delete(&mut self, folder: &str, name: &str) {
+        let key = (folder.to_string(), name.to_string());
+        let Some(previous) = self.files.remove(&key) else { return };
+        if self.is_versioned(folder) {
+            let history = self.history.entry(key).or_default();
+            history.push(OldVersion {
+                version_id: previous.version_id,
+                modified: previous.modified,
+                data: Some(previous.data),
+                options: previous.options,
+            });
+            history.push(OldVersion {
+                version_id: uuid::Uuid::new_v4().simple().to_string(),
+                modified: chrono::Utc::now(),
+                data: None,
+                options: FilePutOptions::default(),
+            });
+        }
+    }
+
+    /// Files in `folder` and its subfolders whose path relative to `folder`
+    /// starts with `prefix`, with that path
+    fn list<'a>(&'a self, folder: &str, prefix: &str) -> Vec<(&'a (String, String), &'a StoredFile, String)> {
+        let nested = format!("{}/", folder);
+        self.files
+            .iter()
+            .filter_map(|(key, file)| {
+                let (file_folder, name) = key;
+                let path = if file_folder == folder {
+                    name.clone()
+                } else {
+                    format!("{}/{}", file_folder.strip_prefix(&nested)?, name)
+                };
+                path.starts_with(prefix).then_some((key, file, path))
+            })
+            .collect()
+    }
+
+    /// Contents and options of the current file, or of one of its versions
+    fn get(&self, folder: &str, name: &str, version_id: Option<&str>) -> Option<(&Vec<u8>, &FilePutOptions)> {
+        let key = (folder.to_string(), name.to_string());
+        let current = self.files.get(&key);
+        match version_id {
+            None => current.map(|f| (&f.data, &f.options)),
+            Some(id) => current
+                .filter(|f| f.version_id == id)
+                .map(|f| (&f.data, &f.options))
+                .or_else(|| {
+                    let old = self.history.get(&key)?.iter().find(|v| v.version_id == id)?;
+                    Some((old.data.as_ref()?, &old.options))
+                }),
+        }
+    }
+}
+
+/// Keeps files in memory, for tests and dry runs.
+///
+/// Every call is recorded, and failures can be injected per trait method.
+/// Folders are created implicitly when a file is written.
+#[derive(Debug, Default)]
+pub struct InMemoryFileSystem {
+    state: Mutex<State>,
+}
+
+impl InMemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a file directly, without recording a call
+    pub fn insert(&self, folder: &str, name: &str, data: impl Into<Vec<u8>>) {
+        self.state.lock().unwrap().put(folder, name, data.into(), FilePutOptions::default());
+    }
+
+    /// Contents of a file, without recording a call
+    pub fn contents(&self, folder: &str, name: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state.files.get(&(folder.to_string(), name.to_string())).map(|f| f.data.clone())
+    }
+
+    /// Remove a file directly, without recording a call
+    pub fn remove(&self, folder: &str, name: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.files.remove(&(folder.to_string(), name.to_string())).is_some()
+    }
+
+    /// Fail the next call of `op`
+    pub fn fail_next(&self, op: FileOp, kind: FailureKind) {
+        self.state.lock().unwrap().failures.push(Failure { op, kind, once: true });
+    }
+
+    /// Fail every call of `op` until [`clear_failures`](Self::clear_failures)
+    pub fn fail_always(&self, op: FileOp, kind: FailureKind) {
+        self.state.lock().unwrap().failures.push(Failure { op, kind, once: false });
+    }
+
+    pub fn clear_failures(&self) {
+        self.state.lock().unwrap().failures.clear();
+    }
+
+    /// Calls received so far, in order
+    pub fn calls(&self) -> Vec<FileCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// Calls of one method received so far
+    pub fn calls_of(&self, op: FileOp) -> Vec<FileCall> {
+        self.state.lock().unwrap().calls.iter().filter(|c| c.op == op).cloned().collect()
+    }
+
+    pub fn clear_calls(&self) {
+        self.state.lock().unwrap().calls.clear();
+    }
+
+    /// Record a call and apply any failure injected for it
+    fn enter(&self, op: FileOp, folder: &str, name: Option<&str>) -> Result<std::sync::MutexGuard<'_, State>> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(FileCall {
+            op,
+            folder: folder.to_string(),
+            name: name.map(str::to_string),
+        });
+
+        if let Some(index) = state.failures.iter().position(|f| f.op == op) {
+            let kind = state.failures[index].kind;
+            if state.failures[index].once {
+                state.failures.remove(index);
+            }
+            let path = match name {
+                Some(name) => format!("{}/{}", folder, name),
+                None => folder.to_string(),
+            };
+            return Err(kind.error(op, &path));
+        }
+
+        Ok(state)
+    }
+}
+
+#[async_trait]
+impl FileSystem for InMemoryFileSystem {
+    async fn folder_exists(&self, folder: &str) -> Result<bool> {
+        let state = self.enter(FileOp::FolderExists, folder, None)?;
+        Ok(state.folders.contains(folder))
+    }
+
+    async fn folder_create(&self, folder: &str, opts: FolderCreateOptions) -> Result<()> {
+        let mut state = self.enter(FileOp::FolderCreate, folder, None)?;
+        state.folders.insert(folder.to_string());
+        if opts.versioning || opts.object_locking {
+            state.versioned.insert(folder.to_string());
+        }
+        Ok(())
+    }
+
+    async fn file_exists(&self, folder: &str, name: &str) -> Result<bool> {
+        let state = self.enter(FileOp::FileExists, folder, Some(name))?;
+        Ok(state.files.contains_key(&(folder.to_string(), name.to_string())))
+    }
+
+    async fn file_get(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<Vec<u8>> {
+        let state = self.enter(FileOp::FileGet, folder, Some(name))?;
+        state.get(folder, name, opts.version_id.as_deref())
+            .map(|(data, _)| data.clone())
+            .ok_or_else(|| Error::NotFound(format!("{}/{}", folder, name)))
+    }
+
+    async fn file_put(&self, folder: &str, name: &str, data: &[u8], opts: FilePutOptions) -> Result<()> {
+        let mut state = self.enter(FileOp::FilePut, folder, Some(name))?;
+        state.put(folder, name, data.to_vec(), opts);
+        Ok(())
+    }
+
+    async fn file_write_bytes(&self, folder: &str, name: &str, data: Vec<u8>, opts: FilePutOptions) -> Result<()> {
+        self.file_put(folder, name, &data, opts).await
+    }
+
+    async fn file_read_bytes(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<Vec<u8>> {
+        self.file_get(folder, name, opts).await
+    }
+
+    async fn file_stat(&self, folder: &str, name: &str, _opts: FileStatOptions) -> Result<FileInfo> {
+        let state = self.enter(FileOp::FileStat, folder, Some(name))?;
+        let file = state.files
+            .get(&(folder.to_string(), name.to_string()))
+            .ok_or_else(|| Error::NotFound(format!("{}/{}", folder, name)))?;
+
+        Ok(FileInfo {
+            name: name.to_string(),
+            size: file.data.len() as u64,
+            modified: file.modified,
+            is_dir: false,
+            content_type: file.options.content_type.clone(),
+            metadata: file.options.metadata.clone(),
+        })
+    }
+
+    async fn file_reader(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<FileReader> {
+        let data = self.file_get(folder, name, opts).await?;
+        Ok(Box::new(std::io::Cursor::new(data)))
+    }
+
+    async fn file_put_stream(
+        &self,
+        folder: &str,
+        name: &str,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        opts: FilePutOptions,
+    ) -> Result<u64> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        self.file_put(folder, name, &data, opts).await?;
+        Ok(data.len() as u64)
+    }
+
+    async fn file_list(&self, folder: &str, prefix: &str) -> Result<Vec<FileInfo>> {
+        let state = self.enter(FileOp::FileList, folder, Some(prefix))?;
+
+        // Files in subfolders are listed with their relative path
+        let files = state.list(folder, prefix)
+            .into_iter()
+            .map(|(_, file, name)| FileInfo {
+                name,
+                size: file.data.len() as u64,
+                modified: file.modified,
+                is_dir: false,
+                content_type: None,
+                metadata: HashMap::new(),
+            })
+            .collect();
+
+        Ok(files)
+    }
+
+    async fn file_delete(&self, folder: &str, name: &str) -> Result<()> {
+        let mut state = self.enter(FileOp::FileDelete, folder, Some(name))?;
+        state.delete(folder, name);
+        Ok(())
+    }
+
+    async fn file_delete_prefix(&self, folder: &str, prefix: &str) -> Result<u64> {
+        let keys: Vec<(String, String)> = {
+            let state = self.enter(FileOp::FileList, folder, Some(prefix))?;
+            state.list(folder, prefix).into_iter().map(|(key, _, _)| key.clone()).collect()
+        };
+        // Deleted by the key they are stored under, as a listed path does
+        // not tell which of its slashes separate subfolders
+        for (file_folder, name) in &keys {
+            self.file_delete(file_folder, name).await?;
+        }
+        Ok(keys.len() as u64)
+    }
+
+    async fn file_versions(&self, folder: &str, name: &str) -> Result<Vec<FileVersion>> {
+        let state = self.enter(FileOp::FileVersions, folder, Some(name))?;
+        let key = (folder.to_string(), name.to_string());
+
+        let mut versions: Vec<FileVersion> = state.files.get(&key)
+            .map(|f| FileVersion {
+                version_id: f.version_id.clone(),
+                size: f.data.len() as u64,
+                modified: f.modified,
+                is_latest: true,
+                is_delete_marker: false,
+            })
+            .into_iter()
+            .collect();
+        for old in state.history.get(&key).into_iter().flatten().rev() {
+            versions.push(FileVersion {
+                version_id: old.version_id.clone(),
+                size: old.data.as_ref().map(|d| d.len() as u64).unwrap_or(0),
+                modified: old.modified,
+                // A deletion on top is the latest version
+                is_latest: versions.is_empty(),
+                is_delete_marker: old.data.is_none(),
+            });
+        }
+
+        Ok(versions)
+    }
+
+    async fn file_restore_version(&self, folder: &str, name: &str, version_id: &str) -> Result<()> {
+        let mut state = self.enter(FileOp::FilePut, folder, Some(name))?;
+        let (data, options) = state.get(folder, name, Some(version_id))
+            .map(|(data, options)| (data.clone(), options.clone()))
+            .ok_or_else(|| Error::NotFound(format!("Version {} of {}/{}", version_id, folder, name)))?;
+        state.put(folder, name, data, options);
+        Ok(())
+    }
+
+    async fn file_presigned_url(&self, _folder: &str, _name: &str, _opts: FilePresignOptions) -> Result<String> {
+        Err(Error::InvalidData("Memory storage cannot issue presigned URLs".to_string()))
+    }
+
+    fn protocol(&self) -> &str {
+        "memory"
+    }
+}
+
+impl std::fmt::Display for InMemoryFileSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock().unwrap();
+        write!(f, "InMemoryFileSystem(folders: {}, files: {})", state.folders.len(), state.files.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn fail_next_fails_one_call() {
+        let fs = InMemoryFileSystem::new();
+        fs.fail_next(FileOp::FilePut, FailureKind::Transient);
+
+        let err = fs.file_put("bucket", "a.txt", b"one", FilePutOptions::default()).await.unwrap_err();
+        assert!(matches!(err, Error::Io(ref e) if e.kind() == std::io::ErrorKind::TimedOut));
+        assert_eq!(fs.contents("bucket", "a.txt"), None);
+
+        fs.file_put("bucket", "a.txt", b"two", FilePutOptions::default()).await.unwrap();
+        assert_eq!(fs.contents("bucket", "a.txt").as_deref(), Some(b"two".as_slice()));
+    }
+
+    #[tokio::test]
+    async fn fail_always_fails_until_cleared() {
+        let fs = InMemoryFileSystem::new();
+        fs.insert("bucket", "a.txt", "data");
+        fs.fail_always(FileOp::FileGet, FailureKind::NotFound);
+
+        for _ in 0..2 {
+            let err = fs.file_get("bucket", "a.txt", FileGetOptions::default()).await.unwrap_err();
+            assert!(matches!(err, Error::NotFound(ref path) if path == "bucket/a.txt"));
+        }
+        // Other methods are unaffected
+        assert!(fs.file_exists("bucket", "a.txt").await.unwrap());
+
+        fs.clear_failures();
+        assert_eq!(fs.file_get("bucket", "a.txt", FileGetOptions::default()).await.unwrap(), b"data");
+    }
+
+    #[tokio::test]
+    async fn permission_failure_is_io_error() {
+        let fs = InMemoryFileSystem::new();
+        fs.fail_next(FileOp::FilePut, FailureKind::Permission);
+        let err = fs.file_put("bucket", "a.txt", b"data", FilePutOptions::default()).await.unwrap_err();
+        assert!(matches!(err, Error::Io(ref e) if e.kind() == std::io::ErrorKind::PermissionDenied));
+    }
+
+    #[tokio::test]
+    async fn delete_prefix_deletes_nested_names() {
+        let fs = InMemoryFileSystem::new();
+        fs.insert("bucket", "dir/a", "one");
+        fs.insert("bucket/dir", "b", "two");
+        fs.insert("bucket", "other", "three");
+
+        assert_eq!(fs.file_delete_prefix("bucket", "dir/").await.unwrap(), 2);
+        assert_eq!(fs.contents("bucket", "dir/a"), None);
+        assert_eq!(fs.contents("bucket/dir", "b"), None);
+        assert!(fs.contents("bucket", "other").is_some());
+    }
+
+    #[tokio::test]
+    async fn calls_are_recorded_in_order() {
+        let fs = InMemoryFileSystem::new();
+        fs.insert("bucket", "a.txt", "data");
+
+        fs.file_exists("bucket", "a.txt").await.unwrap();
+        let mut reader = fs.file_reader("bucket", "a.txt", FileGetOptions::default()).await.unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await.unwrap();
+        fs.file_put_stream("bucket", "b.txt", &mut data.as_slice(), FilePutOptions::default()).await.unwrap();
+        fs.fail_next(FileOp::FileStat, FailureKind::NotFound);
+        let _ = fs.file_stat("bucket", "b.txt", FileStatOptions::default()).await;
+
+        let ops: Vec<FileOp> = fs.calls().iter().map(|c| c.op).collect();
+        assert_eq!(ops, [FileOp::FileExists, FileOp::FileGet, FileOp::FilePut, FileOp::FileStat]);
+        assert_eq!(fs.calls_of(FileOp::FilePut), [FileCall {
+            op: FileOp::FilePut,
+            folder: "bucket".to_string(),
+            name: Some("b.txt".to_string()),
+        }]);
+
+        fs.clear_calls();
+        assert!(fs.calls().is_empty());
+    }
+}
@@ -1,13 +1,75 @@
 use async_trait::async_trait;
-use crate::{Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::{Error, Result};
+use crate::config::{Config, StorageType};
 
+pub mod encrypted;
+pub mod local;
+pub mod memory;
 pub mod s3;
+pub mod stream;
+pub mod webdav;
 
+pub use encrypted::{EncryptedFileSystem, Keyring, RekeyReport};
+pub use local::LocalFileSystem;
+pub use memory::InMemoryFileSystem;
 pub use s3::S3FileSystem;
+pub use stream::{FileReader, Md5Reader};
+pub use webdav::WebDavFileSystem;
 
-#[derive(Debug, Default)]
+/// Build the storage backend selected by `[storage] type`, encrypting files
+/// if `[encryption]` is configured
+pub async fn from_config(config: &Config) -> Result<Arc<dyn FileSystem>> {
+    let backend = backend_from_config(config).await?;
+    match &config.encryption {
+        Some(encryption) => {
+            let encrypted = EncryptedFileSystem::from_config(backend, encryption)?;
+            tracing::info!("Encrypting attachment files with key {}", encrypted.keyring().active());
+            Ok(Arc::new(encrypted))
+        }
+        None => Ok(backend),
+    }
+}
+
+/// Build the storage backend selected by `[storage] type` without encryption
+pub async fn backend_from_config(config: &Config) -> Result<Arc<dyn FileSystem>> {
+    match config.storage_type() {
+        StorageType::Local => {
+            let path = config.storage.as_ref()
+                .and_then(|s| s.path.as_deref())
+                .ok_or_else(|| Error::InvalidData("[storage] path is required for local storage".to_string()))?;
+            tracing::info!("Using local attachment storage at {}", path);
+            Ok(Arc::new(LocalFileSystem::new(path).await?))
+        }
+        StorageType::Memory => {
+            tracing::warn!("Using in-memory attachment storage; files are discarded on exit");
+            Ok(Arc::new(InMemoryFileSystem::new()))
+        }
+        StorageType::S3 => {
+            let s3 = config.s3.as_ref()
+                .ok_or_else(|| Error::InvalidData("[s3] section is required for s3 storage".to_string()))?;
+            tracing::info!("Using S3 attachment storage at {}", s3.endpoint);
+            Ok(Arc::new(S3FileSystem::new(&s3.endpoint, &s3.access_key_id, &s3.secret_access_key, s3.use_ssl).await?))
+        }
+        StorageType::WebDav => {
+            let webdav = config.webdav.as_ref()
+                .ok_or_else(|| Error::InvalidData("[webdav] section is required for webdav storage".to_string()))?;
+            tracing::info!("Using WebDAV attachment storage at {}", webdav.url);
+            Ok(Arc::new(WebDavFileSystem::new(&webdav.url, webdav.username.as_deref(), webdav.password.as_deref())?))
+        }
+    }
+}
+
+/// Metadata key of the MD5 of a file's contents
+pub const MD5_METADATA: &str = "md5";
+
+#[derive(Debug, Clone, Default)]
 pub struct FilePutOptions {
     pub content_type: Option<String>,
+    /// Stored with the file as user metadata; keys are lower case and
+    /// values ASCII. Backends without user metadata ignore it.
+    pub metadata: HashMap<String, String>,
 }
 
 #[derive(Debug, Default)]
@@ -18,9 +80,22 @@ pub struct FileGetOptions {
 #[derive(Debug, Default)]
 pub struct FileStatOptions {}
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone)]
+pub struct FilePresignOptions {
+    /// How long the URL stays valid
+    pub expires_in: std::time::Duration,
+    /// File name the browser should show or save the download as
+    pub download_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct FolderCreateOptions {
+    /// Enable object lock; implies versioning
     pub object_locking: bool,
+    /// Keep earlier versions of overwritten and deleted files
+    pub versioning: bool,
+    /// Default retention of new versions with object lock, in governance mode
+    pub retention_days: Option<u32>,
 }
 
 #[async_trait]
@@ -33,6 +108,34 @@ pub trait FileSystem: Send + Sync + std::fmt::Debug {
     async fn file_write_bytes(&self, folder: &str, name: &str, data: Vec<u8>, opts: FilePutOptions) -> Result<()>;
     async fn file_read_bytes(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<Vec<u8>>;
     async fn file_stat(&self, folder: &str, name: &str, opts: FileStatOptions) -> Result<FileInfo>;
+    /// Open a file for reading without loading it into memory
+    async fn file_reader(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<FileReader>;
+    /// Write a file from a stream; returns the number of bytes written
+    async fn file_put_stream(
+        &self,
+        folder: &str,
+        name: &str,
+        reader: &mut (dyn tokio::io::AsyncRead + Send + Unpin),
+        opts: FilePutOptions,
+    ) -> Result<u64>;
+    /// Files in `folder` whose name starts with `prefix`, recursively.
+    ///
+    /// Names are relative to `folder`.
+    async fn file_list(&self, folder: &str, prefix: &str) -> Result<Vec<FileInfo>>;
+    /// Delete a file; deleting a missing file is not an error
+    async fn file_delete(&self, folder: &str, name: &str) -> Result<()>;
+    /// Delete every file in `folder` whose name starts with `prefix`;
+    /// returns the number of files deleted
+    async fn file_delete_prefix(&self, folder: &str, prefix: &str) -> Result<u64>;
+    /// Stored versions of a file, newest first; a backend without
+    /// versioning reports the current file only
+    async fn file_versions(&self, folder: &str, name: &str) -> Result<Vec<FileVersion>>;
+    /// Make an earlier version the current one; the versions in between are
+    /// kept
+    async fn file_restore_version(&self, folder: &str, name: &str, version_id: &str) -> Result<()>;
+    /// Time-limited URL from which anyone holding it can download the file
+    /// without credentials; only object storage can issue these
+    async fn file_presigned_url(&self, folder: &str, name: &str, opts: FilePresignOptions) -> Result<String>;
     fn protocol(&self) -> &str;
 }
 
@@ -42,6 +145,37 @@ pub struct FileInfo {
     pub size: u64,
     pub modified: chrono::DateTime<chrono::Utc>,
     pub is_dir: bool,
+    /// MIME type recorded with the file, where the backend keeps one
+    pub content_type: Option<String>,
+    /// Metadata written through [`FilePutOptions::metadata`]; only
+    /// [`FileSystem::file_stat`] reads it
+    pub metadata: HashMap<String, String>,
+}
+
+impl FileInfo {
+    /// Options storing a copy of the file with the same content type and
+    /// metadata
+    pub fn put_options(&self) -> FilePutOptions {
+        FilePutOptions {
+            content_type: self.content_type.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+/// Version ID of a file stored without versioning, as S3 reports it
+pub const UNVERSIONED: &str = "null";
+
+/// One stored version of a file
+#[derive(Debug, Clone)]
+pub struct FileVersion {
+    pub version_id: String,
+    pub size: u64,
+    pub modified: chrono::DateTime<chrono::Utc>,
+    /// Whether this is the current version
+    pub is_latest: bool,
+    /// Whether the version records a deletion rather than contents
+    pub is_delete_marker: bool,
 }
 
 impl std::fmt::Display for FileInfo {
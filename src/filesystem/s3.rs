@@ -1,7 +1,65 @@
 use async_trait::async_trait;
-use aws_sdk_s3::{Client, primitives::ByteStream};
+use std::collections::HashMap;
+use aws_sdk_s3::{Client, presigning::PresigningConfig, primitives::ByteStream};
+use aws_sdk_s3::types::{
+    BucketVersioningStatus, CompletedMultipartUpload, CompletedPart, DefaultRetention, Delete, ObjectIdentifier,
+    ObjectLockConfiguration, ObjectLockEnabled, ObjectLockRetentionMode, ObjectLockRule, VersioningConfiguration,
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
 use crate::{Error, Result};
-use super::{FileSystem, FilePutOptions, FileGetOptions, FileStatOptions, FolderCreateOptions, FileInfo};
+use super::{FileSystem, FilePutOptions, FileGetOptions, FileStatOptions, FilePresignOptions, FolderCreateOptions, FileInfo, FileReader, FileVersion};
+
+/// Part size for multipart uploads; S3 requires at least 5 MiB for all but the last part
+const MULTIPART_PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Read up to `MULTIPART_PART_SIZE` bytes; a shorter result means the stream ended
+async fn read_part(reader: &mut (dyn AsyncRead + Send + Unpin)) -> Result<Vec<u8>> {
+    let mut part = Vec::with_capacity(MULTIPART_PART_SIZE);
+    reader.take(MULTIPART_PART_SIZE as u64).read_to_end(&mut part).await?;
+    Ok(part)
+}
+
+/// Split a folder into its bucket and the key prefix below it
+fn bucket_of(folder: &str) -> (&str, &str) {
+    folder.split_once('/').unwrap_or((folder, ""))
+}
+
+/// Bucket and object key of `name` in `folder`; folders may name a path
+/// inside a bucket, e.g. `attachments/ABCD1234`
+fn object_path(folder: &str, name: &str) -> (String, String) {
+    let (bucket, prefix) = bucket_of(folder);
+    let key = if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", prefix.trim_end_matches('/'), name)
+    };
+    (bucket.to_string(), key)
+}
+
+/// Percent-encode all but unreserved characters, and `/` if `keep_slash`
+fn percent_encode(value: &str, keep_slash: bool) -> String {
+    value.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            b'/' if keep_slash => "/".to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// `x-amz-copy-source` value of one version of an object
+fn copy_source(bucket: &str, key: &str, version_id: &str) -> String {
+    format!("{}/{}?versionId={}", bucket, percent_encode(key, true), version_id)
+}
+
+/// `Content-Disposition` showing a download inline under `file_name` (RFC 6266)
+fn inline_disposition(file_name: &str) -> String {
+    format!("inline; filename*=UTF-8''{}", percent_encode(file_name, false))
+}
+
+fn to_chrono(dt: &aws_sdk_s3::primitives::DateTime) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp(dt.secs(), dt.subsec_nanos()).unwrap_or_default()
+}
 
 #[derive(Debug)]
 pub struct S3FileSystem {
@@ -42,12 +100,71 @@ impl S3FileSystem {
             use_ssl,
         })
     }
+
+    /// Upload the parts of a started multipart upload, starting with `first`
+    async fn upload_parts(
+        &self,
+        folder: &str,
+        name: &str,
+        upload_id: &str,
+        first: Vec<u8>,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+    ) -> Result<u64> {
+        let (bucket, key) = object_path(folder, name);
+        let mut parts = Vec::new();
+        let mut written = 0u64;
+        let mut part = first;
+
+        loop {
+            let part_number = parts.len() as i32 + 1;
+            let last = part.len() < MULTIPART_PART_SIZE;
+            written += part.len() as u64;
+
+            // A stream that ends exactly on a part boundary leaves an empty part
+            if !part.is_empty() || parts.is_empty() {
+                let response = self.client
+                    .upload_part()
+                    .bucket(&bucket)
+                    .key(&key)
+                    .upload_id(upload_id)
+                    .part_number(part_number)
+                    .body(ByteStream::from(part))
+                    .send()
+                    .await
+                    .map_err(|e| Error::S3(Box::new(e.into())))?;
+
+                parts.push(
+                    CompletedPart::builder()
+                        .part_number(part_number)
+                        .set_e_tag(response.e_tag().map(str::to_string))
+                        .build(),
+                );
+            }
+
+            if last {
+                break;
+            }
+            part = read_part(reader).await?;
+        }
+
+        self.client
+            .complete_multipart_upload()
+            .bucket(&bucket)
+            .key(&key)
+            .upload_id(upload_id)
+            .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+            .send()
+            .await
+            .map_err(|e| Error::S3(Box::new(e.into())))?;
+
+        Ok(written)
+    }
 }
 
 #[async_trait]
 impl FileSystem for S3FileSystem {
     async fn folder_exists(&self, folder: &str) -> Result<bool> {
-        match self.client.head_bucket().bucket(folder).send().await {
+        match self.client.head_bucket().bucket(bucket_of(folder).0).send().await {
             Ok(_) => Ok(true),
             Err(err) => {
                 if let Some(service_err) = err.as_service_error() {
@@ -55,26 +172,62 @@ impl FileSystem for S3FileSystem {
                         return Ok(false);
                     }
                 }
-                Err(Error::S3(err.into()))
+                Err(Error::S3(Box::new(err.into())))
             }
         }
     }
 
-    async fn folder_create(&self, folder: &str, _opts: FolderCreateOptions) -> Result<()> {
+    async fn folder_create(&self, folder: &str, opts: FolderCreateOptions) -> Result<()> {
+        let bucket = bucket_of(folder).0;
         self.client
             .create_bucket()
-            .bucket(folder)
+            .bucket(bucket)
+            .object_lock_enabled_for_bucket(opts.object_locking)
             .send()
             .await
-            .map_err(|e| Error::S3(e.into()))?;
+            .map_err(|e| Error::S3(Box::new(e.into())))?;
+
+        // Object lock turns versioning on by itself
+        if opts.versioning && !opts.object_locking {
+            self.client
+                .put_bucket_versioning()
+                .bucket(bucket)
+                .versioning_configuration(
+                    VersioningConfiguration::builder().status(BucketVersioningStatus::Enabled).build(),
+                )
+                .send()
+                .await
+                .map_err(|e| Error::S3(Box::new(e.into())))?;
+        }
+
+        if let Some(days) = opts.retention_days.filter(|_| opts.object_locking) {
+            let retention = DefaultRetention::builder()
+                .mode(ObjectLockRetentionMode::Governance)
+                .days(days as i32)
+                .build();
+            self.client
+                .put_object_lock_configuration()
+                .bucket(bucket)
+                .object_lock_configuration(
+                    ObjectLockConfiguration::builder()
+                        .object_lock_enabled(ObjectLockEnabled::Enabled)
+                        .rule(ObjectLockRule::builder().default_retention(retention).build())
+                        .build(),
+                )
+                .send()
+                .await
+                .map_err(|e| Error::S3(Box::new(e.into())))?;
+        }
+
         Ok(())
     }
 
     async fn file_exists(&self, folder: &str, name: &str) -> Result<bool> {
+        let (bucket, key) = object_path(folder, name);
         match self.client
             .head_object()
-            .bucket(folder)
-            .key(name)
+            .bucket(&bucket)
+            .key(&key)
             .send()
             .await
         {
@@ -85,35 +238,40 @@ impl FileSystem for S3FileSystem {
                         return Ok(false);
                     }
                 }
-                Err(Error::S3(err.into()))
+                Err(Error::S3(Box::new(err.into())))
             }
         }
     }
 
     async fn file_get(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<Vec<u8>> {
-        let mut request = self.client.get_object().bucket(folder).key(name);
+        let (bucket, key) = object_path(folder, name);
+        let mut request = self.client.get_object().bucket(&bucket).key(&key);
         
         if let Some(version_id) = opts.version_id {
             request = request.version_id(version_id);
         }
 
-        let response = request.send().await.map_err(|e| Error::S3(e.into()))?;
-        let data = response.body.collect().await.map_err(|e| Error::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))?;
+        let response = request.send().await.map_err(|e| Error::S3(Box::new(e.into())))?;
+        let data = response.body.collect().await.map_err(|e| Error::Io(std::io::Error::other(e)))?;
         Ok(data.into_bytes().to_vec())
     }
 
     async fn file_put(&self, folder: &str, name: &str, data: &[u8], opts: FilePutOptions) -> Result<()> {
+        let (bucket, key) = object_path(folder, name);
         let mut request = self.client
             .put_object()
-            .bucket(folder)
-            .key(name)
+            .bucket(&bucket)
+            .key(&key)
             .body(ByteStream::from(data.to_vec()));
 
         if let Some(content_type) = opts.content_type {
             request = request.content_type(content_type);
         }
+        if !opts.metadata.is_empty() {
+            request = request.set_metadata(Some(opts.metadata));
+        }
 
-        request.send().await.map_err(|e| Error::S3(e.into()))?;
+        request.send().await.map_err(|e| Error::S3(Box::new(e.into())))?;
         Ok(())
     }
 
@@ -126,27 +284,261 @@ impl FileSystem for S3FileSystem {
     }
 
     async fn file_stat(&self, folder: &str, name: &str, _opts: FileStatOptions) -> Result<FileInfo> {
+        let (bucket, key) = object_path(folder, name);
         let response = self.client
             .head_object()
-            .bucket(folder)
-            .key(name)
+            .bucket(&bucket)
+            .key(&key)
             .send()
             .await
-            .map_err(|e| Error::S3(e.into()))?;
+            .map_err(|e| Error::S3(Box::new(e.into())))?;
 
         let size = response.content_length().unwrap_or(0) as u64;
-        let modified = response.last_modified()
-            .map(|dt| chrono::DateTime::from_timestamp(dt.secs(), dt.subsec_nanos()).unwrap_or_default())
-            .unwrap_or_default();
+        let modified = response.last_modified().map(to_chrono).unwrap_or_default();
 
         Ok(FileInfo {
             name: name.to_string(),
             size,
             modified,
             is_dir: false,
+            content_type: response.content_type().map(str::to_string),
+            metadata: response.metadata().cloned().unwrap_or_default(),
         })
     }
 
+    async fn file_reader(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<FileReader> {
+        let (bucket, key) = object_path(folder, name);
+        let mut request = self.client.get_object().bucket(&bucket).key(&key);
+
+        if let Some(version_id) = opts.version_id {
+            request = request.version_id(version_id);
+        }
+
+        let response = request.send().await.map_err(|e| Error::S3(Box::new(e.into())))?;
+        Ok(Box::new(response.body.into_async_read()))
+    }
+
+    async fn file_put_stream(
+        &self,
+        folder: &str,
+        name: &str,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        opts: FilePutOptions,
+    ) -> Result<u64> {
+        let (bucket, key) = object_path(folder, name);
+        let first = read_part(reader).await?;
+
+        // Small files fit in a single request
+        if first.len() < MULTIPART_PART_SIZE {
+            let written = first.len() as u64;
+            self.file_put(folder, name, &first, opts).await?;
+            return Ok(written);
+        }
+
+        let upload = self.client
+            .create_multipart_upload()
+            .bucket(&bucket)
+            .key(&key)
+            .set_content_type(opts.content_type)
+            .set_metadata(Some(opts.metadata).filter(|m| !m.is_empty()))
+            .send()
+            .await
+            .map_err(|e| Error::S3(Box::new(e.into())))?;
+        let upload_id = upload.upload_id()
+            .ok_or_else(|| Error::InvalidData("S3 returned no multipart upload id".to_string()))?
+            .to_string();
+
+        match self.upload_parts(folder, name, &upload_id, first, reader).await {
+            Ok(written) => Ok(written),
+            Err(err) => {
+                // Don't leave orphaned parts behind
+                if let Err(abort_err) = self.client
+                    .abort_multipart_upload()
+                    .bucket(&bucket)
+                    .key(&key)
+                    .upload_id(&upload_id)
+                    .send()
+                    .await
+                {
+                    tracing::warn!("Failed to abort multipart upload of {}/{}: {}", folder, name, abort_err);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn file_list(&self, folder: &str, prefix: &str) -> Result<Vec<FileInfo>> {
+        let (bucket, key_prefix) = object_path(folder, prefix);
+        let strip = key_prefix.len() - prefix.len();
+
+        let mut files = Vec::new();
+        let mut continuation: Option<String> = None;
+
+        loop {
+            let response = self.client
+                .list_objects_v2()
+                .bucket(&bucket)
+                .prefix(&key_prefix)
+                .set_continuation_token(continuation.take())
+                .send()
+                .await
+                .map_err(|e| Error::S3(Box::new(e.into())))?;
+
+            for object in response.contents() {
+                let Some(key) = object.key() else { continue };
+                files.push(FileInfo {
+                    name: key[strip..].to_string(),
+                    size: object.size().unwrap_or(0) as u64,
+                    modified: object.last_modified().map(to_chrono).unwrap_or_default(),
+                    is_dir: false,
+                    content_type: None,
+                    metadata: HashMap::new(),
+                });
+            }
+
+            match response.next_continuation_token() {
+                Some(token) if response.is_truncated().unwrap_or(false) => continuation = Some(token.to_string()),
+                _ => break,
+            }
+        }
+
+        Ok(files)
+    }
+
+    async fn file_delete(&self, folder: &str, name: &str) -> Result<()> {
+        let (bucket, key) = object_path(folder, name);
+        self.client
+            .delete_object()
+            .bucket(&bucket)
+            .key(&key)
+            .send()
+            .await
+            .map_err(|e| Error::S3(Box::new(e.into())))?;
+        Ok(())
+    }
+
+    async fn file_delete_prefix(&self, folder: &str, prefix: &str) -> Result<u64> {
+        let files = self.file_list(folder, prefix).await?;
+        let (bucket, _) = bucket_of(folder);
+
+        // DeleteObjects accepts up to 1000 keys per request
+        for chunk in files.chunks(1000) {
+            let objects = chunk
+                .iter()
+                .map(|file| {
+                    let (_, key) = object_path(folder, &file.name);
+                    ObjectIdentifier::builder().key(key).build()
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| Error::InvalidData(e.to_string()))?;
+            let delete = Delete::builder()
+                .set_objects(Some(objects))
+                .quiet(true)
+                .build()
+                .map_err(|e| Error::InvalidData(e.to_string()))?;
+
+            let response = self.client
+                .delete_objects()
+                .bucket(bucket)
+                .delete(delete)
+                .send()
+                .await
+                .map_err(|e| Error::S3(Box::new(e.into())))?;
+
+            if let Some(failed) = response.errors().first() {
+                return Err(Error::InvalidData(format!(
+                    "Failed to delete {} of {} objects in {}, first: {} ({})",
+                    response.errors().len(),
+                    chunk.len(),
+                    folder,
+                    failed.key().unwrap_or_default(),
+                    failed.message().unwrap_or_default(),
+                )));
+            }
+        }
+
+        Ok(files.len() as u64)
+    }
+
+    async fn file_versions(&self, folder: &str, name: &str) -> Result<Vec<FileVersion>> {
+        let (bucket, key) = object_path(folder, name);
+        let mut versions = Vec::new();
+        let mut key_marker: Option<String> = None;
+        let mut version_marker: Option<String> = None;
+
+        loop {
+            let response = self.client
+                .list_object_versions()
+                .bucket(&bucket)
+                .prefix(&key)
+                .set_key_marker(key_marker.take())
+                .set_version_id_marker(version_marker.take())
+                .send()
+                .await
+                .map_err(|e| Error::S3(Box::new(e.into())))?;
+
+            // The prefix also matches longer keys
+            for version in response.versions().iter().filter(|v| v.key() == Some(key.as_str())) {
+                versions.push(FileVersion {
+                    version_id: version.version_id().unwrap_or("null").to_string(),
+                    size: version.size().unwrap_or(0) as u64,
+                    modified: version.last_modified().map(to_chrono).unwrap_or_default(),
+                    is_latest: version.is_latest().unwrap_or(false),
+                    is_delete_marker: false,
+                });
+            }
+            for marker in response.delete_markers().iter().filter(|m| m.key() == Some(key.as_str())) {
+                versions.push(FileVersion {
+                    version_id: marker.version_id().unwrap_or("null").to_string(),
+                    size: 0,
+                    modified: marker.last_modified().map(to_chrono).unwrap_or_default(),
+                    is_latest: marker.is_latest().unwrap_or(false),
+                    is_delete_marker: true,
+                });
+            }
+
+            if !response.is_truncated().unwrap_or(false) {
+                break;
+            }
+            key_marker = response.next_key_marker().map(str::to_string);
+            version_marker = response.next_version_id_marker().map(str::to_string);
+        }
+
+        versions.sort_by(|a, b| b.is_latest.cmp(&a.is_latest).then(b.modified.cmp(&a.modified)));
+        Ok(versions)
+    }
+
+    async fn file_restore_version(&self, folder: &str, name: &str, version_id: &str) -> Result<()> {
+        let (bucket, key) = object_path(folder, name);
+        // Copying a version onto its own key stores it as the newest one;
+        // S3 copies objects of up to 5 GB this way
+        self.client
+            .copy_object()
+            .bucket(&bucket)
+            .key(&key)
+            .copy_source(copy_source(&bucket, &key, version_id))
+            .send()
+            .await
+            .map_err(|e| Error::S3(Box::new(e.into())))?;
+        Ok(())
+    }
+
+    async fn file_presigned_url(&self, folder: &str, name: &str, opts: FilePresignOptions) -> Result<String> {
+        let (bucket, key) = object_path(folder, name);
+        let presigning = PresigningConfig::expires_in(opts.expires_in)
+            .map_err(|e| Error::InvalidData(format!("Invalid presigned URL lifetime: {}", e)))?;
+
+        let mut request = self.client.get_object().bucket(&bucket).key(&key);
+        if let Some(download_name) = &opts.download_name {
+            request = request.response_content_disposition(inline_disposition(download_name));
+        }
+        let presigned = request
+            .presigned(presigning)
+            .await
+            .map_err(|e| Error::S3(Box::new(e.into())))?;
+        Ok(presigned.uri().to_string())
+    }
+
     fn protocol(&self) -> &str {
         if self.use_ssl {
             "https"
@@ -0,0 +1,60 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+use crate::Result;
+
+/// Readable file contents that are not loaded into memory at once
+pub type FileReader = Box<dyn AsyncRead + Send + Sync + Unpin>;
+
+/// Passes bytes through while computing their MD5 and counting them
+pub struct Md5Reader<R> {
+    inner: R,
+    context: md5::Context,
+    bytes: u64,
+}
+
+impl<R> Md5Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            context: md5::Context::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Number of bytes read so far
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Hex MD5 of the bytes read so far
+    pub fn hex_digest(&self) -> String {
+        format!("{:x}", self.context.clone().compute())
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Md5Reader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = result {
+            let read = &buf.filled()[before..];
+            self.context.consume(read);
+            self.bytes += read.len() as u64;
+        }
+
+        result
+    }
+}
+
+/// Read a stream to its end, returning its hex MD5 and length
+pub async fn md5_of<R: AsyncRead + Unpin>(reader: R) -> Result<(String, u64)> {
+    let mut hashing = Md5Reader::new(reader);
+    tokio::io::copy(&mut hashing, &mut tokio::io::sink()).await?;
+    Ok((hashing.hex_digest(), hashing.bytes_read()))
+}
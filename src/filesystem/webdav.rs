@@ -0,0 +1,572 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use futures_util::TryStreamExt;
+use reqwest::{Client, Method, RequestBuilder, StatusCode, header};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::io::StreamReader;
+use url::Url;
+use crate::{Error, Result};
+use super::{FileSystem, FilePutOptions, FileGetOptions, FileStatOptions, FilePresignOptions, FolderCreateOptions, FileInfo, FileReader, FileVersion, UNVERSIONED};
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:propfind xmlns:D="DAV:"><D:prop><D:resourcetype/><D:getcontentlength/><D:getlastmodified/></D:prop></D:propfind>"#;
+
+/// Multi-Status, the response to PROPFIND
+const MULTI_STATUS: u16 = 207;
+
+fn propfind() -> Method {
+    Method::from_bytes(b"PROPFIND").expect("valid method")
+}
+
+fn mkcol() -> Method {
+    Method::from_bytes(b"MKCOL").expect("valid method")
+}
+
+fn status_error(status: StatusCode, what: &str) -> Error {
+    Error::Api {
+        code: status.as_u16(),
+        message: format!("WebDAV {} failed: {}", what, status),
+    }
+}
+
+/// Path segments of a folder and file name; `..` and `.` are rejected
+fn segments<'a>(folder: &'a str, name: Option<&'a str>) -> Result<Vec<&'a str>> {
+    let segments: Vec<&str> = folder.split('/')
+        .chain(name.into_iter().flat_map(|n| n.split('/')))
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.iter().any(|s| *s == ".." || *s == ".") {
+        return Err(Error::InvalidData(format!("Invalid WebDAV path: {}/{}", folder, name.unwrap_or(""))));
+    }
+    Ok(segments)
+}
+
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Ok(byte) = u8::from_str_radix(&segment[i + 1..i + 3], 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Decoded, non-empty path segments of a URL
+fn decoded_path(url: &Url) -> Vec<String> {
+    url.path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).map(percent_decode).collect())
+        .unwrap_or_default()
+}
+
+fn parse_http_date(value: &str) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::parse_from_rfc2822(value)
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+        .unwrap_or_default()
+}
+
+/// Resource from a PROPFIND response
+struct Resource {
+    /// Decoded path segments
+    path: Vec<String>,
+    is_collection: bool,
+    size: u64,
+    modified: chrono::DateTime<chrono::Utc>,
+}
+
+/// Stores files on a WebDAV server, one collection per folder.
+///
+/// Folders may name nested collections, and file names containing `/` are
+/// stored in subcollections, which are created as needed.
+#[derive(Debug)]
+pub struct WebDavFileSystem {
+    client: Client,
+    /// Root collection, with a trailing slash
+    base: Url,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl WebDavFileSystem {
+    pub fn new(url: &str, username: Option<&str>, password: Option<&str>) -> Result<Self> {
+        let mut base = Url::parse(url)?;
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+
+        Ok(Self {
+            client: Client::new(),
+            base,
+            username: username.map(str::to_string),
+            password: password.map(str::to_string),
+        })
+    }
+
+    fn url(&self, segments: &[&str], collection: bool) -> Url {
+        let mut url = self.base.clone();
+        {
+            let mut path = url.path_segments_mut().expect("base URL has a path");
+            path.pop_if_empty().extend(segments);
+            if collection {
+                path.push("");
+            }
+        }
+        url
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        let request = self.client.request(method, url);
+        match &self.username {
+            Some(username) => request.basic_auth(username, self.password.as_deref()),
+            None => request,
+        }
+    }
+
+    /// Create the collections along `segments`; existing ones are kept
+    async fn create_collections(&self, segments: &[&str]) -> Result<()> {
+        for depth in 1..=segments.len() {
+            let response = self.request(mkcol(), self.url(&segments[..depth], true)).send().await?;
+            // 405: the collection exists already
+            if !response.status().is_success() && response.status() != StatusCode::METHOD_NOT_ALLOWED {
+                return Err(status_error(response.status(), "MKCOL"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Resources of a PROPFIND at `depth`; `None` if the target does not exist
+    async fn propfind(&self, url: Url, depth: &str) -> Result<Option<Vec<Resource>>> {
+        let response = self.request(propfind(), url)
+            .header("Depth", depth)
+            .header(header::CONTENT_TYPE, "application/xml")
+            .body(PROPFIND_BODY)
+            .send()
+            .await?;
+
+        match response.status().as_u16() {
+            MULTI_STATUS => {}
+            404 => return Ok(None),
+            _ => return Err(status_error(response.status(), "PROPFIND")),
+        }
+
+        let body = response.text().await?;
+        self.parse_multistatus(&body).map(Some)
+    }
+
+    fn parse_multistatus(&self, body: &str) -> Result<Vec<Resource>> {
+        let document = roxmltree::Document::parse(body)
+            .map_err(|e| Error::InvalidData(format!("Invalid WebDAV response: {}", e)))?;
+        let dav = |node: &roxmltree::Node, name: &str| node.is_element() && node.tag_name().name() == name && node.tag_name().namespace() == Some("DAV:");
+
+        let mut resources = Vec::new();
+        for response in document.descendants().filter(|n| dav(n, "response")) {
+            let Some(href) = response.children().find(|n| dav(n, "href")).and_then(|n| n.text()) else { continue };
+            let Ok(url) = self.base.join(href.trim()) else { continue };
+            let path = decoded_path(&url);
+
+            let prop = |name: &str| response.descendants().find(|n| dav(n, name));
+            resources.push(Resource {
+                path,
+                is_collection: prop("resourcetype").is_some_and(|n| n.children().any(|c| dav(&c, "collection"))),
+                size: prop("getcontentlength").and_then(|n| n.text()).and_then(|t| t.trim().parse().ok()).unwrap_or(0),
+                modified: prop("getlastmodified").and_then(|n| n.text()).map(|t| parse_http_date(t.trim())).unwrap_or_default(),
+            });
+        }
+
+        Ok(resources)
+    }
+}
+
+#[async_trait]
+impl FileSystem for WebDavFileSystem {
+    async fn folder_exists(&self, folder: &str) -> Result<bool> {
+        let url = self.url(&segments(folder, None)?, true);
+        Ok(self.propfind(url, "0").await?.is_some())
+    }
+
+    async fn folder_create(&self, folder: &str, opts: FolderCreateOptions) -> Result<()> {
+        if opts.versioning || opts.object_locking {
+            return Err(Error::InvalidData("WebDAV storage does not keep file versions".to_string()));
+        }
+        self.create_collections(&segments(folder, None)?).await
+    }
+
+    async fn file_exists(&self, folder: &str, name: &str) -> Result<bool> {
+        let url = self.url(&segments(folder, Some(name))?, false);
+        let response = self.request(Method::HEAD, url).send().await?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            status => Err(status_error(status, "HEAD")),
+        }
+    }
+
+    async fn file_get(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<Vec<u8>> {
+        let mut reader = self.file_reader(folder, name, opts).await?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
+    async fn file_put(&self, folder: &str, name: &str, data: &[u8], opts: FilePutOptions) -> Result<()> {
+        let segments = segments(folder, Some(name))?;
+        let url = self.url(&segments, false);
+
+        let put = || {
+            let mut request = self.request(Method::PUT, url.clone()).body(data.to_vec());
+            if let Some(content_type) = &opts.content_type {
+                request = request.header(header::CONTENT_TYPE, content_type);
+            }
+            request.send()
+        };
+
+        let mut response = put().await?;
+        // 409: a parent collection is missing
+        if response.status() == StatusCode::CONFLICT {
+            self.create_collections(&segments[..segments.len() - 1]).await?;
+            response = put().await?;
+        }
+        if !response.status().is_success() {
+            return Err(status_error(response.status(), "PUT"));
+        }
+        Ok(())
+    }
+
+    async fn file_write_bytes(&self, folder: &str, name: &str, data: Vec<u8>, opts: FilePutOptions) -> Result<()> {
+        self.file_put(folder, name, &data, opts).await
+    }
+
+    async fn file_read_bytes(&self, folder: &str, name: &str, opts: FileGetOptions) -> Result<Vec<u8>> {
+        self.file_get(folder, name, opts).await
+    }
+
+    async fn file_stat(&self, folder: &str, name: &str, _opts: FileStatOptions) -> Result<FileInfo> {
+        let url = self.url(&segments(folder, Some(name))?, false);
+        let response = self.request(Method::HEAD, url).send().await?;
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::NOT_FOUND => return Err(Error::NotFound(format!("{}/{}", folder, name))),
+            status => return Err(status_error(status, "HEAD")),
+        }
+
+        let headers = response.headers();
+        let size = headers.get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let modified = headers.get(header::LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+            .map(parse_http_date)
+            .unwrap_or_default();
+        let content_type = headers.get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        Ok(FileInfo {
+            name: name.to_string(),
+            size,
+            modified,
+            is_dir: false,
+            content_type,
+            metadata: HashMap::new(),
+        })
+    }
+
+    async fn file_reader(&self, folder: &str, name: &str, _opts: FileGetOptions) -> Result<FileReader> {
+        let url = self.url(&segments(folder, Some(name))?, false);
+        let response = self.request(Method::GET, url).send().await?;
+        match response.status() {
+            status if status.is_success() => {}
+            StatusCode::NOT_FOUND => return Err(Error::NotFound(format!("{}/{}", folder, name))),
+            status => return Err(status_error(status, "GET")),
+        }
+
+        let stream = response.bytes_stream().map_err(std::io::Error::other);
+        Ok(Box::new(StreamReader::new(Box::pin(stream))))
+    }
+
+    /// WebDAV has no multipart upload, so the file is read into memory and
+    /// sent in one request
+    async fn file_put_stream(
+        &self,
+        folder: &str,
+        name: &str,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        opts: FilePutOptions,
+    ) -> Result<u64> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        self.file_put(folder, name, &data, opts).await?;
+        Ok(data.len() as u64)
+    }
+
+    async fn file_list(&self, folder: &str, prefix: &str) -> Result<Vec<FileInfo>> {
+        let root = segments(folder, None)?;
+        let root_depth = decoded_path(&self.base).len() + root.len();
+        let mut files = Vec::new();
+
+        // Start at the deepest collection the prefix names
+        let start = prefix.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+        let mut pending = vec![self.url(&segments(folder, Some(start))?, true)];
+
+        // Servers often refuse `Depth: infinity`, so walk one level at a time
+        while let Some(url) = pending.pop() {
+            let own_path = decoded_path(&url);
+            let Some(resources) = self.propfind(url, "1").await? else { continue };
+
+            for resource in resources {
+                if resource.path == own_path || resource.path.len() <= root_depth {
+                    continue;
+                }
+                let name = resource.path[root_depth..].join("/");
+
+                if resource.is_collection {
+                    let dir = format!("{}/", name);
+                    if dir.starts_with(prefix) || prefix.starts_with(&dir) {
+                        let path: Vec<&str> = resource.path.iter().skip(root_depth).map(String::as_str).collect();
+                        pending.push(self.url(&[root.as_slice(), path.as_slice()].concat(), true));
+                    }
+                } else if name.starts_with(prefix) {
+                    files.push(FileInfo {
+                        name,
+                        size: resource.size,
+                        modified: resource.modified,
+                        is_dir: false,
+                        content_type: None,
+                        metadata: HashMap::new(),
+                    });
+                }
+            }
+        }
+
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    async fn file_delete(&self, folder: &str, name: &str) -> Result<()> {
+        let url = self.url(&segments(folder, Some(name))?, false);
+        let response = self.request(Method::DELETE, url).send().await?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::NOT_FOUND => Ok(()),
+            status => Err(status_error(status, "DELETE")),
+        }
+    }
+
+    async fn file_delete_prefix(&self, folder: &str, prefix: &str) -> Result<u64> {
+        let files = self.file_list(folder, prefix).await?;
+        for file in &files {
+            self.file_delete(folder, &file.name).await?;
+        }
+        Ok(files.len() as u64)
+    }
+
+    async fn file_versions(&self, folder: &str, name: &str) -> Result<Vec<FileVersion>> {
+        match self.file_stat(folder, name, FileStatOptions::default()).await {
+            Ok(info) => Ok(vec![FileVersion {
+                version_id: UNVERSIONED.to_string(),
+                size: info.size,
+                modified: info.modified,
+                is_latest: true,
+                is_delete_marker: false,
+            }]),
+            Err(Error::NotFound(_)) => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn file_restore_version(&self, folder: &str, name: &str, version_id: &str) -> Result<()> {
+        if version_id == UNVERSIONED && self.file_exists(folder, name).await? {
+            return Ok(());
+        }
+        Err(Error::NotFound(format!("Version {} of {}/{}", version_id, folder, name)))
+    }
+
+    async fn file_presigned_url(&self, _folder: &str, _name: &str, _opts: FilePresignOptions) -> Result<String> {
+        Err(Error::InvalidData("WebDAV storage cannot issue presigned URLs".to_string()))
+    }
+
+    fn protocol(&self) -> &str {
+        "webdav"
+    }
+}
+
+impl std::fmt::Display for WebDavFileSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WebDavFileSystem(url: {})", self.base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::{Arc, Mutex};
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::{StatusCode, Uri};
+
+    /// Collections, with a trailing slash, and files of a stand-in server
+    #[derive(Default)]
+    struct Tree {
+        collections: BTreeSet<String>,
+        files: BTreeMap<String, Vec<u8>>,
+    }
+
+    type Shared = Arc<Mutex<Tree>>;
+
+    fn parent(path: &str) -> &str {
+        let trimmed = path.trim_end_matches('/');
+        &trimmed[..trimmed.rfind('/').map_or(0, |i| i + 1)]
+    }
+
+    fn multistatus(entries: &[(String, Option<usize>)]) -> String {
+        let responses: String = entries.iter()
+            .map(|(path, size)| {
+                let props = match size {
+                    None => "<D:resourcetype><D:collection/></D:resourcetype>".to_string(),
+                    Some(size) => format!(
+                        "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>\
+                         <D:getlastmodified>Mon, 01 Jan 2024 00:00:00 GMT</D:getlastmodified>",
+                        size
+                    ),
+                };
+                format!(
+                    "<D:response><D:href>{}</D:href><D:propstat><D:prop>{}</D:prop>\
+                     <D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+                    path.replace(' ', "%20"),
+                    props
+                )
+            })
+            .collect();
+        format!(r#"<?xml version="1.0"?><D:multistatus xmlns:D="DAV:">{}</D:multistatus>"#, responses)
+    }
+
+    /// Just enough of WebDAV for the backend: PROPFIND at depth 1 on
+    /// collections, MKCOL and PUT
+    async fn dav(State(tree): State<Shared>, method: axum::http::Method, uri: Uri, body: Bytes) -> (StatusCode, String) {
+        let path = percent_decode(uri.path());
+        let mut tree = tree.lock().unwrap();
+
+        match method.as_str() {
+            "PROPFIND" if tree.collections.contains(&path) => {
+                let mut entries = vec![(path.clone(), None)];
+                entries.extend(tree.collections.iter()
+                    .filter(|c| *c != &path && parent(c) == path)
+                    .map(|c| (c.clone(), None)));
+                entries.extend(tree.files.iter()
+                    .filter(|(f, _)| parent(f) == path)
+                    .map(|(f, data)| (f.clone(), Some(data.len()))));
+                (StatusCode::MULTI_STATUS, multistatus(&entries))
+            }
+            "MKCOL" if tree.collections.contains(&path) => (StatusCode::METHOD_NOT_ALLOWED, String::new()),
+            "MKCOL" | "PUT" if !tree.collections.contains(parent(&path)) => (StatusCode::CONFLICT, String::new()),
+            "MKCOL" => {
+                tree.collections.insert(path);
+                (StatusCode::CREATED, String::new())
+            }
+            "PUT" => {
+                tree.files.insert(path, body.to_vec());
+                (StatusCode::CREATED, String::new())
+            }
+            _ => (StatusCode::NOT_FOUND, String::new()),
+        }
+    }
+
+    /// Backend talking to a stand-in server rooted at `/dav/`, which holds
+    /// `files` and the collections they are in
+    async fn server(files: &[&str]) -> (WebDavFileSystem, Shared) {
+        let mut tree = Tree::default();
+        tree.collections.insert("/dav/".to_string());
+        for file in files {
+            let path = format!("/dav/{}", file);
+            let mut dir = parent(&path);
+            while dir.len() > "/dav/".len() {
+                tree.collections.insert(dir.to_string());
+                dir = parent(dir);
+            }
+            tree.files.insert(path, file.as_bytes().to_vec());
+        }
+        let tree = Arc::new(Mutex::new(tree));
+
+        let app = axum::Router::new().fallback(dav).with_state(tree.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let fs = WebDavFileSystem::new(&format!("http://{}/dav", address), None, None).unwrap();
+        (fs, tree)
+    }
+
+    fn names(files: Vec<FileInfo>) -> Vec<String> {
+        files.into_iter().map(|f| f.name).collect()
+    }
+
+    #[test]
+    fn multistatus_is_parsed() {
+        let fs = WebDavFileSystem::new("https://dav.example.com/remote.php/dav/", None, None).unwrap();
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+            <d:multistatus xmlns:d="DAV:" xmlns:x="urn:other">
+              <d:response>
+                <d:href>/remote.php/dav/zotero/</d:href>
+                <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+              </d:response>
+              <d:response>
+                <d:href>https://dav.example.com/remote.php/dav/zotero/My%20Paper.zip</d:href>
+                <d:propstat><d:prop>
+                  <d:resourcetype/>
+                  <d:getcontentlength> 1234 </d:getcontentlength>
+                  <d:getlastmodified>Tue, 02 Jan 2024 03:04:05 GMT</d:getlastmodified>
+                  <x:collection/>
+                </d:prop></d:propstat>
+              </d:response>
+              <d:response><d:propstat/></d:response>
+            </d:multistatus>"#;
+
+        let resources = fs.parse_multistatus(body).unwrap();
+        assert_eq!(resources.len(), 2);
+
+        assert_eq!(resources[0].path, ["remote.php", "dav", "zotero"]);
+        assert!(resources[0].is_collection);
+
+        assert_eq!(resources[1].path, ["remote.php", "dav", "zotero", "My Paper.zip"]);
+        assert!(!resources[1].is_collection);
+        assert_eq!(resources[1].size, 1234);
+        assert_eq!(resources[1].modified.to_rfc3339(), "2024-01-02T03:04:05+00:00");
+
+        assert!(matches!(fs.parse_multistatus("<unclosed>"), Err(Error::InvalidData(_))));
+    }
+
+    #[tokio::test]
+    async fn file_list_walks_subcollections() {
+        let (fs, _) = server(&["zotero/A.zip", "zotero/dir/b.txt", "zotero/dir/sub/c d.txt", "other/x"]).await;
+
+        assert_eq!(names(fs.file_list("zotero", "").await.unwrap()), ["A.zip", "dir/b.txt", "dir/sub/c d.txt"]);
+        assert_eq!(names(fs.file_list("zotero", "dir/s").await.unwrap()), ["dir/sub/c d.txt"]);
+        assert_eq!(names(fs.file_list("zotero", "A").await.unwrap()), ["A.zip"]);
+        assert!(fs.file_list("missing", "").await.unwrap().is_empty());
+
+        let files = fs.file_list("zotero/dir", "").await.unwrap();
+        assert_eq!(names(files.clone()), ["b.txt", "sub/c d.txt"]);
+        assert_eq!(files[0].size, "zotero/dir/b.txt".len() as u64);
+    }
+
+    #[tokio::test]
+    async fn file_put_creates_missing_collections() {
+        let (fs, tree) = server(&["zotero/A.zip"]).await;
+
+        fs.file_put("zotero", "new/deep/f.txt", b"data", FilePutOptions::default()).await.unwrap();
+
+        let tree = tree.lock().unwrap();
+        assert_eq!(tree.files.get("/dav/zotero/new/deep/f.txt").map(Vec::as_slice), Some(b"data".as_slice()));
+        assert!(tree.collections.contains("/dav/zotero/new/"));
+        assert!(tree.collections.contains("/dav/zotero/new/deep/"));
+    }
+}
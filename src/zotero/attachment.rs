@@ -0,0 +1,267 @@
+//! Transfer state of attachment files, kept in the `attachments` table.
+
+use sqlx::{PgPool, Row};
+
+use crate::Result;
+use super::{Item, LibraryType, AttachmentLayout};
+
+/// Metadata key of the item key a stored file belongs to
+pub const KEY_METADATA: &str = "zotero-key";
+/// Metadata key of the item's library, as `{library_type}/{library_id}`
+pub const LIBRARY_METADATA: &str = "zotero-library";
+/// Metadata key of the modification time Zotero reports, in milliseconds
+pub const MTIME_METADATA: &str = "zotero-mtime";
+
+/// Outcome of the last transfer of an attachment file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferStatus {
+    /// Stored copy matches the file in Zotero
+    Downloaded,
+    /// Stored file was sent to Zotero
+    Uploaded,
+    /// Zotero has no file for the item
+    Missing,
+    /// Download failed; retried on the next sync
+    DownloadFailed,
+    /// Upload failed; retried while the stored file differs from Zotero's
+    UploadFailed,
+}
+
+impl TransferStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferStatus::Downloaded => "downloaded",
+            TransferStatus::Uploaded => "uploaded",
+            TransferStatus::Missing => "missing",
+            TransferStatus::DownloadFailed => "download_failed",
+            TransferStatus::UploadFailed => "upload_failed",
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(self, TransferStatus::DownloadFailed | TransferStatus::UploadFailed)
+    }
+}
+
+impl std::fmt::Display for TransferStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Row of the `attachments` table
+#[derive(Debug, Clone)]
+pub struct AttachmentState {
+    pub item_key: String,
+    pub library_id: i64,
+    pub library_type: LibraryType,
+    /// Storage folder (bucket) of the file
+    pub folder: String,
+    /// Name of the file within `folder`
+    pub name: String,
+    pub size: Option<i64>,
+    /// MD5 of the stored file
+    pub md5: Option<String>,
+    /// Modification time Zotero reports, in milliseconds
+    pub mtime: Option<i64>,
+    pub content_type: Option<String>,
+    pub status: TransferStatus,
+    pub error: Option<String>,
+}
+
+impl AttachmentState {
+    /// State of an item's file without size and hash
+    pub fn new(item: &Item, layout: &AttachmentLayout, status: TransferStatus) -> Self {
+        let (folder, name) = item.file_location(layout);
+        let field = |name: &str| item.data.extra_fields.get(name);
+
+        Self {
+            item_key: item.key.clone(),
+            library_id: item.library_id,
+            library_type: item.library_type,
+            folder,
+            name,
+            size: None,
+            md5: None,
+            mtime: field("mtime").and_then(|v| v.as_i64()),
+            content_type: field("contentType").and_then(|v| v.as_str()).map(str::to_string),
+            status,
+            error: None,
+        }
+    }
+
+    pub fn with_file(mut self, md5: String, size: u64) -> Self {
+        self.md5 = Some(md5);
+        self.size = Some(size as i64);
+        self
+    }
+
+    pub fn with_error(mut self, error: &crate::Error) -> Self {
+        self.error = Some(error.to_string());
+        self
+    }
+
+    /// Insert or replace the row.
+    ///
+    /// Size and hash of a previous transfer are kept when a failed one
+    /// carries none; `attempts` counts consecutive failures.
+    pub async fn save(&self, db: &PgPool, schema: &str) -> Result<()> {
+        let query = format!(
+            r#"
+            INSERT INTO {schema}.attachments
+                (item_key, library_id, library_type, storage_folder, storage_name, size, md5, mtime,
+                 content_type, status, last_error, attempts, transferred_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, CASE WHEN $12 THEN 1 ELSE 0 END, NOW())
+            ON CONFLICT (item_key, library_id, library_type) DO UPDATE SET
+                storage_folder = EXCLUDED.storage_folder,
+                storage_name = EXCLUDED.storage_name,
+                size = COALESCE(EXCLUDED.size, {schema}.attachments.size),
+                md5 = COALESCE(EXCLUDED.md5, {schema}.attachments.md5),
+                mtime = COALESCE(EXCLUDED.mtime, {schema}.attachments.mtime),
+                content_type = COALESCE(EXCLUDED.content_type, {schema}.attachments.content_type),
+                status = EXCLUDED.status,
+                last_error = EXCLUDED.last_error,
+                attempts = CASE WHEN $12 THEN {schema}.attachments.attempts + 1 ELSE 0 END,
+                transferred_at = NOW()
+            "#,
+            schema = schema
+        );
+
+        sqlx::query(&query)
+            .bind(&self.item_key)
+            .bind(self.library_id)
+            .bind(self.library_type)
+            .bind(&self.folder)
+            .bind(&self.name)
+            .bind(self.size)
+            .bind(&self.md5)
+            .bind(self.mtime)
+            .bind(&self.content_type)
+            .bind(self.status.as_str())
+            .bind(&self.error)
+            .bind(self.status.is_failure())
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Keys of a library's attachments whose last download failed
+    pub async fn failed_downloads(db: &PgPool, schema: &str, library_id: i64, library_type: LibraryType) -> Result<Vec<String>> {
+        let query = format!(
+            r#"
+            SELECT item_key FROM {}.attachments
+            WHERE library_id = $1 AND library_type = $2 AND status = $3
+            ORDER BY transferred_at
+            "#,
+            schema
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(library_id)
+            .bind(library_type)
+            .bind(TransferStatus::DownloadFailed.as_str())
+            .fetch_all(db)
+            .await?;
+
+        Ok(rows.iter().map(|row| row.get("item_key")).collect())
+    }
+}
+
+/// Bytes read from the start of a download to guess its content type
+pub const SNIFF_LEN: usize = 512;
+
+/// Content type of a file from its first bytes, or else from its name's
+/// extension; `None` if neither is recognised
+pub fn guess_content_type(name: &str, head: &[u8]) -> Option<&'static str> {
+    sniff(head).or_else(|| by_extension(name))
+}
+
+fn sniff(head: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"%PDF-", "application/pdf"),
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xFF\xD8\xFF", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%!PS", "application/postscript"),
+        (b"{\\rtf", "application/rtf"),
+        (b"AT&TFORM", "image/vnd.djvu"),
+    ];
+    if let Some((_, content_type)) = SIGNATURES.iter().find(|(signature, _)| head.starts_with(signature)) {
+        return Some(content_type);
+    }
+    if head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    if head.starts_with(b"PK\x03\x04") {
+        // EPUB requires an uncompressed `mimetype` entry first
+        let epub = head.get(30..58) == Some(b"mimetypeapplication/epub+zip".as_slice());
+        return Some(if epub { "application/epub+zip" } else { "application/zip" });
+    }
+
+    let start = head.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(head.len());
+    let text = head[start..].to_ascii_lowercase();
+    let html = [b"<!doctype html".as_slice(), b"<html", b"<head", b"<body"];
+    if html.iter().any(|tag| text.starts_with(tag)) {
+        return Some("text/html");
+    }
+    None
+}
+
+fn by_extension(name: &str) -> Option<&'static str> {
+    let (_, extension) = name.rsplit_once('.')?;
+    Some(match extension.to_ascii_lowercase().as_str() {
+        "pdf" => "application/pdf",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "epub" => "application/epub+zip",
+        "djvu" => "image/vnd.djvu",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_type_from_signature() {
+        assert_eq!(guess_content_type("paper", b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(guess_content_type("x.bin", b"\x89PNG\r\n\x1a\n...."), Some("image/png"));
+        assert_eq!(guess_content_type("x", b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(guess_content_type("x", b"  \n<!DOCTYPE html><html>"), Some("text/html"));
+        assert_eq!(guess_content_type("x", b"PK\x03\x04"), Some("application/zip"));
+    }
+
+    #[test]
+    fn epub_is_told_from_zip() {
+        let mut head = b"PK\x03\x04".to_vec();
+        head.resize(30, 0);
+        head.extend_from_slice(b"mimetypeapplication/epub+zip");
+        assert_eq!(guess_content_type("book", &head), Some("application/epub+zip"));
+    }
+
+    #[test]
+    fn signature_wins_over_extension() {
+        assert_eq!(guess_content_type("scan.pdf", b"\xFF\xD8\xFF\xE0"), Some("image/jpeg"));
+    }
+
+    #[test]
+    fn content_type_from_extension() {
+        assert_eq!(guess_content_type("notes.TXT", b"plain words"), Some("text/plain"));
+        assert_eq!(guess_content_type("style.css", b""), Some("text/css"));
+        assert_eq!(guess_content_type("data.xyz", b"plain words"), None);
+        assert_eq!(guess_content_type("no-extension", b""), None);
+    }
+}
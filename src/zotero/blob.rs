@@ -0,0 +1,293 @@
+//! Content-addressed attachment storage.
+//!
+//! With `[storage] content_addressed = true` every distinct file is stored
+//! once, under its MD5, no matter how many attachment items share it. The
+//! `blobs` table counts the references held in `attachment_blobs`; blobs
+//! nothing refers to any longer are deleted by [`release_unreferenced`].
+//!
+//! Linking and releasing both lock the blob's row, so a blob is never
+//! deleted from storage while an item is being pointed at it.
+
+use sqlx::{PgPool, Row};
+use tracing::{info, warn};
+
+use crate::{Error, Result};
+use crate::filesystem::FileSystem;
+use super::{AttachmentLayout, LibraryType};
+
+/// Whether `md5` is a hex MD5 digest usable as a blob address
+pub fn is_blob_address(md5: &str) -> bool {
+    md5.len() == 32 && md5.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Point an attachment item at the blob holding its file.
+///
+/// Creates the blob row if needed; the reference count is maintained by a
+/// trigger on `attachment_blobs`. Fails with `NotFound` if the blob's file
+/// was released after it was stored, so that it is stored again.
+#[allow(clippy::too_many_arguments)]
+pub async fn link(
+    db: &PgPool,
+    schema: &str,
+    filesystem: &dyn FileSystem,
+    layout: &AttachmentLayout,
+    item_key: &str,
+    library_id: i64,
+    library_type: LibraryType,
+    md5: &str,
+    size: u64,
+) -> Result<()> {
+    let mut tx = db.begin().await?;
+
+    // Updating an existing row locks it until the reference is in place, and
+    // waits for a release in progress
+    let query = format!(
+        "INSERT INTO {}.blobs (md5, size) VALUES ($1, $2) ON CONFLICT (md5) DO UPDATE SET size = EXCLUDED.size",
+        schema
+    );
+    sqlx::query(&query)
+        .bind(md5)
+        .bind(size as i64)
+        .execute(&mut *tx)
+        .await?;
+
+    let (folder, name) = layout.blob_location(md5);
+    if !filesystem.file_exists(&folder, &name).await? {
+        return Err(Error::NotFound(format!("Blob {} was released before it could be linked", md5)));
+    }
+
+    let query = format!(
+        r#"
+        INSERT INTO {schema}.attachment_blobs (item_key, library_id, library_type, md5)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (item_key, library_id, library_type) DO UPDATE SET md5 = EXCLUDED.md5
+        WHERE {schema}.attachment_blobs.md5 IS DISTINCT FROM EXCLUDED.md5
+        "#,
+        schema = schema
+    );
+    sqlx::query(&query)
+        .bind(item_key)
+        .bind(library_id)
+        .bind(library_type)
+        .bind(md5)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// MD5 of every blob that is still referenced
+pub async fn referenced(db: &PgPool, schema: &str) -> Result<Vec<String>> {
+    let query = format!("SELECT md5 FROM {}.blobs WHERE refcount > 0", schema);
+    let rows = sqlx::query(&query).fetch_all(db).await?;
+    Ok(rows.iter().map(|row| row.get("md5")).collect())
+}
+
+/// Delete the blobs no attachment item refers to any longer, from storage
+/// first and then from the table; returns the number deleted.
+///
+/// Each blob's row stays locked until it is gone, and blobs linked again
+/// in the meantime are kept. A blob whose file cannot be deleted is tried
+/// again on the next run.
+pub async fn release_unreferenced(db: &PgPool, schema: &str, filesystem: &dyn FileSystem, layout: &AttachmentLayout) -> Result<u64> {
+    let query = format!("SELECT md5 FROM {}.blobs WHERE refcount <= 0", schema);
+    let candidates: Vec<String> = sqlx::query(&query)
+        .fetch_all(db)
+        .await?
+        .iter()
+        .map(|row| row.get("md5"))
+        .collect();
+
+    let lock_query = format!("SELECT refcount FROM {}.blobs WHERE md5 = $1 FOR UPDATE", schema);
+    let delete_query = format!("DELETE FROM {}.blobs WHERE md5 = $1", schema);
+
+    let mut released = 0;
+    for md5 in candidates {
+        let mut tx = db.begin().await?;
+
+        let refcount: Option<i32> = sqlx::query(&lock_query)
+            .bind(&md5)
+            .fetch_optional(&mut *tx)
+            .await?
+            .map(|row| row.get("refcount"));
+        if refcount.is_none_or(|refcount| refcount > 0) {
+            continue;
+        }
+
+        let (folder, name) = layout.blob_location(&md5);
+        match filesystem.file_delete(&folder, &name).await {
+            Ok(()) | Err(Error::NotFound(_)) => {}
+            Err(e) => {
+                warn!("Cannot delete unreferenced blob {}: {}", md5, e);
+                continue;
+            }
+        }
+
+        sqlx::query(&delete_query).bind(&md5).execute(&mut *tx).await?;
+        tx.commit().await?;
+        info!("Released unreferenced blob {}", md5);
+        released += 1;
+    }
+
+    Ok(released)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use sqlx::Executor;
+    use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+    use crate::filesystem::InMemoryFileSystem;
+    use crate::filesystem::memory::{FailureKind, FileOp};
+
+    const MD5_A: &str = "0cc175b9c0f1b6a831c399e269772661";
+    const MD5_B: &str = "92eb5ffee6ae2fec3ad71c777531578f";
+
+    /// Pool on a fresh schema, in the database `POSTERO_TEST_DSN` names,
+    /// with the blob tables of migration 011
+    async fn test_db() -> (PgPool, String) {
+        let dsn = std::env::var("POSTERO_TEST_DSN").expect("POSTERO_TEST_DSN names the database to test against");
+        let schema = format!("postero_test_{}", uuid::Uuid::new_v4().simple());
+
+        let admin = PgPool::connect(&dsn).await.unwrap();
+        admin.execute(format!("CREATE SCHEMA {}", schema).as_str()).await.unwrap();
+
+        let options = PgConnectOptions::from_str(&dsn).unwrap().options([("search_path", schema.as_str())]);
+        let db = PgPoolOptions::new().connect_with(options).await.unwrap();
+
+        let migration = include_str!("../../migrations/011_content_addressed_storage.sql");
+        let tables = migration.split("-- Step 3").next().unwrap().replace("public.", &format!("{}.", schema));
+        db.execute(format!(
+            r#"
+            CREATE TYPE library_type AS ENUM ('user', 'group');
+            CREATE TABLE items (
+                key varchar(8) NOT NULL,
+                library_id bigint NOT NULL,
+                library_type library_type NOT NULL,
+                PRIMARY KEY (key, library_id, library_type)
+            );
+            INSERT INTO items VALUES ('ITEM0001', 1, 'user'), ('ITEM0002', 1, 'user');
+            {}
+            "#,
+            tables
+        ).as_str()).await.unwrap();
+
+        (db, schema)
+    }
+
+    async fn drop_schema(db: PgPool, schema: &str) {
+        db.execute(format!("DROP SCHEMA {} CASCADE", schema).as_str()).await.unwrap();
+    }
+
+    async fn refcount(db: &PgPool, schema: &str, md5: &str) -> Option<i32> {
+        sqlx::query(&format!("SELECT refcount FROM {}.blobs WHERE md5 = $1", schema))
+            .bind(md5)
+            .fetch_optional(db)
+            .await
+            .unwrap()
+            .map(|row| row.get("refcount"))
+    }
+
+    fn storage(layout: &AttachmentLayout, md5s: &[&str]) -> InMemoryFileSystem {
+        let fs = InMemoryFileSystem::new();
+        for md5 in md5s {
+            let (folder, name) = layout.blob_location(md5);
+            fs.insert(&folder, &name, md5.as_bytes());
+        }
+        fs
+    }
+
+    #[test]
+    fn blob_addresses() {
+        assert!(is_blob_address(MD5_A));
+        assert!(!is_blob_address("0cc175b9"));
+        assert!(!is_blob_address("../../../../../../../../etc/passwd"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the database in POSTERO_TEST_DSN"]
+    async fn references_are_counted() {
+        let (db, schema) = test_db().await;
+        let layout = AttachmentLayout::default().with_content_addressed(true);
+        let fs = storage(&layout, &[MD5_A, MD5_B]);
+
+        link(&db, &schema, &fs, &layout, "ITEM0001", 1, LibraryType::User, MD5_A, 1).await.unwrap();
+        link(&db, &schema, &fs, &layout, "ITEM0002", 1, LibraryType::User, MD5_A, 1).await.unwrap();
+        assert_eq!(refcount(&db, &schema, MD5_A).await, Some(2));
+
+        // Linking again to the same blob changes nothing
+        link(&db, &schema, &fs, &layout, "ITEM0002", 1, LibraryType::User, MD5_A, 1).await.unwrap();
+        assert_eq!(refcount(&db, &schema, MD5_A).await, Some(2));
+
+        // A new file moves the reference
+        link(&db, &schema, &fs, &layout, "ITEM0002", 1, LibraryType::User, MD5_B, 1).await.unwrap();
+        assert_eq!(refcount(&db, &schema, MD5_A).await, Some(1));
+        assert_eq!(refcount(&db, &schema, MD5_B).await, Some(1));
+
+        // Deleting the item drops its reference
+        sqlx::query(&format!("DELETE FROM {}.items WHERE key = 'ITEM0002'", schema)).execute(&db).await.unwrap();
+        assert_eq!(refcount(&db, &schema, MD5_B).await, Some(0));
+        assert_eq!(referenced(&db, &schema).await.unwrap(), [MD5_A]);
+
+        drop_schema(db, &schema).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the database in POSTERO_TEST_DSN"]
+    async fn only_unreferenced_blobs_are_released() {
+        let (db, schema) = test_db().await;
+        let layout = AttachmentLayout::default().with_content_addressed(true);
+        let fs = storage(&layout, &[MD5_A, MD5_B]);
+
+        link(&db, &schema, &fs, &layout, "ITEM0001", 1, LibraryType::User, MD5_A, 1).await.unwrap();
+        link(&db, &schema, &fs, &layout, "ITEM0002", 1, LibraryType::User, MD5_B, 1).await.unwrap();
+        link(&db, &schema, &fs, &layout, "ITEM0002", 1, LibraryType::User, MD5_A, 1).await.unwrap();
+
+        assert_eq!(release_unreferenced(&db, &schema, &fs, &layout).await.unwrap(), 1);
+        assert_eq!(refcount(&db, &schema, MD5_B).await, None);
+        let (folder, name) = layout.blob_location(MD5_B);
+        assert_eq!(fs.contents(&folder, &name), None);
+        let (folder, name) = layout.blob_location(MD5_A);
+        assert!(fs.contents(&folder, &name).is_some());
+
+        assert_eq!(release_unreferenced(&db, &schema, &fs, &layout).await.unwrap(), 0);
+
+        drop_schema(db, &schema).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the database in POSTERO_TEST_DSN"]
+    async fn blob_is_kept_if_its_file_cannot_be_deleted() {
+        let (db, schema) = test_db().await;
+        let layout = AttachmentLayout::default().with_content_addressed(true);
+        let fs = storage(&layout, &[MD5_A, MD5_B]);
+
+        link(&db, &schema, &fs, &layout, "ITEM0001", 1, LibraryType::User, MD5_A, 1).await.unwrap();
+        link(&db, &schema, &fs, &layout, "ITEM0001", 1, LibraryType::User, MD5_B, 1).await.unwrap();
+
+        fs.fail_next(FileOp::FileDelete, FailureKind::Transient);
+        assert_eq!(release_unreferenced(&db, &schema, &fs, &layout).await.unwrap(), 0);
+        assert_eq!(refcount(&db, &schema, MD5_A).await, Some(0));
+
+        assert_eq!(release_unreferenced(&db, &schema, &fs, &layout).await.unwrap(), 1);
+        assert_eq!(refcount(&db, &schema, MD5_A).await, None);
+
+        drop_schema(db, &schema).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the database in POSTERO_TEST_DSN"]
+    async fn linking_a_released_blob_fails() {
+        let (db, schema) = test_db().await;
+        let layout = AttachmentLayout::default().with_content_addressed(true);
+        let fs = storage(&layout, &[]);
+
+        let result = link(&db, &schema, &fs, &layout, "ITEM0001", 1, LibraryType::User, MD5_A, 1).await;
+        assert!(matches!(result, Err(Error::NotFound(_))));
+        assert_eq!(refcount(&db, &schema, MD5_A).await, None);
+
+        drop_schema(db, &schema).await;
+    }
+}
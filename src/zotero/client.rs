@@ -1,13 +1,84 @@
 use reqwest::{Client, header::{HeaderMap, HeaderValue}};
+use serde::de::DeserializeOwned;
 use sqlx::PgPool;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
 use url::Url;
 use crate::{Error, Result};
-use crate::filesystem::FileSystem;
-use super::{Library, ApiKey, UploadAuthorization, UploadAuthorizationResponse, LibraryType};
+use crate::filesystem::{FileSystem, FileReader};
+use futures_util::TryStreamExt;
+use tokio_util::io::{ReaderStream, StreamReader};
+use super::{Library, ApiKey, UploadAuthorization, UploadAuthorizationResponse, LibraryType, AttachmentLayout};
 use serde_json;
 
+/// Maximum number of objects Zotero returns per page
+const PAGE_LIMIT: usize = 100;
+
+/// Maximum number of objects Zotero accepts in one write request
+pub const WRITE_BATCH_LIMIT: usize = 50;
+
+/// Retry budget for requests sent through the client
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on every further attempt
+    pub base_delay: Duration,
+    /// Upper bound for the exponential delay; server-provided waits are not capped
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Jittered exponential delay before retry number `attempt` (1-based)
+    fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        half + half.mul_f64(rand::random::<f64>())
+    }
+}
+
+/// Parse a header holding a number of seconds (`Retry-After`, `Backoff`)
+fn header_secs(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// Whether re-sending the request cannot apply a write twice
+fn is_idempotent(request: &reqwest::Request) -> bool {
+    let headers = request.headers();
+    request.method() != reqwest::Method::POST
+        || headers.contains_key("If-Unmodified-Since-Version")
+        || headers.contains_key("If-Match")
+        || headers.contains_key("If-None-Match")
+}
+
+/// Extract the `rel="next"` target from a `Link` response header
+fn next_page_link(headers: &HeaderMap) -> Option<Url> {
+    let link = headers.get("Link")?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (target, rel) = part.split_once(';')?;
+        if !rel.split(';').any(|param| param.trim() == "rel=\"next\"") {
+            return None;
+        }
+        let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+        Url::parse(target).ok()
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct ZoteroClient {
     client: Client,
@@ -15,8 +86,12 @@ pub struct ZoteroClient {
     db: PgPool,
     db_schema: String,
     fs: Arc<dyn FileSystem>,
+    attachment_layout: AttachmentLayout,
+    webdav: Option<super::webdav::ZoteroWebDav>,
     new_group_active: bool,
     current_key: Option<ApiKey>,
+    retry_policy: RetryPolicy,
+    backoff_until: Arc<Mutex<Option<Instant>>>,
 }
 
 impl ZoteroClient {
@@ -51,8 +126,12 @@ impl ZoteroClient {
             db,
             db_schema: db_schema.to_string(),
             fs,
+            attachment_layout: AttachmentLayout::default(),
+            webdav: None,
             new_group_active,
             current_key: None,
+            retry_policy: RetryPolicy::default(),
+            backoff_until: Arc::new(Mutex::new(None)),
         };
 
         zotero.init().await?;
@@ -73,9 +152,36 @@ impl ZoteroClient {
         &self.fs
     }
 
+    pub fn db(&self) -> &PgPool {
+        &self.db
+    }
+
+    pub fn db_schema(&self) -> &str {
+        &self.db_schema
+    }
+
+    /// Where attachment files are kept in [`filesystem`](Self::filesystem)
+    pub fn attachment_layout(&self) -> &AttachmentLayout {
+        &self.attachment_layout
+    }
+
+    pub fn set_attachment_layout(&mut self, attachment_layout: AttachmentLayout) {
+        self.attachment_layout = attachment_layout;
+    }
+
+    /// WebDAV server holding the files of a library instead of Zotero
+    /// Storage; Zotero only supports this for the personal library
+    pub fn webdav(&self, library_type: LibraryType) -> Option<&super::webdav::ZoteroWebDav> {
+        self.webdav.as_ref().filter(|_| library_type == LibraryType::User)
+    }
+
+    pub fn set_webdav(&mut self, webdav: Option<super::webdav::ZoteroWebDav>) {
+        self.webdav = webdav;
+    }
+
     pub async fn get_api_key_info(&self) -> Result<ApiKey> {
         let url = self.base_url.join("keys/current")?;
-        let response = self.client.get(url).send().await?;
+        let response = self.execute(self.client.get(url)).await?;
         
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -91,11 +197,10 @@ impl ZoteroClient {
     pub async fn get_user_group_versions(&self, user_id: i64) -> Result<HashMap<i64, i64>> {
         let url = self.base_url.join(&format!("users/{}/groups", user_id))?;
         
-        let response = self.client
+        let request = self.client
             .get(url)
-            .query(&[("format", "versions")])
-            .send()
-            .await?;
+            .query(&[("format", "versions")]);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -119,7 +224,7 @@ impl ZoteroClient {
     pub async fn get_group_cloud(&self, group_id: i64) -> Result<super::GroupData> {
         let url = self.base_url.join(&format!("groups/{}", group_id))?;
         
-        let response = self.client.get(url).send().await?;
+        let response = self.execute(self.client.get(url)).await?;
         
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -136,8 +241,8 @@ impl ZoteroClient {
         let query = format!(
             r#"
             SELECT l.id, l.library_type, l.version, l.created, l.modified, l.data, l.deleted,
-                   l.item_version, l.collection_version, l.tag_version, l.gitlab,
-                   sl.active, sl.incoming_sync, sl.outgoing_sync, sl.tags
+                   l.item_version, l.collection_version, l.search_version, l.tag_version, l.fulltext_version, l.gitlab,
+                   sl.active, sl.incoming_sync, sl.outgoing_sync, sl.tags, sl.conflict_policy
             FROM {}.libraries l, {}.sync_libraries sl
             WHERE l.id = sl.library_id AND l.library_type = sl.library_type
                   AND l.id = $1 AND l.library_type = 'group'
@@ -158,8 +263,8 @@ impl ZoteroClient {
         let query = format!(
             r#"
             SELECT l.id, l.library_type, l.version, l.created, l.modified, l.data, l.deleted,
-                   l.item_version, l.collection_version, l.tag_version, l.gitlab,
-                   sl.active, sl.incoming_sync, sl.outgoing_sync, sl.tags
+                   l.item_version, l.collection_version, l.search_version, l.tag_version, l.fulltext_version, l.gitlab,
+                   sl.active, sl.incoming_sync, sl.outgoing_sync, sl.tags, sl.conflict_policy
             FROM {}.libraries l, {}.sync_libraries sl
             WHERE l.id = sl.library_id AND l.library_type = sl.library_type
                   AND l.library_type = 'group'
@@ -243,55 +348,94 @@ impl ZoteroClient {
         Ok(())
     }
 
-    pub fn check_retry(&self, headers: &HeaderMap) -> bool {
-        headers.get("retry-after").is_some()
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
     }
 
-    pub async fn handle_retry(&self, headers: &HeaderMap) -> Result<()> {
-        if let Some(retry_after) = headers.get("retry-after") {
-            if let Ok(retry_str) = retry_after.to_str() {
-                if let Ok(retry_secs) = retry_str.parse::<u64>() {
-                    tracing::warn!("Rate limited, waiting {} seconds before retry", retry_secs);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(retry_secs)).await;
-                    return Ok(());
-                }
-            }
-        }
-        Ok(())
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
     }
 
-    pub fn check_backoff(&self, headers: &HeaderMap) -> bool {
-        headers.get("backoff").is_some()
-    }
+    /// Send a request to the Zotero API, honouring `Backoff` and retrying
+    /// on 429/503 until the retry budget is exhausted.
+    ///
+    /// Throttled responses are always safe to repeat because the server did
+    /// not act on them. Transport failures are only retried for idempotent
+    /// requests, i.e. non-POST methods or writes guarded by
+    /// `If-Unmodified-Since-Version`. Requests whose body cannot be cloned
+    /// (streams) are sent exactly once.
+    async fn execute(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let mut request = request.build()?;
+        let idempotent = is_idempotent(&request);
+        let mut attempt: u32 = 1;
+
+        loop {
+            self.wait_for_backoff().await;
+
+            let retry = request.try_clone().filter(|_| attempt < self.retry_policy.max_attempts);
+            let method = request.method().clone();
+            let url = request.url().clone();
+
+            match self.client.execute(request).await {
+                Ok(response) => {
+                    let headers = response.headers();
+                    if let Some(backoff) = header_secs(headers, "Backoff") {
+                        self.record_backoff(backoff);
+                    }
+
+                    let status = response.status().as_u16();
+                    if status != 429 && status != 503 {
+                        return Ok(response);
+                    }
+
+                    let retry_after = header_secs(headers, "Retry-After");
+                    let Some(next) = retry else {
+                        tracing::error!("{} {} still throttled ({}) after {} attempts", method, url, status, attempt);
+                        return Err(Error::RateLimit { retry_after });
+                    };
+
+                    let delay = retry_after
+                        .map(Duration::from_secs)
+                        .unwrap_or_else(|| self.retry_policy.delay(attempt));
+                    tracing::warn!("{} {} returned {}, retrying in {:?} (attempt {}/{})",
+                        method, url, status, delay, attempt, self.retry_policy.max_attempts);
+                    tokio::time::sleep(delay).await;
+                    request = next;
+                }
+                Err(e) => {
+                    let transient = e.is_timeout() || e.is_connect();
+                    let Some(next) = retry.filter(|_| idempotent && transient) else {
+                        return Err(e.into());
+                    };
 
-    pub async fn handle_backoff(&self, headers: &HeaderMap) -> Result<()> {
-        if let Some(backoff) = headers.get("backoff") {
-            if let Ok(backoff_str) = backoff.to_str() {
-                if let Ok(backoff_secs) = backoff_str.parse::<u64>() {
-                    tracing::warn!("Backoff requested, waiting {} seconds", backoff_secs);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(backoff_secs)).await;
-                    return Ok(());
+                    let delay = self.retry_policy.delay(attempt);
+                    tracing::warn!("{} {} failed: {}, retrying in {:?} (attempt {}/{})",
+                        method, url, e, delay, attempt, self.retry_policy.max_attempts);
+                    tokio::time::sleep(delay).await;
+                    request = next;
                 }
             }
+
+            attempt += 1;
         }
-        Ok(())
     }
 
-    // Helper method to handle rate limiting for any API call
-    pub async fn handle_rate_limiting(&self, response: &reqwest::Response) -> Result<()> {
-        let headers = response.headers();
-        
-        // Handle backoff first (more urgent)
-        if self.check_backoff(headers) {
-            self.handle_backoff(headers).await?;
+    /// Remember a server-requested `Backoff` so that every clone of this
+    /// client pauses before its next request
+    fn record_backoff(&self, secs: u64) {
+        let until = Instant::now() + Duration::from_secs(secs);
+        let mut backoff_until = self.backoff_until.lock().unwrap_or_else(|e| e.into_inner());
+        if backoff_until.is_none_or(|current| current < until) {
+            tracing::warn!("Backoff requested, pausing requests for {} seconds", secs);
+            *backoff_until = Some(until);
         }
-        
-        // Handle retry-after
-        if self.check_retry(headers) {
-            self.handle_retry(headers).await?;
+    }
+
+    async fn wait_for_backoff(&self) {
+        let until = *self.backoff_until.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(until) = until {
+            tokio::time::sleep_until(until).await;
         }
-        
-        Ok(())
     }
 
     pub async fn delete_collection_db(&self, key: &str) -> Result<()> {
@@ -304,11 +448,10 @@ impl ZoteroClient {
     pub async fn get_collections_version_cloud(&self, group_id: i64, since_version: i64) -> Result<(std::collections::HashMap<String, i64>, i64)> {
         let url = self.base_url.join(&format!("groups/{}/collections", group_id))?;
         
-        let response = self.client
+        let request = self.client
             .get(url)
-            .query(&[("format", "versions"), ("since", &since_version.to_string())])
-            .send()
-            .await?;
+            .query(&[("format", "versions"), ("since", &since_version.to_string())]);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -335,29 +478,11 @@ impl ZoteroClient {
 
         let url = self.base_url.join(&format!("groups/{}/collections", group_id))?;
         let keys = collection_keys.join(",");
-        
-        let response = self.client
-            .get(url)
-            .query(&[("collectionKey", &keys)])
-            .send()
-            .await?;
 
-        if !response.status().is_success() {
-            return Err(Error::Api {
-                code: response.status().as_u16(),
-                message: response.text().await.unwrap_or_default(),
-            });
-        }
-
-        let last_modified_version = response
-            .headers()
-            .get("Last-Modified-Version")
-            .and_then(|h| h.to_str().ok())
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(0);
-
-        let collections: Vec<super::Collection> = response.json().await?;
-        Ok((collections, last_modified_version))
+        let (collections, last_modified_version) = self
+            .get_paginated::<super::Collection>(url, &[("collectionKey", keys)])
+            .await?;
+        Ok((collections, last_modified_version.unwrap_or(0)))
     }
 
     // Item API methods
@@ -373,11 +498,10 @@ impl ZoteroClient {
             params.push(("trashed", "1".to_string()));
         }
         
-        let response = self.client
+        let request = self.client
             .get(url)
-            .query(&params)
-            .send()
-            .await?;
+            .query(&params);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -404,61 +528,31 @@ impl ZoteroClient {
 
         let url = self.base_url.join(&format!("groups/{}/items", group_id))?;
         let keys = item_keys.join(",");
-        
-        let response = self.client
-            .get(url)
-            .query(&[("itemKey", &keys)])
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(Error::Api {
-                code: response.status().as_u16(),
-                message: response.text().await.unwrap_or_default(),
-            });
-        }
 
-        let items: Vec<super::Item> = response.json().await?;
+        let (items, _) = self
+            .get_paginated::<super::Item>(url, &[("itemKey", keys)])
+            .await?;
         Ok(items)
     }
 
     // Tag API methods
     pub async fn get_tags_cloud(&self, group_id: i64, since_version: i64) -> Result<(Vec<super::Tag>, i64)> {
         let url = self.base_url.join(&format!("groups/{}/tags", group_id))?;
-        
-        let response = self.client
-            .get(url)
-            .query(&[("since", &since_version.to_string())])
-            .send()
-            .await?;
 
-        if !response.status().is_success() {
-            return Err(Error::Api {
-                code: response.status().as_u16(),
-                message: response.text().await.unwrap_or_default(),
-            });
-        }
-
-        let last_modified_version = response
-            .headers()
-            .get("Last-Modified-Version")
-            .and_then(|h| h.to_str().ok())
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(since_version);
-
-        let tags: Vec<super::Tag> = response.json().await?;
-        Ok((tags, last_modified_version))
+        let (tags, last_modified_version) = self
+            .get_paginated::<super::Tag>(url, &[("since", since_version.to_string())])
+            .await?;
+        Ok((tags, last_modified_version.unwrap_or(since_version)))
     }
 
     // Deletion API methods
     pub async fn get_deletions_cloud(&self, group_id: i64, since_version: i64) -> Result<(super::Deletions, i64)> {
         let url = self.base_url.join(&format!("groups/{}/deleted", group_id))?;
         
-        let response = self.client
+        let request = self.client
             .get(url)
-            .query(&[("since", &since_version.to_string())])
-            .send()
-            .await?;
+            .query(&[("since", &since_version.to_string())]);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -505,10 +599,7 @@ impl ZoteroClient {
             .header("If-Unmodified-Since-Version", library_version.to_string())
             .json(&api_data);
 
-        let response = request.send().await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             200 | 201 => {
@@ -532,13 +623,6 @@ impl ZoteroClient {
                     message: "Collection has been modified remotely. Sync required.".to_string(),
                 })
             }
-            429 | 503 => {
-                // Rate limited or service unavailable - should have been handled by handle_rate_limiting
-                Err(Error::Api {
-                    code: response.status().as_u16(),
-                    message: "Rate limited or service unavailable".to_string(),
-                })
-            }
             _ => {
                 Err(Error::Api {
                     code: response.status().as_u16(),
@@ -602,10 +686,7 @@ impl ZoteroClient {
             .header("If-Unmodified-Since-Version", library_version.to_string())
             .json(&api_data);
 
-        let response = request.send().await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             200 | 201 => {
@@ -636,13 +717,6 @@ impl ZoteroClient {
                     message: "Item too large. File upload required for attachments.".to_string(),
                 })
             }
-            429 | 503 => {
-                // Rate limited or service unavailable
-                Err(Error::Api {
-                    code: response.status().as_u16(),
-                    message: "Rate limited or service unavailable".to_string(),
-                })
-            }
             _ => {
                 Err(Error::Api {
                     code: response.status().as_u16(),
@@ -655,14 +729,10 @@ impl ZoteroClient {
     pub async fn delete_collection(&self, group_id: i64, collection_key: &str, library_version: i64) -> Result<i64> {
         let url = self.base_url.join(&format!("groups/{}/collections/{}", group_id, collection_key))?;
 
-        let response = self.client
+        let request = self.client
             .delete(url)
-            .header("If-Unmodified-Since-Version", library_version.to_string())
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+            .header("If-Unmodified-Since-Version", library_version.to_string());
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             204 => {
@@ -694,14 +764,10 @@ impl ZoteroClient {
     pub async fn delete_item(&self, group_id: i64, item_key: &str, library_version: i64) -> Result<i64> {
         let url = self.base_url.join(&format!("groups/{}/items/{}", group_id, item_key))?;
 
-        let response = self.client
+        let request = self.client
             .delete(url)
-            .header("If-Unmodified-Since-Version", library_version.to_string())
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+            .header("If-Unmodified-Since-Version", library_version.to_string());
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             204 => {
@@ -734,13 +800,9 @@ impl ZoteroClient {
     pub async fn get_attachment_download_url(&self, group_id: i64, item_key: &str) -> Result<String> {
         let url = self.base_url.join(&format!("groups/{}/items/{}/file", group_id, item_key))?;
         
-        let response = self.client
-            .get(url)
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+        let request = self.client
+            .get(url);
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             302 => {
@@ -776,13 +838,9 @@ impl ZoteroClient {
     }
 
     pub async fn download_file(&self, download_url: &str) -> Result<Vec<u8>> {
-        let response = self.client
-            .get(download_url)
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+        let request = self.client
+            .get(download_url);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -795,6 +853,26 @@ impl ZoteroClient {
         Ok(file_data.to_vec())
     }
 
+    /// Download a file without buffering it.
+    ///
+    /// Returns the body as a reader and its length, if the server sent one.
+    pub async fn download_file_stream(&self, download_url: &str) -> Result<(FileReader, Option<u64>)> {
+        let request = self.client
+            .get(download_url);
+        let response = self.execute(request).await?;
+
+        if !response.status().is_success() {
+            return Err(Error::Api {
+                code: response.status().as_u16(),
+                message: "Failed to download file".to_string(),
+            });
+        }
+
+        let length = response.content_length();
+        let stream = response.bytes_stream().map_err(std::io::Error::other);
+        Ok((Box::new(StreamReader::new(Box::pin(stream))), length))
+    }
+
     pub async fn get_upload_authorization(
         &self,
         group_id: i64,
@@ -819,14 +897,10 @@ impl ZoteroClient {
             upload_data["mtime"] = serde_json::Value::Number(serde_json::Number::from(mtime_val));
         }
 
-        let response = self.client
+        let request = self.client
             .post(url)
-            .json(&upload_data)
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+            .json(&upload_data);
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             200 => {
@@ -888,11 +962,45 @@ impl ZoteroClient {
             .file_name("file");
         form = form.part("file", file_part);
 
-        let response = self.client
+        let request = self.client
             .post(upload_url)
-            .multipart(form)
-            .send()
-            .await?;
+            .multipart(form);
+        let response = self.execute(request).await?;
+
+        if !response.status().is_success() {
+            return Err(Error::Api {
+                code: response.status().as_u16(),
+                message: format!("File upload failed: {}", response.text().await.unwrap_or_default()),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Upload a file to the URL from an upload authorization without buffering it
+    pub async fn upload_file_stream_to_url(
+        &self,
+        upload_url: &str,
+        reader: FileReader,
+        length: u64,
+        params: &std::collections::HashMap<String, String>,
+    ) -> Result<()> {
+        let mut form = reqwest::multipart::Form::new();
+
+        for (key, value) in params {
+            form = form.text(key.clone(), value.clone());
+        }
+
+        // The file must be the last field of the form
+        let body = reqwest::Body::wrap_stream(ReaderStream::new(reader));
+        let file_part = reqwest::multipart::Part::stream_with_length(body, length)
+            .file_name("file");
+        form = form.part("file", file_part);
+
+        let request = self.client
+            .post(upload_url)
+            .multipart(form);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -916,14 +1024,10 @@ impl ZoteroClient {
             "upload": upload_key
         });
 
-        let response = self.client
+        let request = self.client
             .post(url)
-            .json(&completion_data)
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+            .json(&completion_data);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -945,17 +1049,80 @@ impl ZoteroClient {
         self.base_url.join(&path).map_err(Error::from)
     }
 
+    /// Fetch every page of a list endpoint and return the combined objects
+    /// together with the `Last-Modified-Version` of the first page.
+    ///
+    /// Follows `Link: rel="next"` when the server sends it and otherwise
+    /// advances `start` until `Total-Results` objects have been read.
+    async fn get_paginated<T: DeserializeOwned>(&self, url: Url, params: &[(&str, String)]) -> Result<(Vec<T>, Option<i64>)> {
+        let mut results: Vec<T> = Vec::new();
+        let mut last_modified_version = None;
+        let mut next_url: Option<Url> = None;
+
+        loop {
+            let request = match next_url.take() {
+                Some(next) => self.client.get(next),
+                None => self.client
+                    .get(url.clone())
+                    .query(params)
+                    .query(&[("start", results.len().to_string()), ("limit", PAGE_LIMIT.to_string())]),
+            };
+
+            let response = self.execute(request).await?;
+
+            if !response.status().is_success() {
+                return Err(Error::Api {
+                    code: response.status().as_u16(),
+                    message: response.text().await.unwrap_or_default(),
+                });
+            }
+
+            if last_modified_version.is_none() {
+                last_modified_version = response
+                    .headers()
+                    .get("Last-Modified-Version")
+                    .and_then(|h| h.to_str().ok())
+                    .and_then(|s| s.parse().ok());
+            }
+
+            let total_results: Option<usize> = response
+                .headers()
+                .get("Total-Results")
+                .and_then(|h| h.to_str().ok())
+                .and_then(|s| s.parse().ok());
+            next_url = next_page_link(response.headers());
+
+            let response_text = response.text().await?;
+            let page: Vec<T> = serde_json::from_str(&response_text).map_err(|e| {
+                tracing::error!("Failed to deserialize page of {}: {}", url, e);
+                tracing::error!("Full response text: {}", response_text);
+                Error::InvalidData(format!("Failed to parse {}: {}", url.path(), e))
+            })?;
+
+            let page_len = page.len();
+            results.extend(page);
+            tracing::debug!("Fetched {} of {:?} objects from {}", results.len(), total_results, url);
+
+            if next_url.is_some() {
+                continue;
+            }
+
+            match total_results {
+                Some(total) if page_len > 0 && results.len() < total => continue,
+                _ => break,
+            }
+        }
+
+        Ok((results, last_modified_version))
+    }
+
     pub async fn delete_item_unified(&self, library_id: i64, library_type: LibraryType, item_key: &str, library_version: i64) -> Result<i64> {
         let url = self.build_library_url(library_id, library_type, &format!("items/{}", item_key))?;
 
-        let response = self.client
+        let request = self.client
             .delete(url)
-            .header("If-Unmodified-Since-Version", library_version.to_string())
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+            .header("If-Unmodified-Since-Version", library_version.to_string());
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             204 => {
@@ -984,19 +1151,99 @@ impl ZoteroClient {
         }
     }
 
+    /// Delete up to [`WRITE_BATCH_LIMIT`] items in one request.
+    ///
+    /// Returns the new library version.
+    pub async fn delete_items_unified(&self, library_id: i64, library_type: LibraryType, item_keys: &[String], library_version: i64) -> Result<i64> {
+        self.delete_objects(library_id, library_type, "items", "itemKey", item_keys, library_version).await
+    }
+
+    /// Delete up to [`WRITE_BATCH_LIMIT`] collections in one request.
+    ///
+    /// Returns the new library version.
+    pub async fn delete_collections_unified(&self, library_id: i64, library_type: LibraryType, collection_keys: &[String], library_version: i64) -> Result<i64> {
+        self.delete_objects(library_id, library_type, "collections", "collectionKey", collection_keys, library_version).await
+    }
+
+    async fn delete_objects(&self, library_id: i64, library_type: LibraryType, endpoint: &str, key_param: &str, keys: &[String], library_version: i64) -> Result<i64> {
+        if keys.is_empty() {
+            return Ok(library_version);
+        }
+        if keys.len() > WRITE_BATCH_LIMIT {
+            return Err(Error::InvalidData(format!(
+                "Cannot delete more than {} {} per request, got {}",
+                WRITE_BATCH_LIMIT, endpoint, keys.len()
+            )));
+        }
+
+        let url = self.build_library_url(library_id, library_type, endpoint)?;
+
+        let request = self.client
+            .delete(url)
+            .query(&[(key_param, keys.join(","))])
+            .header("If-Unmodified-Since-Version", library_version.to_string());
+        let response = self.execute(request).await?;
+
+        match response.status().as_u16() {
+            204 => {
+                let new_version = response
+                    .headers()
+                    .get("Last-Modified-Version")
+                    .and_then(|h| h.to_str().ok())
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(library_version + 1);
+                Ok(new_version)
+            }
+            412 => {
+                // Precondition failed - at least one object changed remotely
+                Err(Error::Api {
+                    code: 412,
+                    message: format!("Library has been modified remotely. Sync required before deleting {}.", endpoint),
+                })
+            }
+            _ => {
+                Err(Error::Api {
+                    code: response.status().as_u16(),
+                    message: response.text().await.unwrap_or_default(),
+                })
+            }
+        }
+    }
+
     pub async fn upload_item_unified(&self, library_id: i64, library_type: LibraryType, item: &super::Item, library_version: i64) -> Result<i64> {
+        let (result, new_version) = self
+            .upload_items_unified(library_id, library_type, std::slice::from_ref(item), library_version)
+            .await?;
+
+        // A single-object write still reports rejection per object
+        match result.failed.get("0") {
+            Some(failed) => Err(Error::Api {
+                code: failed.code as u16,
+                message: failed.message.clone(),
+            }),
+            None => Ok(new_version),
+        }
+    }
+
+    /// Create or update up to [`WRITE_BATCH_LIMIT`] items in one request.
+    ///
+    /// Returns the per-index result and the new library version.
+    pub async fn upload_items_unified(&self, library_id: i64, library_type: LibraryType, items: &[super::Item], library_version: i64) -> Result<(super::ItemCollectionCreateResult, i64)> {
+        if items.len() > WRITE_BATCH_LIMIT {
+            return Err(Error::Validation(format!(
+                "Cannot upload {} items in one request (limit {})",
+                items.len(), WRITE_BATCH_LIMIT
+            )));
+        }
+
         let url = self.build_library_url(library_id, library_type, "items")?;
 
-        let items_array = vec![&item.data];
-        let response = self.client
+        let items_array: Vec<&super::ItemData> = items.iter().map(|item| &item.data).collect();
+        let request = self.client
             .post(url)
             .header("If-Unmodified-Since-Version", library_version.to_string())
-            .json(&items_array)
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+            .json(&items_array);
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             200 => {
@@ -1007,7 +1254,8 @@ impl ZoteroClient {
                     .and_then(|h| h.to_str().ok())
                     .and_then(|s| s.parse().ok())
                     .unwrap_or(library_version + 1);
-                Ok(new_version)
+                let result: super::ItemCollectionCreateResult = response.json().await?;
+                Ok((result, new_version))
             }
             412 => {
                 // Precondition failed - conflict
@@ -1028,13 +1276,9 @@ impl ZoteroClient {
     pub async fn get_attachment_download_url_unified(&self, library_id: i64, library_type: LibraryType, item_key: &str) -> Result<String> {
         let url = self.build_library_url(library_id, library_type, &format!("items/{}/file", item_key))?;
         
-        let response = self.client
-            .get(url)
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+        let request = self.client
+            .get(url);
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             302 => {
@@ -1069,6 +1313,14 @@ impl ZoteroClient {
         }
     }
 
+    /// Ask Zotero for permission to upload an attachment file.
+    ///
+    /// `previous_md5` is the hash of the file Zotero currently holds for the
+    /// item, or `None` for a first upload. Zotero answers 412 if its file no
+    /// longer matches. `mtime` is in milliseconds. Snapshots are sent as a
+    /// ZIP archive; `zip` then carries the archive's MD5 and file name, while
+    /// `md5` and `filename` describe the main file.
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_upload_authorization_unified(
         &self,
         library_id: i64,
@@ -1076,46 +1328,46 @@ impl ZoteroClient {
         item_key: &str,
         filename: &str,
         filesize: usize,
-        md5: &Option<String>,
-        mtime: Option<i64>,
+        md5: &str,
+        mtime: i64,
+        previous_md5: Option<&str>,
+        zip: Option<(&str, &str)>,
     ) -> Result<UploadAuthorization> {
         let url = self.build_library_url(library_id, library_type, &format!("items/{}/file", item_key))?;
-        
-        let mut upload_data = serde_json::json!({
-            "filename": filename,
-            "filesize": filesize,
-        });
 
-        if let Some(ref md5_hash) = md5 {
-            upload_data["md5"] = serde_json::Value::String(md5_hash.clone());
-        }
-
-        if let Some(mtime_val) = mtime {
-            upload_data["mtime"] = serde_json::Value::Number(serde_json::Number::from(mtime_val));
+        let mut form = vec![
+            ("md5", md5.to_string()),
+            ("filename", filename.to_string()),
+            ("filesize", filesize.to_string()),
+            ("mtime", mtime.to_string()),
+            ("params", "1".to_string()),
+        ];
+        if let Some((zip_md5, zip_filename)) = zip {
+            form.push(("zipMD5", zip_md5.to_string()));
+            form.push(("zipFilename", zip_filename.to_string()));
         }
 
-        let response = self.client
-            .post(url)
-            .json(&upload_data)
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+        let request = match previous_md5 {
+            Some(previous) => self.client.post(url).header("If-Match", previous),
+            None => self.client.post(url).header("If-None-Match", "*"),
+        };
+        let response = self.execute(request.form(&form)).await?;
 
         match response.status().as_u16() {
-            200 => {
-                // File already exists, no upload needed
-                Ok(UploadAuthorization {
-                    exists: true,
-                    upload_url: None,
-                    upload_key: None,
-                    params: None,
-                })
-            }
-            201 => {
-                // Upload required
-                let auth: UploadAuthorizationResponse = response.json().await?;
+            200 | 201 => {
+                let body: serde_json::Value = response.json().await?;
+
+                // Zotero already has a file with this hash
+                if body.get("exists").is_some() {
+                    return Ok(UploadAuthorization {
+                        exists: true,
+                        upload_url: None,
+                        upload_key: None,
+                        params: None,
+                    });
+                }
+
+                let auth: UploadAuthorizationResponse = serde_json::from_value(body)?;
                 Ok(UploadAuthorization {
                     exists: false,
                     upload_url: Some(auth.url),
@@ -1126,7 +1378,7 @@ impl ZoteroClient {
             412 => {
                 Err(Error::Api {
                     code: 412,
-                    message: "File upload precondition failed".to_string(),
+                    message: "Attachment file has been modified remotely".to_string(),
                 })
             }
             413 => {
@@ -1144,49 +1396,56 @@ impl ZoteroClient {
         }
     }
 
+    /// Tell Zotero an authorized upload finished.
+    ///
+    /// Returns the new library version if Zotero reported one.
     pub async fn register_upload_completion_unified(
         &self,
         library_id: i64,
         library_type: LibraryType,
         item_key: &str,
         upload_key: &str,
-    ) -> Result<()> {
+        previous_md5: Option<&str>,
+    ) -> Result<Option<i64>> {
         let url = self.build_library_url(library_id, library_type, &format!("items/{}/file", item_key))?;
-        
-        let completion_data = serde_json::json!({
-            "upload": upload_key
-        });
-
-        let response = self.client
-            .post(url)
-            .json(&completion_data)
-            .send()
-            .await?;
 
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+        let request = match previous_md5 {
+            Some(previous) => self.client.post(url).header("If-Match", previous),
+            None => self.client.post(url).header("If-None-Match", "*"),
+        };
+        let response = self.execute(request.form(&[("upload", upload_key)])).await?;
 
-        if !response.status().is_success() {
-            return Err(Error::Api {
-                code: response.status().as_u16(),
-                message: response.text().await.unwrap_or_default(),
-            });
+        match response.status().as_u16() {
+            200..=299 => {
+                let new_version = response
+                    .headers()
+                    .get("Last-Modified-Version")
+                    .and_then(|h| h.to_str().ok())
+                    .and_then(|s| s.parse().ok());
+                Ok(new_version)
+            }
+            412 => {
+                Err(Error::Api {
+                    code: 412,
+                    message: "Attachment file has been modified remotely".to_string(),
+                })
+            }
+            _ => {
+                Err(Error::Api {
+                    code: response.status().as_u16(),
+                    message: response.text().await.unwrap_or_default(),
+                })
+            }
         }
-
-        Ok(())
     }
 
     pub async fn delete_collection_unified(&self, library_id: i64, library_type: LibraryType, collection_key: &str, library_version: i64) -> Result<i64> {
         let url = self.build_library_url(library_id, library_type, &format!("collections/{}", collection_key))?;
 
-        let response = self.client
+        let request = self.client
             .delete(url)
-            .header("If-Unmodified-Since-Version", library_version.to_string())
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+            .header("If-Unmodified-Since-Version", library_version.to_string());
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             204 => {
@@ -1219,19 +1478,61 @@ impl ZoteroClient {
         let url = self.build_library_url(library_id, library_type, "collections")?;
 
         let collections_array = vec![&collection.data];
-        let response = self.client
+        let request = self.client
             .post(url)
             .header("If-Unmodified-Since-Version", library_version.to_string())
-            .json(&collections_array)
-            .send()
-            .await?;
-
-        // Handle rate limiting
-        self.handle_rate_limiting(&response).await?;
+            .json(&collections_array);
+        let response = self.execute(request).await?;
 
         match response.status().as_u16() {
             200 => {
                 // Success
+                let new_version = response
+                    .headers()
+                    .get("Last-Modified-Version")
+                    .and_then(|h| h.to_str().ok())
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(library_version + 1);
+
+                // A single-object write still reports rejection per object
+                let result: super::ItemCollectionCreateResult = response.json().await?;
+                match result.failed.get("0") {
+                    Some(failed) => Err(Error::Api {
+                        code: failed.code as u16,
+                        message: failed.message.clone(),
+                    }),
+                    None => Ok(new_version),
+                }
+            }
+            412 => {
+                // Precondition failed - conflict
+                Err(Error::Api {
+                    code: 412,
+                    message: "Library has been modified remotely. Sync required.".to_string(),
+                })
+            }
+            _ => {
+                Err(Error::Api {
+                    code: response.status().as_u16(),
+                    message: response.text().await.unwrap_or_default(),
+                })
+            }
+        }
+    }
+
+    pub async fn delete_search_unified(&self, library_id: i64, library_type: LibraryType, search_key: &str, library_version: i64) -> Result<i64> {
+        // Saved searches can only be deleted through the multi-object endpoint
+        let url = self.build_library_url(library_id, library_type, "searches")?;
+
+        let request = self.client
+            .delete(url)
+            .query(&[("searchKey", search_key)])
+            .header("If-Unmodified-Since-Version", library_version.to_string());
+        let response = self.execute(request).await?;
+
+        match response.status().as_u16() {
+            204 => {
+                // Success - deleted
                 let new_version = response
                     .headers()
                     .get("Last-Modified-Version")
@@ -1240,6 +1541,52 @@ impl ZoteroClient {
                     .unwrap_or(library_version + 1);
                 Ok(new_version)
             }
+            412 => {
+                // Precondition failed - conflict
+                Err(Error::Api {
+                    code: 412,
+                    message: "Search has been modified remotely. Sync required.".to_string(),
+                })
+            }
+            _ => {
+                Err(Error::Api {
+                    code: response.status().as_u16(),
+                    message: response.text().await.unwrap_or_default(),
+                })
+            }
+        }
+    }
+
+    pub async fn upload_search_unified(&self, library_id: i64, library_type: LibraryType, search: &super::Search, library_version: i64) -> Result<i64> {
+        let url = self.build_library_url(library_id, library_type, "searches")?;
+
+        let searches_array = vec![&search.data];
+        let request = self.client
+            .post(url)
+            .header("If-Unmodified-Since-Version", library_version.to_string())
+            .json(&searches_array);
+        let response = self.execute(request).await?;
+
+        match response.status().as_u16() {
+            200 => {
+                // Success
+                let new_version = response
+                    .headers()
+                    .get("Last-Modified-Version")
+                    .and_then(|h| h.to_str().ok())
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(library_version + 1);
+
+                // A single-object write still reports rejection per object
+                let result: super::ItemCollectionCreateResult = response.json().await?;
+                match result.failed.get("0") {
+                    Some(failed) => Err(Error::Api {
+                        code: failed.code as u16,
+                        message: failed.message.clone(),
+                    }),
+                    None => Ok(new_version),
+                }
+            }
             412 => {
                 // Precondition failed - conflict
                 Err(Error::Api {
@@ -1260,8 +1607,8 @@ impl ZoteroClient {
         let query = format!(
             r#"
             SELECT l.id, l.library_type, l.version, l.created, l.modified, l.data, l.deleted,
-                   l.item_version, l.collection_version, l.tag_version, l.gitlab,
-                   sl.active, sl.incoming_sync, sl.outgoing_sync, sl.tags
+                   l.item_version, l.collection_version, l.search_version, l.tag_version, l.fulltext_version, l.gitlab,
+                   sl.active, sl.incoming_sync, sl.outgoing_sync, sl.tags, sl.conflict_policy
             FROM {}.libraries l, {}.sync_libraries sl
             WHERE l.id = sl.library_id AND l.library_type = sl.library_type
                   AND l.id = $1 AND l.library_type = 'user'
@@ -1350,11 +1697,10 @@ impl ZoteroClient {
         
         tracing::info!("Calling collections versions API: {} with since={}", url, since_version);
         
-        let response = self.client
+        let request = self.client
             .get(url)
-            .query(&[("format", "versions"), ("since", &since_version.to_string())])
-            .send()
-            .await?;
+            .query(&[("format", "versions"), ("since", &since_version.to_string())]);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             tracing::error!("Collections versions API failed with status: {}", response.status());
@@ -1404,15 +1750,53 @@ impl ZoteroClient {
         let keys = collection_keys.join(",");
         
         tracing::info!("Calling collections data API: {} with keys={}", url, keys);
-        
-        let response = self.client
-            .get(url)
-            .query(&[("collectionKey", &keys)])
-            .send()
+
+        let (api_collections, last_modified_version) = self
+            .get_paginated::<super::CollectionApiResponse>(url, &[("collectionKey", keys)])
             .await?;
 
+        tracing::info!("Successfully parsed collections API response: {} collections", api_collections.len());
+
+        // Convert API response to internal Collection structs
+        let mut collections = Vec::new();
+        for api_collection in api_collections {
+            let library_type = match api_collection.library.library_type.as_str() {
+                "user" => super::LibraryType::User,
+                "group" => super::LibraryType::Group,
+                _ => super::LibraryType::Group, // Default fallback
+            };
+            
+            let collection = super::Collection {
+                key: api_collection.key,
+                version: api_collection.version,
+                library_id: api_collection.library.id,
+                library_type,
+                data: api_collection.data,
+                meta: Some(super::collection::CollectionMeta {
+                    num_collections: api_collection.meta.num_collections,
+                    num_items: api_collection.meta.num_items,
+                }),
+                deleted: false,
+                sync_status: super::SyncStatus::Synced,
+                synced_data: None,
+                db: None,
+                db_schema: None,
+            };
+            collections.push(collection);
+        }
+
+        Ok((collections, last_modified_version.unwrap_or(0)))
+    }
+
+    pub async fn get_searches_version_cloud_unified(&self, library_id: i64, library_type: LibraryType, since_version: i64) -> Result<(std::collections::HashMap<String, i64>, i64)> {
+        let url = self.build_library_url(library_id, library_type, "searches")?;
+
+        let request = self.client
+            .get(url)
+            .query(&[("format", "versions"), ("since", &since_version.to_string())]);
+        let response = self.execute(request).await?;
+
         if !response.status().is_success() {
-            tracing::error!("Collections data API failed with status: {}", response.status());
             return Err(Error::Api {
                 code: response.status().as_u16(),
                 message: response.text().await.unwrap_or_default(),
@@ -1424,59 +1808,47 @@ impl ZoteroClient {
             .get("Last-Modified-Version")
             .and_then(|h| h.to_str().ok())
             .and_then(|s| s.parse().ok())
-            .unwrap_or(0);
+            .unwrap_or(since_version);
 
-        // Get the raw response text for debugging
-        let response_text = response.text().await?;
-        tracing::info!("Collections data API response (first 1000 chars): {}", 
-            if response_text.len() > 1000 { 
-                format!("{}...", &response_text[..1000]) 
-            } else { 
-                response_text.clone() 
-            }
-        );
+        let versions: std::collections::HashMap<String, i64> = response.json().await?;
+        Ok((versions, last_modified_version))
+    }
 
-        // Try to deserialize and log any errors
-        match serde_json::from_str::<Vec<super::CollectionApiResponse>>(&response_text) {
-            Ok(api_collections) => {
-                tracing::info!("Successfully parsed collections API response: {} collections", api_collections.len());
-                
-                // Convert API response to internal Collection structs
-                let mut collections = Vec::new();
-                for api_collection in api_collections {
-                    let library_type = match api_collection.library.library_type.as_str() {
-                        "user" => super::LibraryType::User,
-                        "group" => super::LibraryType::Group,
-                        _ => super::LibraryType::Group, // Default fallback
-                    };
-                    
-                    let collection = super::Collection {
-                        key: api_collection.key,
-                        version: api_collection.version,
-                        library_id: api_collection.library.id,
-                        library_type,
-                        data: api_collection.data,
-                        meta: Some(super::collection::CollectionMeta {
-                            num_collections: api_collection.meta.num_collections,
-                            num_items: api_collection.meta.num_items,
-                        }),
-                        deleted: false,
-                        sync_status: super::SyncStatus::Synced,
-                        db: None,
-                        db_schema: None,
-                    };
-                    collections.push(collection);
-                }
-                
-                tracing::info!("Successfully converted to internal collections: {} collections", collections.len());
-                Ok((collections, last_modified_version))
-            }
-            Err(e) => {
-                tracing::error!("Failed to deserialize collections data response: {}", e);
-                tracing::error!("Full response text: {}", response_text);
-                Err(Error::InvalidData(format!("Failed to parse collections data: {}", e)))
-            }
+    pub async fn get_searches_cloud_unified(&self, library_id: i64, library_type: LibraryType, search_keys: &[String]) -> Result<Vec<super::Search>> {
+        if search_keys.is_empty() {
+            return Ok(Vec::new());
         }
+
+        let url = self.build_library_url(library_id, library_type, "searches")?;
+        let keys = search_keys.join(",");
+
+        let (api_searches, _) = self
+            .get_paginated::<super::SearchApiResponse>(url, &[("searchKey", keys)])
+            .await?;
+
+        // Convert API response to internal Search structs
+        let mut searches = Vec::new();
+        for api_search in api_searches {
+            let library_type = match api_search.library.library_type.as_str() {
+                "user" => super::LibraryType::User,
+                "group" => super::LibraryType::Group,
+                _ => super::LibraryType::Group, // Default fallback
+            };
+
+            searches.push(super::Search {
+                key: api_search.key,
+                version: api_search.version,
+                library_id: api_search.library.id,
+                library_type,
+                data: api_search.data,
+                deleted: false,
+                sync_status: super::SyncStatus::Synced,
+                db: None,
+                db_schema: None,
+            });
+        }
+
+        Ok(searches)
     }
 
     pub async fn get_items_version_cloud_unified(&self, library_id: i64, library_type: LibraryType, since_version: i64, trashed: bool) -> Result<(std::collections::HashMap<String, i64>, i64)> {
@@ -1491,11 +1863,10 @@ impl ZoteroClient {
             params.push(("trashed", "1".to_string()));
         }
         
-        let response = self.client
+        let request = self.client
             .get(url)
-            .query(&params)
-            .send()
-            .await?;
+            .query(&params);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -1522,75 +1893,53 @@ impl ZoteroClient {
 
         let url = self.build_library_url(library_id, library_type, "items")?;
         let keys = item_keys.join(",");
-        
-        let response = self.client
-            .get(url)
-            .query(&[("itemKey", &keys)])
-            .send()
+
+        let (api_items, _) = self
+            .get_paginated::<super::ItemApiResponse>(url, &[("itemKey", keys)])
             .await?;
 
-        if !response.status().is_success() {
-            return Err(Error::Api {
-                code: response.status().as_u16(),
-                message: response.text().await.unwrap_or_default(),
-            });
+        // Convert API response to internal Item structs
+        let mut items = Vec::new();
+        for api_item in api_items {
+            let library_type = match api_item.library.library_type.as_str() {
+                "user" => super::LibraryType::User,
+                "group" => super::LibraryType::Group,
+                _ => super::LibraryType::Group, // Default fallback
+            };
+            
+            let item = super::Item {
+                key: api_item.key,
+                version: api_item.version,
+                library_id: api_item.library.id,
+                library_type,
+                data: api_item.data,
+                meta: Some(super::item::ItemMeta {
+                    created_by_user: api_item.meta.created_by_user,
+                    creator_summary: api_item.meta.creator_summary,
+                    parsed_date: api_item.meta.parsed_date,
+                    num_children: api_item.meta.num_children,
+                }),
+                trashed: false, // Will be set based on API data if needed
+                deleted: false,
+                sync_status: super::SyncStatus::Synced,
+                md5: None, // Will be extracted from data if it's an attachment
+                synced_data: None,
+                db: None,
+                db_schema: None,
+            };
+            items.push(item);
         }
 
-        // Get the raw response text for debugging
-        let response_text = response.text().await?;
-        
-        // Try to deserialize and log any errors
-        match serde_json::from_str::<Vec<super::ItemApiResponse>>(&response_text) {
-            Ok(api_items) => {
-                // Convert API response to internal Item structs
-                let mut items = Vec::new();
-                for api_item in api_items {
-                    let library_type = match api_item.library.library_type.as_str() {
-                        "user" => super::LibraryType::User,
-                        "group" => super::LibraryType::Group,
-                        _ => super::LibraryType::Group, // Default fallback
-                    };
-                    
-                    let item = super::Item {
-                        key: api_item.key,
-                        version: api_item.version,
-                        library_id: api_item.library.id,
-                        library_type,
-                        data: api_item.data,
-                        meta: Some(super::item::ItemMeta {
-                            created_by_user: api_item.meta.created_by_user,
-                            creator_summary: api_item.meta.creator_summary,
-                            parsed_date: api_item.meta.parsed_date,
-                            num_children: api_item.meta.num_children,
-                        }),
-                        trashed: false, // Will be set based on API data if needed
-                        deleted: false,
-                        sync_status: super::SyncStatus::Synced,
-                        md5: None, // Will be extracted from data if it's an attachment
-                        db: None,
-                        db_schema: None,
-                    };
-                    items.push(item);
-                }
-                
-                Ok(items)
-            }
-            Err(e) => {
-                tracing::error!("Failed to deserialize items response: {}", e);
-                tracing::error!("Full response text: {}", response_text);
-                Err(Error::InvalidData(format!("Failed to parse items: {}", e)))
-            }
-        }
+        Ok(items)
     }
 
-    pub async fn get_tags_cloud_unified(&self, library_id: i64, library_type: LibraryType, since_version: i64) -> Result<(Vec<super::Tag>, i64)> {
-        let url = self.build_library_url(library_id, library_type, "tags")?;
-        
-        let response = self.client
+    pub async fn get_fulltext_versions_cloud_unified(&self, library_id: i64, library_type: LibraryType, since_version: i64) -> Result<(std::collections::HashMap<String, i64>, i64)> {
+        let url = self.build_library_url(library_id, library_type, "fulltext")?;
+
+        let request = self.client
             .get(url)
-            .query(&[("since", &since_version.to_string())])
-            .send()
-            .await?;
+            .query(&[("since", &since_version.to_string())]);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             return Err(Error::Api {
@@ -1606,8 +1955,42 @@ impl ZoteroClient {
             .and_then(|s| s.parse().ok())
             .unwrap_or(since_version);
 
-        let tags: Vec<super::Tag> = response.json().await?;
-        Ok((tags, last_modified_version))
+        let versions: std::collections::HashMap<String, i64> = response.json().await?;
+        Ok((versions, last_modified_version))
+    }
+
+    /// Fetch the extracted full-text content of an item.
+    ///
+    /// Returns `None` when Zotero has no full-text content for the item.
+    pub async fn get_item_fulltext_cloud_unified(&self, library_id: i64, library_type: LibraryType, item_key: &str) -> Result<Option<super::FullTextData>> {
+        let url = self.build_library_url(library_id, library_type, &format!("items/{}/fulltext", item_key))?;
+
+        let request = self.client
+            .get(url);
+        let response = self.execute(request).await?;
+
+        match response.status().as_u16() {
+            200 => {
+                let fulltext: super::FullTextData = response.json().await?;
+                Ok(Some(fulltext))
+            }
+            404 => Ok(None),
+            _ => {
+                Err(Error::Api {
+                    code: response.status().as_u16(),
+                    message: response.text().await.unwrap_or_default(),
+                })
+            }
+        }
+    }
+
+    pub async fn get_tags_cloud_unified(&self, library_id: i64, library_type: LibraryType, since_version: i64) -> Result<(Vec<super::Tag>, i64)> {
+        let url = self.build_library_url(library_id, library_type, "tags")?;
+
+        let (tags, last_modified_version) = self
+            .get_paginated::<super::Tag>(url, &[("since", since_version.to_string())])
+            .await?;
+        Ok((tags, last_modified_version.unwrap_or(since_version)))
     }
 
     pub async fn get_deletions_cloud_unified(&self, library_id: i64, library_type: LibraryType, since_version: i64) -> Result<(super::Deletions, i64)> {
@@ -1615,11 +1998,10 @@ impl ZoteroClient {
         
         tracing::info!("Calling deletions API: {} with since={}", url, since_version);
         
-        let response = self.client
+        let request = self.client
             .get(url)
-            .query(&[("since", &since_version.to_string())])
-            .send()
-            .await?;
+            .query(&[("since", &since_version.to_string())]);
+        let response = self.execute(request).await?;
 
         if !response.status().is_success() {
             tracing::error!("Deletions API failed with status: {}", response.status());
@@ -1649,8 +2031,8 @@ impl ZoteroClient {
         // Try to deserialize and log any errors
         match serde_json::from_str::<super::Deletions>(&response_text) {
             Ok(deletions) => {
-                tracing::info!("Successfully parsed deletions: {} collections, {} items, {} tags", 
-                    deletions.collections.len(), deletions.items.len(), deletions.tags.len());
+                tracing::info!("Successfully parsed deletions: {} collections, {} searches, {} items, {} tags", 
+                    deletions.collections.len(), deletions.searches.len(), deletions.items.len(), deletions.tags.len());
                 Ok((deletions, last_modified_version))
             }
             Err(e) => {
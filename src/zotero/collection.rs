@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use crate::{Result, Error};
-use super::{CollectionData, SyncStatus, LibraryType};
+use super::{CollectionData, SyncStatus, LibraryType, FieldChange};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Collection {
@@ -13,6 +13,9 @@ pub struct Collection {
     pub meta: Option<CollectionMeta>,
     pub deleted: bool,
     pub sync_status: SyncStatus,
+    /// `data` as it was when last in agreement with Zotero
+    #[serde(default)]
+    pub synced_data: Option<serde_json::Value>,
     
     #[serde(skip)]
     pub db: Option<PgPool>,
@@ -34,13 +37,19 @@ impl Collection {
         self.db_schema = Some(db_schema);
     }
 
+    /// Fields of `data` that differ from the last-synced snapshot
+    pub fn diff(&self) -> Result<Vec<FieldChange>> {
+        let data = serde_json::to_value(&self.data)?;
+        Ok(super::diff::diff_fields(self.synced_data.as_ref(), &data))
+    }
+
     pub async fn update_local(&self) -> Result<()> {
         let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
         let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
 
         let data_json = serde_json::to_string(&self.data)?;
         let meta_json = self.meta.as_ref()
-            .map(|m| serde_json::to_string(m))
+            .map(serde_json::to_string)
             .transpose()?;
 
         let sync_status_str = match self.sync_status {
@@ -52,14 +61,15 @@ impl Collection {
 
         let query = format!(
             r#"
-            INSERT INTO {}.collections (key, version, library_id, library_type, data, meta, deleted, sync)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            INSERT INTO {0}.collections (key, version, library_id, library_type, data, meta, deleted, sync, synced_data)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             ON CONFLICT (key, library_id, library_type) DO UPDATE SET
                 version = EXCLUDED.version,
                 data = EXCLUDED.data,
                 meta = EXCLUDED.meta,
                 deleted = EXCLUDED.deleted,
-                sync = EXCLUDED.sync
+                sync = EXCLUDED.sync,
+                synced_data = COALESCE(EXCLUDED.synced_data, {0}.collections.synced_data)
             "#,
             schema
         );
@@ -73,12 +83,43 @@ impl Collection {
             .bind(&meta_json)
             .bind(self.deleted)
             .bind(sync_status_str)
+            .bind(&self.synced_data)
             .execute(db)
             .await?;
 
         Ok(())
     }
 
+    /// Delete up to [`WRITE_BATCH_LIMIT`](super::client::WRITE_BATCH_LIMIT)
+    /// collections of one library in a single request and remove their local rows.
+    ///
+    /// Returns the new library version.
+    pub async fn delete_cloud_batch(client: &super::ZoteroClient, collections: &[Collection], library_version: i64) -> Result<i64> {
+        let Some(first) = collections.first() else {
+            return Ok(library_version);
+        };
+
+        let keys: Vec<String> = collections.iter().map(|collection| collection.key.clone()).collect();
+        let new_version = client
+            .delete_collections_unified(first.library_id, first.library_type, &keys, library_version)
+            .await?;
+
+        if let (Some(db), Some(schema)) = (&first.db, &first.db_schema) {
+            let query = format!(
+                "DELETE FROM {}.collections WHERE key = ANY($1) AND library_id = $2 AND library_type = $3",
+                schema
+            );
+            sqlx::query(&query)
+                .bind(&keys)
+                .bind(first.library_id)
+                .bind(first.library_type)
+                .execute(db)
+                .await?;
+        }
+
+        Ok(new_version)
+    }
+
     pub async fn update_cloud(&mut self, client: &super::ZoteroClient, library_version: i64) -> Result<i64> {
         // Check if collection is marked for deletion
         if self.deleted {
@@ -110,11 +151,12 @@ impl Collection {
                 // Update local status
                 self.sync_status = SyncStatus::Synced;
                 self.version = new_version;
+                self.synced_data = Some(serde_json::to_value(&self.data)?);
                 
                 // Update local database
                 if let (Some(db), Some(schema)) = (&self.db, &self.db_schema) {
                     let query = format!(
-                        "UPDATE {}.collections SET sync = 'synced', version = $1 WHERE key = $2 AND library_id = $3 AND library_type = $4",
+                        "UPDATE {}.collections SET sync = 'synced', version = $1, synced_data = data WHERE key = $2 AND library_id = $3 AND library_type = $4",
                         schema
                     );
                     sqlx::query(&query)
@@ -0,0 +1,713 @@
+//! Conflict resolution for uploads rejected with 412 Precondition Failed.
+//!
+//! Zotero rejects a write when the library (or the object) changed since the
+//! version we sent. Instead of failing the upload, the resolver fetches the
+//! remote object, compares it with the local row and the `synced_data`
+//! snapshot taken at the last successful sync, and applies the library's
+//! [`ConflictPolicy`]. Conflicts it cannot settle are stored in
+//! `sync_conflicts` and the row is parked as `incomplete` until reviewed;
+//! [`SyncConflicts`] lists them and settles them by hand.
+//!
+//! Attachment files are handled the same way, except that two versions of a
+//! file cannot be merged: under `merge` they are recorded like under `manual`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::{PgPool, Row};
+use tracing::{info, warn};
+
+use crate::{Error, Result};
+use crate::filesystem::FileSystem;
+use super::{ZoteroClient, ConflictPolicy, LibraryType, SyncStatus, Item, Collection, ChangeSource, TransferStatus};
+
+/// Fields maintained by Zotero itself; the remote value is always kept
+const SERVER_FIELDS: &[&str] = &["key", "version", "dateAdded", "dateModified"];
+
+/// A field that was changed both locally and remotely since the last sync
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldConflict {
+    pub field: String,
+    pub base: Option<Value>,
+    pub local: Option<Value>,
+    pub remote: Option<Value>,
+}
+
+/// Result of a field-level three-way merge
+#[derive(Debug, Clone)]
+pub struct MergeResult {
+    /// Merged object; conflicting fields keep their local value
+    pub merged: Value,
+    pub conflicts: Vec<FieldConflict>,
+}
+
+/// What the resolver did with a rejected upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictOutcome {
+    /// The object itself was unchanged remotely; re-sent with a fresh version
+    Retried,
+    /// Local data was uploaded over the remote object
+    LocalApplied,
+    /// Remote data replaced the local row
+    RemoteApplied,
+    /// Non-overlapping changes from both sides were combined and uploaded
+    Merged,
+    /// Stored in `sync_conflicts` for manual review
+    Recorded,
+}
+
+impl std::fmt::Display for ConflictOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConflictOutcome::Retried => write!(f, "retried"),
+            ConflictOutcome::LocalApplied => write!(f, "local applied"),
+            ConflictOutcome::RemoteApplied => write!(f, "remote applied"),
+            ConflictOutcome::Merged => write!(f, "merged"),
+            ConflictOutcome::Recorded => write!(f, "recorded"),
+        }
+    }
+}
+
+/// Decision taken for an object that changed on both sides
+enum Decision {
+    Upload(Value, ConflictOutcome),
+    AcceptRemote,
+    Record(Vec<FieldConflict>),
+}
+
+/// Merge `local` and `remote` field by field against their common ancestor.
+///
+/// Without a `base` snapshot every differing field counts as a conflict.
+pub fn three_way_merge(base: Option<&Value>, local: &Value, remote: &Value) -> MergeResult {
+    let empty = Map::new();
+    let base = base.and_then(Value::as_object);
+    let local = local.as_object().unwrap_or(&empty);
+    let remote = remote.as_object().unwrap_or(&empty);
+
+    let mut merged = Map::new();
+    let mut conflicts = Vec::new();
+
+    let fields = local.keys().chain(remote.keys().filter(|k| !local.contains_key(*k)));
+    for field in fields {
+        let l = local.get(field);
+        let r = remote.get(field);
+
+        let value = if SERVER_FIELDS.contains(&field.as_str()) || l == r {
+            r.or(l)
+        } else {
+            match base.map(|b| b.get(field)) {
+                // Only the remote side changed
+                Some(b) if b == l => r,
+                // Only the local side changed
+                Some(b) if b == r => l,
+                b => {
+                    conflicts.push(FieldConflict {
+                        field: field.clone(),
+                        base: b.flatten().cloned(),
+                        local: l.cloned(),
+                        remote: r.cloned(),
+                    });
+                    l
+                }
+            }
+        };
+
+        if let Some(value) = value {
+            merged.insert(field.clone(), value.clone());
+        }
+    }
+
+    MergeResult { merged: Value::Object(merged), conflicts }
+}
+
+/// Resolves 412 responses for a single library according to its policy
+pub struct ConflictResolver<'a> {
+    client: &'a ZoteroClient,
+    db: &'a PgPool,
+    schema: &'a str,
+    policy: ConflictPolicy,
+}
+
+impl<'a> ConflictResolver<'a> {
+    pub fn new(client: &'a ZoteroClient, db: &'a PgPool, schema: &'a str, policy: ConflictPolicy) -> Self {
+        Self { client, db, schema, policy }
+    }
+
+    /// Load the configured policy of a library
+    pub async fn load_policy(db: &PgPool, schema: &str, library_id: i64, library_type: LibraryType) -> Result<ConflictPolicy> {
+        let query = format!(
+            "SELECT conflict_policy FROM {}.sync_libraries WHERE library_id = $1 AND library_type = $2",
+            schema
+        );
+
+        let policy = sqlx::query_scalar::<_, ConflictPolicy>(&query)
+            .bind(library_id)
+            .bind(library_type)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(policy.unwrap_or_default())
+    }
+
+    /// Resolve a rejected item upload
+    pub async fn resolve_item(&self, item: &mut Item, library_version: &mut i64) -> Result<ConflictOutcome> {
+        let remote = self.client
+            .get_items_cloud_unified(item.library_id, item.library_type, std::slice::from_ref(&item.key))
+            .await?
+            .into_iter()
+            .next();
+        let (_, current_version) = self.client
+            .get_items_version_cloud_unified(item.library_id, item.library_type, *library_version, false)
+            .await?;
+        *library_version = current_version.max(*library_version);
+
+        let local_data = serde_json::to_value(&item.data)?;
+        let base = item.synced_data.clone();
+
+        let Some(remote) = remote else {
+            // Never uploaded, so the 412 can only be about the library version
+            if item.sync_status == SyncStatus::New {
+                item.update_cloud(self.client, library_version).await?;
+                return Ok(ConflictOutcome::Retried);
+            }
+            return self.resolve_remote_deleted("item", "items", &item.key, item.library_id, item.library_type, base, local_data).await;
+        };
+
+        // Another object moved the library version; this one is untouched remotely
+        if remote.version <= item.version {
+            item.data.version = remote.version;
+            item.update_cloud(self.client, library_version).await?;
+            return Ok(ConflictOutcome::Retried);
+        }
+
+        let remote_data = serde_json::to_value(&remote.data)?;
+        match self.decide(base.as_ref(), &local_data, &remote_data) {
+            Decision::Upload(data, outcome) => {
+                item.data = serde_json::from_value(data)?;
+                item.data.version = remote.version;
+                item.version = remote.version;
+                item.sync_status = SyncStatus::Modified;
+                item.update_cloud(self.client, library_version).await?;
+                self.store_data("items", &item.key, item.library_id, item.library_type, &serde_json::to_value(&item.data)?).await?;
+                Ok(outcome)
+            }
+            Decision::AcceptRemote => {
+                self.accept_remote("items", &item.key, item.library_id, item.library_type, &remote_data, remote.version).await?;
+                *item = Item { synced_data: Some(remote_data), db: item.db.take(), db_schema: item.db_schema.take(), ..remote };
+                Ok(ConflictOutcome::RemoteApplied)
+            }
+            Decision::Record(conflicts) => {
+                self.record("item", "items", &item.key, item.library_id, item.library_type, base, local_data, Some(remote_data), Some(remote.version), &conflicts).await?;
+                Ok(ConflictOutcome::Recorded)
+            }
+        }
+    }
+
+    /// Resolve a rejected collection upload
+    pub async fn resolve_collection(&self, collection: &mut Collection, library_version: &mut i64) -> Result<ConflictOutcome> {
+        let (remote, current_version) = self.client
+            .get_collections_cloud_unified(collection.library_id, collection.library_type, std::slice::from_ref(&collection.key))
+            .await?;
+        *library_version = current_version.max(*library_version);
+
+        let local_data = serde_json::to_value(&collection.data)?;
+        let base = collection.synced_data.clone();
+
+        let Some(remote) = remote.into_iter().next() else {
+            // Never uploaded, so the 412 can only be about the library version
+            if collection.sync_status == SyncStatus::New {
+                *library_version = collection.update_cloud(self.client, *library_version).await?;
+                return Ok(ConflictOutcome::Retried);
+            }
+            return self.resolve_remote_deleted("collection", "collections", &collection.key, collection.library_id, collection.library_type, base, local_data).await;
+        };
+
+        // Another object moved the library version; this one is untouched remotely
+        if remote.version <= collection.version {
+            collection.data.version = remote.version;
+            *library_version = collection.update_cloud(self.client, *library_version).await?;
+            return Ok(ConflictOutcome::Retried);
+        }
+
+        let remote_data = serde_json::to_value(&remote.data)?;
+        match self.decide(base.as_ref(), &local_data, &remote_data) {
+            Decision::Upload(data, outcome) => {
+                collection.data = serde_json::from_value(data)?;
+                collection.data.version = remote.version;
+                collection.version = remote.version;
+                collection.sync_status = SyncStatus::Modified;
+                *library_version = collection.update_cloud(self.client, *library_version).await?;
+                self.store_data("collections", &collection.key, collection.library_id, collection.library_type, &serde_json::to_value(&collection.data)?).await?;
+                Ok(outcome)
+            }
+            Decision::AcceptRemote => {
+                self.accept_remote("collections", &collection.key, collection.library_id, collection.library_type, &remote_data, remote.version).await?;
+                *collection = Collection { synced_data: Some(remote_data), db: collection.db.take(), db_schema: collection.db_schema.take(), ..remote };
+                Ok(ConflictOutcome::RemoteApplied)
+            }
+            Decision::Record(conflicts) => {
+                self.record("collection", "collections", &collection.key, collection.library_id, collection.library_type, base, local_data, Some(remote_data), Some(remote.version), &conflicts).await?;
+                Ok(ConflictOutcome::Recorded)
+            }
+        }
+    }
+
+    /// Resolve a rejected attachment file upload
+    pub async fn resolve_file(&self, item: &mut Item, filesystem: &dyn FileSystem, library_version: &mut i64) -> Result<ConflictOutcome> {
+        let remote = self.client
+            .get_items_cloud_unified(item.library_id, item.library_type, std::slice::from_ref(&item.key))
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::NotFound(format!("Attachment {} not found in Zotero", item.key)))?;
+        let remote_md5 = remote.data.extra_fields.get("md5").cloned();
+
+        match self.policy {
+            ConflictPolicy::LocalWins => {
+                // Replace whatever file Zotero holds now
+                match &remote_md5 {
+                    Some(md5) => item.data.extra_fields.insert("md5".to_string(), md5.clone()),
+                    None => item.data.extra_fields.remove("md5"),
+                };
+                item.upload_file_cloud(self.client, filesystem, library_version).await?;
+                Ok(ConflictOutcome::LocalApplied)
+            }
+            ConflictPolicy::RemoteWins => {
+                remote.download_attachment_cloud(self.client, filesystem).await?;
+
+                let remote_data = serde_json::to_value(&remote.data)?;
+                self.accept_remote("items", &item.key, item.library_id, item.library_type, &remote_data, remote.version).await?;
+
+                let md5 = remote_md5.as_ref().and_then(Value::as_str).map(str::to_string);
+                let query = format!(
+                    "UPDATE {}.items SET md5 = $1 WHERE key = $2 AND library_id = $3 AND library_type = $4",
+                    self.schema
+                );
+                sqlx::query(&query)
+                    .bind(&md5)
+                    .bind(&item.key)
+                    .bind(item.library_id)
+                    .bind(item.library_type)
+                    .execute(self.db)
+                    .await?;
+
+                *item = Item { md5, synced_data: Some(remote_data), db: item.db.take(), db_schema: item.db_schema.take(), ..remote };
+                Ok(ConflictOutcome::RemoteApplied)
+            }
+            ConflictPolicy::Merge | ConflictPolicy::Manual => {
+                let base = item.data.extra_fields.get("md5").map(|md5| serde_json::json!({ "md5": md5 }));
+                let local = serde_json::json!({ "md5": item.md5 });
+                let remote_file = serde_json::json!({
+                    "md5": remote_md5,
+                    "mtime": remote.data.extra_fields.get("mtime"),
+                });
+                self.record("file", "items", &item.key, item.library_id, item.library_type, base, local, Some(remote_file), Some(remote.version), &[]).await?;
+                Ok(ConflictOutcome::Recorded)
+            }
+        }
+    }
+
+    fn decide(&self, base: Option<&Value>, local: &Value, remote: &Value) -> Decision {
+        let result = three_way_merge(base, local, remote);
+
+        // Both sides already agree
+        if result.conflicts.is_empty() && &result.merged == remote {
+            return Decision::AcceptRemote;
+        }
+
+        match self.policy {
+            ConflictPolicy::LocalWins => Decision::Upload(local.clone(), ConflictOutcome::LocalApplied),
+            ConflictPolicy::RemoteWins => Decision::AcceptRemote,
+            ConflictPolicy::Merge if result.conflicts.is_empty() => Decision::Upload(result.merged, ConflictOutcome::Merged),
+            ConflictPolicy::Merge | ConflictPolicy::Manual => Decision::Record(result.conflicts),
+        }
+    }
+
+    /// The object was deleted in Zotero while it was modified locally
+    #[allow(clippy::too_many_arguments)]
+    async fn resolve_remote_deleted(
+        &self,
+        entity_type: &str,
+        table: &str,
+        key: &str,
+        library_id: i64,
+        library_type: LibraryType,
+        base: Option<Value>,
+        local_data: Value,
+    ) -> Result<ConflictOutcome> {
+        if self.policy == ConflictPolicy::RemoteWins {
+            let query = format!(
+                "DELETE FROM {}.{} WHERE key = $1 AND library_id = $2 AND library_type = $3",
+                self.schema, table
+            );
+            sqlx::query(&query)
+                .bind(key)
+                .bind(library_id)
+                .bind(library_type)
+                .execute(self.db)
+                .await?;
+            info!("{} {} was deleted remotely, removed local copy", entity_type, key);
+            return Ok(ConflictOutcome::RemoteApplied);
+        }
+
+        self.record(entity_type, table, key, library_id, library_type, base, local_data, None, None, &[]).await?;
+        Ok(ConflictOutcome::Recorded)
+    }
+
+    /// Persist uploaded data as both the current row and the new snapshot
+    async fn store_data(&self, table: &str, key: &str, library_id: i64, library_type: LibraryType, data: &Value) -> Result<()> {
+        let query = format!(
+            "UPDATE {}.{} SET data = $1, synced_data = $1 WHERE key = $2 AND library_id = $3 AND library_type = $4",
+            self.schema, table
+        );
+
+        sqlx::query(&query)
+            .bind(data)
+            .bind(key)
+            .bind(library_id)
+            .bind(library_type)
+            .execute(self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn accept_remote(&self, table: &str, key: &str, library_id: i64, library_type: LibraryType, data: &Value, version: i64) -> Result<()> {
+        let query = format!(
+            r#"
+            UPDATE {}.{} SET data = $1, synced_data = $1, version = $2, sync = 'synced'
+            WHERE key = $3 AND library_id = $4 AND library_type = $5
+            "#,
+            self.schema, table
+        );
+
+        sqlx::query(&query)
+            .bind(data)
+            .bind(version)
+            .bind(key)
+            .bind(library_id)
+            .bind(library_type)
+            .execute(self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Store an unresolved conflict and park the row until it is reviewed
+    #[allow(clippy::too_many_arguments)]
+    async fn record(
+        &self,
+        entity_type: &str,
+        table: &str,
+        key: &str,
+        library_id: i64,
+        library_type: LibraryType,
+        base: Option<Value>,
+        local_data: Value,
+        remote_data: Option<Value>,
+        remote_version: Option<i64>,
+        conflicts: &[FieldConflict],
+    ) -> Result<()> {
+        let query = format!(
+            r#"
+            INSERT INTO {}.sync_conflicts
+                (entity_type, entity_key, library_id, library_type, policy, base_data, local_data, remote_data, remote_version, fields)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT (entity_type, entity_key, library_id, library_type) WHERE resolved_at IS NULL
+            DO UPDATE SET
+                policy = EXCLUDED.policy,
+                base_data = EXCLUDED.base_data,
+                local_data = EXCLUDED.local_data,
+                remote_data = EXCLUDED.remote_data,
+                remote_version = EXCLUDED.remote_version,
+                fields = EXCLUDED.fields,
+                created_at = NOW()
+            "#,
+            self.schema
+        );
+
+        sqlx::query(&query)
+            .bind(entity_type)
+            .bind(key)
+            .bind(library_id)
+            .bind(library_type)
+            .bind(self.policy)
+            .bind(&base)
+            .bind(&local_data)
+            .bind(&remote_data)
+            .bind(remote_version)
+            .bind(serde_json::to_value(conflicts)?)
+            .execute(self.db)
+            .await?;
+
+        let query = format!(
+            "UPDATE {}.{} SET sync = 'incomplete' WHERE key = $1 AND library_id = $2 AND library_type = $3",
+            self.schema, table
+        );
+        sqlx::query(&query)
+            .bind(key)
+            .bind(library_id)
+            .bind(library_type)
+            .execute(self.db)
+            .await?;
+
+        warn!(
+            "Recorded sync conflict for {} {} in {} library {} ({} conflicting fields)",
+            entity_type, key, library_type, library_id, conflicts.len()
+        );
+        Ok(())
+    }
+}
+
+/// An unresolved conflict stored in `sync_conflicts`
+#[derive(Debug, Clone)]
+pub struct RecordedConflict {
+    pub id: i64,
+    /// `item`, `collection` or `file`
+    pub entity_type: String,
+    pub entity_key: String,
+    pub library_id: i64,
+    pub library_type: LibraryType,
+    /// Fields changed on both sides; empty for files and deletions
+    pub fields: Vec<String>,
+    /// The object was deleted in Zotero
+    pub remote_deleted: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How a recorded conflict is settled
+#[derive(Debug, Clone)]
+pub enum Resolution {
+    /// Keep the local data or file and send it to Zotero on the next sync
+    Local,
+    /// Replace the local row, or file, with the one in Zotero
+    Remote,
+    /// Send this data to Zotero instead, e.g. a hand-merged copy of both
+    /// sides; not possible for files
+    Edited(Value),
+}
+
+/// Review and manual resolution of the conflicts in `sync_conflicts`
+pub struct SyncConflicts {
+    db: PgPool,
+    schema: String,
+}
+
+impl SyncConflicts {
+    pub fn new(db: PgPool, schema: String) -> Self {
+        Self { db, schema }
+    }
+
+    /// Unresolved conflicts, oldest first, optionally of one library only
+    pub async fn list(&self, library: Option<(i64, LibraryType)>) -> Result<Vec<RecordedConflict>> {
+        let query = format!(
+            r#"
+            SELECT id, entity_type, entity_key, library_id, library_type, fields, remote_data IS NULL AS remote_deleted, created_at
+            FROM {}.sync_conflicts
+            WHERE resolved_at IS NULL
+              AND ($1::BIGINT IS NULL OR (library_id = $1 AND library_type = $2))
+            ORDER BY created_at, id
+            "#,
+            self.schema
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(library.map(|(id, _)| id))
+            .bind(library.map(|(_, library_type)| library_type))
+            .fetch_all(&self.db)
+            .await?;
+
+        Ok(rows.iter().map(|row| {
+            let fields: Option<Value> = row.get("fields");
+            RecordedConflict {
+                id: row.get("id"),
+                entity_type: row.get("entity_type"),
+                entity_key: row.get("entity_key"),
+                library_id: row.get("library_id"),
+                library_type: row.get("library_type"),
+                fields: fields.as_ref()
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|f| f.get("field").and_then(Value::as_str).map(str::to_string))
+                    .collect(),
+                remote_deleted: row.get("remote_deleted"),
+                created_at: row.get("created_at"),
+            }
+        }).collect())
+    }
+
+    /// Settle conflict `id` and release its row for the next sync.
+    ///
+    /// Local and edited data is marked `modified` on top of the remote
+    /// version, so the next outgoing sync uploads it; remote data replaces
+    /// the row as if it had just been downloaded. A remote file is fetched by
+    /// the next sync's retry of failed downloads.
+    pub async fn resolve(&self, id: i64, resolution: Resolution) -> Result<()> {
+        let mut tx = self.db.begin().await?;
+
+        // Applying a resolution is a local edit, whatever the pool is tagged with
+        sqlx::query("SELECT set_config('postero.change_source', $1, true)")
+            .bind(ChangeSource::Local.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+        let query = format!(
+            r#"
+            SELECT entity_type, entity_key, library_id, library_type, local_data, remote_data, remote_version
+            FROM {}.sync_conflicts
+            WHERE id = $1 AND resolved_at IS NULL
+            FOR UPDATE
+            "#,
+            self.schema
+        );
+        let row = sqlx::query(&query)
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("No unresolved sync conflict {}", id)))?;
+
+        let entity_type: String = row.get("entity_type");
+        let key: String = row.get("entity_key");
+        let library_id: i64 = row.get("library_id");
+        let library_type: LibraryType = row.get("library_type");
+        let local_data: Option<Value> = row.get("local_data");
+        let remote_data: Option<Value> = row.get("remote_data");
+        let remote_version: Option<i64> = row.get("remote_version");
+
+        let table = match entity_type.as_str() {
+            "item" | "file" => "items",
+            "collection" => "collections",
+            other => return Err(Error::InvalidData(format!("Unknown conflict entity type {}", other))),
+        };
+
+        if entity_type == "file" {
+            let remote_md5 = remote_data.as_ref().and_then(|d| d.get("md5")).and_then(Value::as_str).map(str::to_string);
+            let take_remote = match resolution {
+                Resolution::Local => false,
+                Resolution::Remote => true,
+                Resolution::Edited(_) => {
+                    return Err(Error::Validation("File conflicts are resolved by taking the local or the remote file".to_string()));
+                }
+            };
+
+            // Zotero's file becomes the one the local file replaces, or the
+            // one stored locally
+            let query = format!(
+                r#"
+                UPDATE {}.items SET
+                    data = CASE WHEN $1::TEXT IS NULL THEN data - 'md5' ELSE jsonb_set(data, '{{md5}}', to_jsonb($1::TEXT)) END,
+                    md5 = CASE WHEN $2 THEN $1 ELSE md5 END,
+                    sync = 'synced'
+                WHERE key = $3 AND library_id = $4 AND library_type = $5
+                "#,
+                self.schema
+            );
+            sqlx::query(&query)
+                .bind(&remote_md5)
+                .bind(take_remote)
+                .bind(&key)
+                .bind(library_id)
+                .bind(library_type)
+                .execute(&mut *tx)
+                .await?;
+
+            if take_remote {
+                let query = format!(
+                    r#"
+                    UPDATE {}.attachments SET status = $1, last_error = 'Conflict resolved in favour of the file in Zotero'
+                    WHERE item_key = $2 AND library_id = $3 AND library_type = $4
+                    "#,
+                    self.schema
+                );
+                let result = sqlx::query(&query)
+                    .bind(TransferStatus::DownloadFailed.as_str())
+                    .bind(&key)
+                    .bind(library_id)
+                    .bind(library_type)
+                    .execute(&mut *tx)
+                    .await?;
+                if result.rows_affected() == 0 {
+                    warn!("Attachment {} has no transfer state; its file is downloaded when it next changes in Zotero", key);
+                }
+            }
+        } else {
+            let data = match resolution {
+                Resolution::Remote => None,
+                Resolution::Local => Some(local_data.ok_or_else(|| Error::InvalidData(format!("Sync conflict {} has no local data", id)))?),
+                Resolution::Edited(data) if data.is_object() => Some(data),
+                Resolution::Edited(_) => return Err(Error::Validation("Edited data must be a JSON object".to_string())),
+            };
+
+            match (data, remote_data) {
+                (None, Some(remote)) => {
+                    let query = format!(
+                        r#"
+                        UPDATE {}.{} SET data = $1, synced_data = $1, version = $2, sync = 'synced'
+                        WHERE key = $3 AND library_id = $4 AND library_type = $5
+                        "#,
+                        self.schema, table
+                    );
+                    sqlx::query(&query)
+                        .bind(&remote)
+                        .bind(remote_version.unwrap_or_default())
+                        .bind(&key)
+                        .bind(library_id)
+                        .bind(library_type)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                (None, None) => {
+                    let query = format!(
+                        "DELETE FROM {}.{} WHERE key = $1 AND library_id = $2 AND library_type = $3",
+                        self.schema, table
+                    );
+                    sqlx::query(&query)
+                        .bind(&key)
+                        .bind(library_id)
+                        .bind(library_type)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                // Uploaded over the remote version, or created again if
+                // Zotero deleted the object
+                (Some(mut data), remote) => {
+                    let version = remote_version.filter(|_| remote.is_some()).unwrap_or(0);
+                    let sync = if remote.is_some() { SyncStatus::Modified } else { SyncStatus::New };
+                    if let Some(object) = data.as_object_mut() {
+                        object.insert("key".to_string(), Value::from(key.as_str()));
+                        object.insert("version".to_string(), Value::from(version));
+                    }
+
+                    let query = format!(
+                        r#"
+                        UPDATE {}.{} SET data = $1, synced_data = $2, version = $3, sync = $4
+                        WHERE key = $5 AND library_id = $6 AND library_type = $7
+                        "#,
+                        self.schema, table
+                    );
+                    sqlx::query(&query)
+                        .bind(&data)
+                        .bind(&remote)
+                        .bind(version)
+                        .bind(sync)
+                        .bind(&key)
+                        .bind(library_id)
+                        .bind(library_type)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+        }
+
+        let query = format!("UPDATE {}.sync_conflicts SET resolved_at = NOW() WHERE id = $1", self.schema);
+        sqlx::query(&query)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        info!("Resolved sync conflict {} for {} {} in {} library {}", id, entity_type, key, library_type, library_id);
+        Ok(())
+    }
+}
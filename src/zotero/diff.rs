@@ -0,0 +1,57 @@
+//! Structured field diffs between an object's data and its last-synced snapshot.
+
+use std::collections::BTreeSet;
+use serde::Serialize;
+use serde_json::Value;
+
+/// How a single top-level field differs from the snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A top-level field whose value differs from the snapshot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub kind: ChangeKind,
+    /// Value in the snapshot, `None` if the field was added
+    pub old: Option<Value>,
+    /// Current value, `None` if the field was removed
+    pub new: Option<Value>,
+}
+
+/// Compare two JSON objects field by field, ordered by field name.
+///
+/// A missing snapshot (never synced) reports every field as added.
+pub fn diff_fields(old: Option<&Value>, new: &Value) -> Vec<FieldChange> {
+    let old = old.and_then(Value::as_object);
+    let new = new.as_object();
+
+    let fields: BTreeSet<&String> = old.into_iter().flat_map(|o| o.keys())
+        .chain(new.into_iter().flat_map(|n| n.keys()))
+        .collect();
+
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let before = old.and_then(|o| o.get(field));
+            let after = new.and_then(|n| n.get(field));
+            let kind = match (before, after) {
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (Some(b), Some(a)) if b != a => ChangeKind::Modified,
+                _ => return None,
+            };
+            Some(FieldChange {
+                field: field.clone(),
+                kind,
+                old: before.cloned(),
+                new: after.cloned(),
+            })
+        })
+        .collect()
+}
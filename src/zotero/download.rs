@@ -0,0 +1,138 @@
+//! Presigned download URLs for attachment files, for clients of the API.
+//!
+//! Clients authenticate with the token they use for PostgREST. Access
+//! follows the row-level security policies on `items`: the token's
+//! `library_id` and `library_type` claims select the one library it may
+//! read, and the anonymous role sees no deleted items.
+
+use std::sync::Arc;
+use std::time::Duration;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Deserializer};
+use sqlx::{PgPool, Row};
+
+use crate::{Error, Result};
+use crate::filesystem::{FileSystem, FilePresignOptions};
+use super::{AttachmentLayout, Item, ItemData, LibraryType, SyncStatus};
+
+/// Role of tokens without a `role` claim, as `PGRST_DB_ANON_ROLE`
+pub const ANON_ROLE: &str = "api_anon";
+/// Role with access to all items of its library
+pub const USER_ROLE: &str = "api_user";
+
+/// Claims of an API token
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiClaims {
+    pub role: Option<String>,
+    #[serde(deserialize_with = "library_id")]
+    pub library_id: i64,
+    pub library_type: LibraryType,
+}
+
+/// Library IDs may be given as numbers or strings, as PostgREST passes
+/// claims on as text either way
+fn library_id<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<i64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Number(i64),
+        Text(String),
+    }
+
+    match Id::deserialize(deserializer)? {
+        Id::Number(id) => Ok(id),
+        Id::Text(id) => id.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+impl ApiClaims {
+    /// Check an HS256 token signed with `secret`; `exp` is enforced if set
+    pub fn verify(token: &str, secret: &str) -> Result<Self> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.required_spec_claims.clear();
+        validation.validate_aud = false;
+
+        jsonwebtoken::decode::<Self>(token, &DecodingKey::from_secret(secret.as_bytes()), &validation)
+            .map(|data| data.claims)
+            .map_err(|e| Error::Validation(format!("Invalid token: {}", e)))
+    }
+
+    /// Database role the token acts as
+    pub fn role(&self) -> &str {
+        self.role.as_deref().unwrap_or(ANON_ROLE)
+    }
+}
+
+pub struct AttachmentDownloads {
+    db: PgPool,
+    schema: String,
+    filesystem: Arc<dyn FileSystem>,
+    layout: AttachmentLayout,
+    expires_in: Duration,
+}
+
+impl AttachmentDownloads {
+    pub fn new(db: PgPool, schema: String, filesystem: Arc<dyn FileSystem>, layout: AttachmentLayout, expires_in: Duration) -> Self {
+        Self { db, schema, filesystem, layout, expires_in }
+    }
+
+    /// Presigned URL of an attachment's stored file.
+    ///
+    /// Attachments the claims give no access to are reported as not found,
+    /// like rows hidden by row-level security.
+    pub async fn presigned_url(&self, claims: &ApiClaims, item_key: &str) -> Result<String> {
+        let include_deleted = match claims.role() {
+            USER_ROLE => true,
+            ANON_ROLE => false,
+            role => return Err(Error::Validation(format!("Role {} has no access to items", role))),
+        };
+
+        let query = format!(
+            r#"
+            SELECT key, version, library_id, library_type, data, trashed, deleted, md5
+            FROM {}.items
+            WHERE key = $1 AND library_id = $2 AND library_type = $3
+              AND data->>'itemType' = 'attachment'
+              AND ($4 OR NOT deleted)
+            "#,
+            self.schema
+        );
+
+        let row = sqlx::query(&query)
+            .bind(item_key)
+            .bind(claims.library_id)
+            .bind(claims.library_type)
+            .bind(include_deleted)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("Attachment {}", item_key)))?;
+
+        let data_value: serde_json::Value = row.get("data");
+        let item = Item {
+            key: row.get("key"),
+            version: row.get("version"),
+            library_id: row.get("library_id"),
+            library_type: row.get("library_type"),
+            data: serde_json::from_value::<ItemData>(data_value)?,
+            meta: None,
+            trashed: row.get("trashed"),
+            deleted: row.get("deleted"),
+            sync_status: SyncStatus::Synced,
+            md5: row.get("md5"),
+            synced_data: None,
+            db: None,
+            db_schema: None,
+        };
+
+        let (folder, name) = item.file_location(&self.layout);
+        if !self.filesystem.file_exists(&folder, &name).await? {
+            return Err(Error::NotFound(format!("No stored file for attachment {}", item_key)));
+        }
+
+        let download_name = item.data.extra_fields.get("filename").and_then(|v| v.as_str()).map(str::to_string);
+        self.filesystem.file_presigned_url(&folder, &name, FilePresignOptions {
+            expires_in: self.expires_in,
+            download_name,
+        }).await
+    }
+}
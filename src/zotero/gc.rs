@@ -0,0 +1,147 @@
+//! Garbage collection of stored attachment files.
+//!
+//! Deleting items (through `sync_deleted`, `clear_local` or by hand) removes
+//! their rows but leaves their files in storage. The collector compares what
+//! is stored in the attachment bucket with the attachment items in `items`
+//! and removes, or in a dry run only reports, files without one. With
+//! content-addressed storage, unreferenced blobs are released first.
+
+use std::collections::{BTreeSet, HashSet};
+use std::ops::Bound;
+use std::sync::Arc;
+use sqlx::{PgPool, Row};
+use tracing::info;
+
+use crate::Result;
+use crate::filesystem::{FileSystem, FileInfo};
+use super::LibraryType;
+use super::blob;
+use super::layout::{legacy_location, AttachmentLayout};
+
+/// Outcome of a garbage collection run
+#[derive(Debug, Default)]
+pub struct GcReport {
+    /// Number of stored files examined
+    pub scanned: usize,
+    /// Files without a matching attachment item
+    pub orphans: Vec<FileInfo>,
+    /// Number of files deleted; zero in a dry run
+    pub removed: u64,
+}
+
+impl GcReport {
+    /// Total size of the orphaned files
+    pub fn orphaned_bytes(&self) -> u64 {
+        self.orphans.iter().map(|f| f.size).sum()
+    }
+}
+
+/// Whether `name` lies below one of the snapshot prefixes. A prefix of
+/// `name` sorts at or before it, and as prefixes do not nest, only the last
+/// one that does can match.
+fn in_snapshot(prefixes: &BTreeSet<String>, name: &str) -> bool {
+    prefixes.range::<str, _>((Bound::Unbounded, Bound::Included(name))).next_back().is_some_and(|prefix| name.starts_with(prefix.as_str()))
+}
+
+pub struct AttachmentGc {
+    db: PgPool,
+    schema: String,
+    filesystem: Arc<dyn FileSystem>,
+    layout: AttachmentLayout,
+}
+
+impl AttachmentGc {
+    pub fn new(db: PgPool, schema: String, filesystem: Arc<dyn FileSystem>, layout: AttachmentLayout) -> Self {
+        Self { db, schema, filesystem, layout }
+    }
+
+    /// Find orphaned files and, unless `dry_run`, delete them
+    pub async fn run(&self, dry_run: bool) -> Result<GcReport> {
+        let bucket = self.layout.bucket();
+        if self.layout.is_content_addressed() && !dry_run {
+            let released = blob::release_unreferenced(&self.db, &self.schema, self.filesystem.as_ref(), &self.layout).await?;
+            info!("Released {} unreferenced blobs", released);
+        }
+
+        let stored = self.filesystem.file_list(bucket, "").await?;
+        let (expected, snapshot_prefixes) = self.expected_files().await?;
+
+        let mut report = GcReport {
+            scanned: stored.len(),
+            ..Default::default()
+        };
+
+        for file in stored {
+            if expected.contains(&file.name) || in_snapshot(&snapshot_prefixes, &file.name) {
+                continue;
+            }
+
+            if dry_run {
+                info!("Found orphaned attachment file {}/{} ({} bytes)", bucket, file.name, file.size);
+            } else {
+                info!("Removing orphaned attachment file {}/{} ({} bytes)", bucket, file.name, file.size);
+                self.filesystem.file_delete(bucket, &file.name).await?;
+                report.removed += 1;
+            }
+            report.orphans.push(file);
+        }
+
+        Ok(report)
+    }
+
+    /// Object keys of all attachment files in the bucket that belong to an
+    /// item, and the prefixes of unpacked snapshots
+    async fn expected_files(&self) -> Result<(HashSet<String>, BTreeSet<String>)> {
+        let query = format!(
+            r#"
+            SELECT key, library_id, library_type, data->>'filename' AS filename,
+                   data->>'linkMode' AS link_mode, md5, data->>'md5' AS remote_md5
+            FROM {}.items
+            WHERE data->>'itemType' = 'attachment'
+            "#,
+            self.schema
+        );
+
+        let rows = sqlx::query(&query).fetch_all(&self.db).await?;
+
+        let mut expected = HashSet::with_capacity(rows.len());
+        let mut snapshot_prefixes = BTreeSet::new();
+        for row in rows {
+            let key: String = row.get("key");
+            let library_id: i64 = row.get("library_id");
+            let library_type: LibraryType = row.get("library_type");
+            let filename: Option<String> = row.get("filename");
+            let link_mode: Option<String> = row.get("link_mode");
+
+            if link_mode.as_deref() == Some("imported_url") {
+                snapshot_prefixes.insert(self.layout.snapshot_prefix(library_type, library_id, &key));
+                continue;
+            }
+
+            expected.insert(self.layout.object_key(library_type, library_id, &key, filename.as_deref()));
+
+            // Blobs written for an item but not linked yet
+            if self.layout.is_content_addressed() {
+                for md5 in [row.get::<Option<String>, _>("md5"), row.get("remote_md5")].into_iter().flatten() {
+                    if blob::is_blob_address(&md5) {
+                        expected.insert(self.layout.blob_location(&md5).1);
+                    }
+                }
+            }
+
+            // Files not yet moved by `migrate-layout` are not orphans
+            let (legacy_folder, legacy_name) = legacy_location(&key, filename.as_deref());
+            if legacy_folder == self.layout.bucket() {
+                expected.insert(legacy_name);
+            }
+        }
+
+        if self.layout.is_content_addressed() {
+            for md5 in blob::referenced(&self.db, &self.schema).await? {
+                expected.insert(self.layout.blob_location(&md5).1);
+            }
+        }
+
+        Ok((expected, snapshot_prefixes))
+    }
+}
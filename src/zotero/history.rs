@@ -0,0 +1,205 @@
+//! Change history of items and collections and point-in-time restore.
+//!
+//! History rows are written by the `record_change_history` trigger. Each
+//! process tags its database connections with a [`ChangeSource`] so the
+//! history shows whether a change was downloaded from Zotero, made locally
+//! or applied by the sync worker.
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{PgPool, Row};
+use sqlx::postgres::PgPoolOptions;
+
+use crate::Result;
+use super::LibraryType;
+
+/// Origin of a change recorded in `change_history`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeSource {
+    /// Downloaded from Zotero by the sync tool
+    Cloud,
+    /// Edited directly in the database (API, psql, restore)
+    Local,
+    /// Applied by the event-driven sync worker
+    Worker,
+}
+
+impl ChangeSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeSource::Cloud => "cloud",
+            ChangeSource::Local => "local",
+            ChangeSource::Worker => "worker",
+        }
+    }
+
+    /// Connect a pool whose writes are recorded with this source
+    pub async fn connect(self, dsn: &str) -> Result<PgPool> {
+        let source = self.as_str();
+        let pool = PgPoolOptions::new()
+            .after_connect(move |conn, _meta| Box::pin(async move {
+                sqlx::query("SELECT set_config('postero.change_source', $1, false)")
+                    .bind(source)
+                    .execute(conn)
+                    .await?;
+                Ok(())
+            }))
+            .connect(dsn)
+            .await?;
+        Ok(pool)
+    }
+}
+
+impl std::fmt::Display for ChangeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Point-in-time restore based on `change_history`
+pub struct History {
+    db: PgPool,
+    schema: String,
+}
+
+impl History {
+    pub fn new(db: PgPool, schema: String) -> Self {
+        Self { db, schema }
+    }
+
+    /// Restore every item and collection of a library to its state at `at`.
+    ///
+    /// Returns the number of rows changed.
+    pub async fn restore_library(&self, library_id: i64, library_type: LibraryType, at: DateTime<Utc>) -> Result<u64> {
+        self.restore(library_id, library_type, None, at).await
+    }
+
+    /// Restore a single item to its state at `at`.
+    ///
+    /// Returns false if the item has not changed since.
+    pub async fn restore_item(&self, library_id: i64, library_type: LibraryType, item_key: &str, at: DateTime<Utc>) -> Result<bool> {
+        Ok(self.restore(library_id, library_type, Some(("item", item_key)), at).await? > 0)
+    }
+
+    /// Restored rows are marked `modified` (or `new` if they no longer exist
+    /// locally) so the next outgoing sync sends them back to Zotero; objects
+    /// that did not exist at `at` are flagged deleted.
+    async fn restore(&self, library_id: i64, library_type: LibraryType, entity: Option<(&str, &str)>, at: DateTime<Utc>) -> Result<u64> {
+        let mut tx = self.db.begin().await?;
+
+        // The restore itself is a local edit, whatever the pool is tagged with
+        sqlx::query("SELECT set_config('postero.change_source', $1, true)")
+            .bind(ChangeSource::Local.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+        // State at `at` is the last change up to then or, for objects whose
+        // history starts later, what the first later change replaced
+        let query = format!(
+            r#"
+            WITH changed AS (
+                SELECT DISTINCT entity_type, entity_key
+                FROM {0}.change_history
+                WHERE library_id = $1 AND library_type = $2 AND changed_at > $3
+                  AND ($4::TEXT IS NULL OR (entity_type = $4 AND entity_key = $5))
+            )
+            SELECT c.entity_type, c.entity_key,
+                   CASE WHEN before.id IS NOT NULL THEN before.new_data ELSE after.old_data END AS data
+            FROM changed c
+            LEFT JOIN LATERAL (
+                SELECT h.id, h.new_data FROM {0}.change_history h
+                WHERE h.library_id = $1 AND h.library_type = $2
+                  AND h.entity_type = c.entity_type AND h.entity_key = c.entity_key
+                  AND h.changed_at <= $3
+                ORDER BY h.changed_at DESC, h.id DESC
+                LIMIT 1
+            ) before ON true
+            LEFT JOIN LATERAL (
+                SELECT h.old_data FROM {0}.change_history h
+                WHERE h.library_id = $1 AND h.library_type = $2
+                  AND h.entity_type = c.entity_type AND h.entity_key = c.entity_key
+                  AND h.changed_at > $3
+                ORDER BY h.changed_at, h.id
+                LIMIT 1
+            ) after ON true
+            ORDER BY c.entity_type, c.entity_key
+            "#,
+            self.schema
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(library_id)
+            .bind(library_type)
+            .bind(at)
+            .bind(entity.map(|(entity_type, _)| entity_type))
+            .bind(entity.map(|(_, key)| key))
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let mut restored = 0u64;
+
+        for row in rows {
+            let entity_type: String = row.get("entity_type");
+            let key: String = row.get("entity_key");
+            let data: Option<Value> = row.get("data");
+
+            let table = match entity_type.as_str() {
+                "item" => "items",
+                "collection" => "collections",
+                other => {
+                    tracing::warn!("Skipping history of unknown entity type {}", other);
+                    continue;
+                }
+            };
+
+            let result = match data {
+                // Keep the row's current version so the upload is not rejected as stale
+                Some(data) => {
+                    let query = format!(
+                        r#"
+                        INSERT INTO {0}.{1} (key, version, library_id, library_type, data, deleted, sync)
+                        VALUES ($1, 0, $2, $3, jsonb_set($4, '{{version}}', '0'), false, 'new')
+                        ON CONFLICT (key, library_id, library_type) DO UPDATE SET
+                            data = jsonb_set(EXCLUDED.data, '{{version}}', to_jsonb({0}.{1}.version)),
+                            deleted = false,
+                            sync = 'modified'
+                        WHERE {0}.{1}.data IS DISTINCT FROM jsonb_set(EXCLUDED.data, '{{version}}', to_jsonb({0}.{1}.version))
+                           OR {0}.{1}.deleted
+                        "#,
+                        self.schema, table
+                    );
+                    sqlx::query(&query)
+                        .bind(&key)
+                        .bind(library_id)
+                        .bind(library_type)
+                        .bind(&data)
+                        .execute(&mut *tx)
+                        .await?
+                }
+                None => {
+                    let query = format!(
+                        r#"
+                        UPDATE {}.{} SET deleted = true, sync = 'modified'
+                        WHERE key = $1 AND library_id = $2 AND library_type = $3 AND NOT deleted
+                        "#,
+                        self.schema, table
+                    );
+                    sqlx::query(&query)
+                        .bind(&key)
+                        .bind(library_id)
+                        .bind(library_type)
+                        .execute(&mut *tx)
+                        .await?
+                }
+            };
+
+            if result.rows_affected() > 0 {
+                tracing::info!("Restored {} {} to its state at {}", entity_type, key, at);
+                restored += result.rows_affected();
+            }
+        }
+
+        tx.commit().await?;
+        Ok(restored)
+    }
+}
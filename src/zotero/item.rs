@@ -1,8 +1,15 @@
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use crate::{Result, Error};
-use super::{ItemData, SyncStatus, LibraryType};
-use crate::filesystem::{FileSystem, FileGetOptions, FilePutOptions};
+use super::{ItemData, SyncStatus, LibraryType, FieldChange};
+use std::collections::HashMap;
+use crate::filesystem::{FileSystem, FileGetOptions, FilePutOptions, FileStatOptions, Md5Reader, MD5_METADATA};
+use crate::filesystem::stream::md5_of;
+use super::attachment::{AttachmentState, TransferStatus, SNIFF_LEN, KEY_METADATA, LIBRARY_METADATA, MTIME_METADATA, guess_content_type};
+use super::blob;
+use super::snapshot;
+use super::webdav::{self, ZoteroWebDav, WebDavProperties};
+use tokio::io::AsyncReadExt;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Item {
@@ -15,7 +22,12 @@ pub struct Item {
     pub trashed: bool,
     pub deleted: bool,
     pub sync_status: SyncStatus,
+    /// MD5 of the stored attachment file; set it when replacing the file so
+    /// the next outgoing sync uploads it
     pub md5: Option<String>,
+    /// `data` as it was when last in agreement with Zotero
+    #[serde(default)]
+    pub synced_data: Option<serde_json::Value>,
     
     #[serde(skip)]
     pub db: Option<PgPool>,
@@ -54,13 +66,19 @@ impl Item {
         self.db_schema = Some(db_schema);
     }
 
+    /// Fields of `data` that differ from the last-synced snapshot
+    pub fn diff(&self) -> Result<Vec<FieldChange>> {
+        let data = serde_json::to_value(&self.data)?;
+        Ok(super::diff::diff_fields(self.synced_data.as_ref(), &data))
+    }
+
     pub async fn update_local(&self) -> Result<()> {
         let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
         let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
 
         let data_json = serde_json::to_string(&self.data)?;
         let meta_json = self.meta.as_ref()
-            .map(|m| serde_json::to_string(m))
+            .map(serde_json::to_string)
             .transpose()?;
 
         let sync_status_str = match self.sync_status {
@@ -72,8 +90,8 @@ impl Item {
 
         let query = format!(
             r#"
-            INSERT INTO {}.items (key, version, library_id, library_type, data, meta, trashed, deleted, sync, md5)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            INSERT INTO {0}.items (key, version, library_id, library_type, data, meta, trashed, deleted, sync, md5, synced_data)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             ON CONFLICT (key, library_id, library_type) DO UPDATE SET
                 version = EXCLUDED.version,
                 data = EXCLUDED.data,
@@ -81,7 +99,8 @@ impl Item {
                 trashed = EXCLUDED.trashed,
                 deleted = EXCLUDED.deleted,
                 sync = EXCLUDED.sync,
-                md5 = EXCLUDED.md5
+                md5 = EXCLUDED.md5,
+                synced_data = COALESCE(EXCLUDED.synced_data, {0}.items.synced_data)
             "#,
             schema
         );
@@ -97,6 +116,7 @@ impl Item {
             .bind(self.deleted)
             .bind(sync_status_str)
             .bind(&self.md5)
+            .bind(&self.synced_data)
             .execute(db)
             .await?;
 
@@ -130,34 +150,10 @@ impl Item {
             SyncStatus::New | SyncStatus::Modified => {
                 // Upload item to Zotero API
                 let new_version = client.upload_item_unified(self.library_id, self.library_type, self, *library_version).await?;
-                
-                // Update local status
-                self.sync_status = SyncStatus::Synced;
-                self.version = new_version;
                 *library_version = new_version;
                 
-                // Handle file upload for imported_file attachments
-                if self.data.item_type == "attachment" && 
-                   self.data.extra_fields.get("linkMode").and_then(|v| v.as_str()) == Some("imported_file") {
-                    // TODO: File upload requires filesystem and file_path parameters
-                    // self.upload_file_cloud(client, filesystem, library_id, library_type, file_path).await?;
-                    tracing::info!("Attachment upload skipped - requires filesystem context");
-                }
-                
-                // Update local database
-                if let (Some(db), Some(schema)) = (&self.db, &self.db_schema) {
-                    let query = format!(
-                        "UPDATE {}.items SET sync = 'synced', version = $1 WHERE key = $2 AND library_id = $3 AND library_type = $4",
-                        schema
-                    );
-                    sqlx::query(&query)
-                        .bind(self.version)
-                        .bind(&self.key)
-                        .bind(self.library_id)
-                        .bind(self.library_type)
-                        .execute(db)
-                        .await?;
-                }
+                // Update local status
+                self.mark_synced(new_version).await?;
             }
             
             SyncStatus::Synced => {
@@ -174,6 +170,96 @@ impl Item {
         Ok(())
     }
 
+    /// Upload up to [`WRITE_BATCH_LIMIT`](super::client::WRITE_BATCH_LIMIT) new or
+    /// modified items of one library in a single request.
+    ///
+    /// Returns one result per item, in order. Objects Zotero rejected carry
+    /// their per-object error, e.g. 412 when the object changed remotely.
+    pub async fn update_cloud_batch(client: &super::ZoteroClient, items: &mut [Item], library_version: &mut i64) -> Result<Vec<Result<()>>> {
+        let Some(first) = items.first() else {
+            return Ok(Vec::new());
+        };
+
+        let (result, new_version) = client
+            .upload_items_unified(first.library_id, first.library_type, items, *library_version)
+            .await?;
+        *library_version = new_version;
+
+        let mut outcomes = Vec::with_capacity(items.len());
+        for (index, item) in items.iter_mut().enumerate() {
+            let index = index.to_string();
+            let outcome = if result.success.contains_key(&index) {
+                item.mark_synced(new_version).await
+            } else if result.unchanged.contains_key(&index) {
+                item.mark_synced(item.version).await
+            } else if let Some(failed) = result.failed.get(&index) {
+                Err(Error::Api {
+                    code: failed.code as u16,
+                    message: failed.message.clone(),
+                })
+            } else {
+                Err(Error::InvalidData(format!("No result for item {} in batch upload", item.key)))
+            };
+            outcomes.push(outcome);
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Delete up to [`WRITE_BATCH_LIMIT`](super::client::WRITE_BATCH_LIMIT) items
+    /// of one library in a single request and remove their local rows.
+    pub async fn delete_cloud_batch(client: &super::ZoteroClient, items: &[Item], library_version: &mut i64) -> Result<()> {
+        let Some(first) = items.first() else {
+            return Ok(());
+        };
+
+        let keys: Vec<String> = items.iter().map(|item| item.key.clone()).collect();
+        let new_version = client
+            .delete_items_unified(first.library_id, first.library_type, &keys, *library_version)
+            .await?;
+        *library_version = new_version;
+
+        if let (Some(db), Some(schema)) = (&first.db, &first.db_schema) {
+            let query = format!(
+                "DELETE FROM {}.items WHERE key = ANY($1) AND library_id = $2 AND library_type = $3",
+                schema
+            );
+            sqlx::query(&query)
+                .bind(&keys)
+                .bind(first.library_id)
+                .bind(first.library_type)
+                .execute(db)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Record that Zotero now holds this item at `version`
+    async fn mark_synced(&mut self, version: i64) -> Result<()> {
+        self.sync_status = SyncStatus::Synced;
+        self.version = version;
+        self.synced_data = Some(serde_json::to_value(&self.data)?);
+
+        if let (Some(db), Some(schema)) = (&self.db, &self.db_schema) {
+            let query = format!(
+                "UPDATE {}.items SET sync = 'synced', version = $1, synced_data = data WHERE key = $2 AND library_id = $3 AND library_type = $4",
+                schema
+            );
+            sqlx::query(&query)
+                .bind(self.version)
+                .bind(&self.key)
+                .bind(self.library_id)
+                .bind(self.library_type)
+                .execute(db)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Download the attachment file into storage and record the outcome in
+    /// the `attachments` table
     pub async fn download_attachment_cloud(
         &self,
         client: &super::ZoteroClient,
@@ -184,50 +270,128 @@ impl Item {
             .and_then(|v| v.as_str())
             .unwrap_or("");
 
-        if link_mode != "linked_file" && link_mode != "imported_file" {
+        if link_mode != "linked_file" && link_mode != "imported_file" && link_mode != "imported_url" {
             tracing::debug!("Skipping non-file attachment: {}", self.key);
             return Ok(());
         }
 
-        // Get filename from item data
-        let filename = self.data.extra_fields.get("filename")
-            .and_then(|v| v.as_str())
-            .unwrap_or("unknown");
+        // A blob released before it was linked has to be downloaded again
+        let result = match self.download_file(client, filesystem).await {
+            Ok(Some((md5, size))) => self.link_blob(client, filesystem, &md5, size).await.map(|_| Some((md5, size))),
+            other => other,
+        };
+
+        let layout = client.attachment_layout();
+        let state = match &result {
+            Ok(Some((md5, size))) => {
+                AttachmentState::new(self, layout, TransferStatus::Downloaded).with_file(md5.clone(), *size)
+            }
+            Ok(None) => AttachmentState::new(self, layout, TransferStatus::Missing),
+            Err(e) => AttachmentState::new(self, layout, TransferStatus::DownloadFailed).with_error(e),
+        };
+        if let Err(e) = state.save(client.db(), client.db_schema()).await {
+            tracing::warn!("Cannot record attachment state of {}: {}", self.key, e);
+        }
+
+        result.map(|_| ())
+    }
+
+    /// Download the file unless storage already holds it; returns its MD5
+    /// and size, or `None` if Zotero has no file
+    async fn download_file(
+        &self,
+        client: &super::ZoteroClient,
+        filesystem: &dyn FileSystem,
+    ) -> Result<Option<(String, u64)>> {
+        if self.is_snapshot() {
+            return self.download_snapshot(client, filesystem).await;
+        }
 
-        let s3_key = format!("attachments/{}/{}", self.key, filename);
+        let (folder, filename) = self.file_location(client.attachment_layout());
+        let s3_key = format!("{}/{}", folder, filename);
 
-        // Check if file already exists in S3 with correct MD5
+        // Check if the file is already stored with the correct MD5; with
+        // content-addressed storage another item may have stored it
         let cloud_md5 = self.data.extra_fields.get("md5")
             .and_then(|v| v.as_str());
 
         if let Some(expected_md5) = cloud_md5 {
-            let folder = format!("attachments/{}", self.key);
-            if let Ok(existing_data) = filesystem.file_get(&folder, filename, FileGetOptions::default()).await {
-                let actual_md5 = format!("{:x}", md5::compute(&existing_data));
-                if actual_md5 == expected_md5 {
-                    tracing::debug!("File already exists with correct MD5: {}/{}", folder, filename);
-                    return Ok(());
+            if let Ok(existing) = filesystem.file_reader(&folder, &filename, FileGetOptions::default()).await {
+                if let Ok((actual_md5, size)) = md5_of(existing).await {
+                    if actual_md5 == expected_md5 {
+                        tracing::debug!("File already exists with correct MD5: {}/{}", folder, filename);
+                        return Ok(Some((actual_md5, size)));
+                    }
                 }
             }
         }
 
+        if let Some(webdav) = client.webdav(self.library_type) {
+            return self.download_webdav(webdav, filesystem, client.attachment_layout(), &folder, &filename).await;
+        }
+
         // Get download URL from Zotero API
         let download_url = match client.get_attachment_download_url_unified(self.library_id, self.library_type, &self.key).await {
             Ok(url) => url,
             Err(Error::Api { code: 404, .. }) => {
                 tracing::warn!("Attachment file not found in Zotero: {}", self.key);
-                return Ok(());
+                return Ok(None);
             }
             Err(e) => return Err(e),
         };
 
-        // Download file data
+        // Stream the download into storage, hashing it on the way
         tracing::info!("Downloading attachment: {} -> {}", self.key, s3_key);
-        let file_data = client.download_file(&download_url).await?;
+        let (mut download, _) = client.download_file_stream(&download_url).await?;
+        // The first bytes tell the content type if Zotero has none
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        (&mut download).take(SNIFF_LEN as u64).read_to_end(&mut head).await?;
+        let options = self.file_put_options(client.attachment_layout(), &head);
+        let mut reader = Md5Reader::new(std::io::Cursor::new(head).chain(download));
+        let size = filesystem.file_put_stream(&folder, &filename, &mut reader, options).await?;
 
-        // Verify MD5 if provided
+        // Verify MD5 if provided; the file is only known to be corrupt once it
+        // is stored, so it is deleted again rather than left to be served
+        let actual_md5 = reader.hex_digest();
         if let Some(expected_md5) = cloud_md5 {
-            let actual_md5 = format!("{:x}", md5::compute(&file_data));
+            if actual_md5 != expected_md5 {
+                if let Err(e) = filesystem.file_delete(&folder, &filename).await {
+                    tracing::warn!("Cannot delete corrupt download {}: {}", s3_key, e);
+                }
+                return Err(Error::Validation(format!(
+                    "MD5 mismatch for {}: expected {}, got {}",
+                    self.key, expected_md5, actual_md5
+                )));
+            }
+        }
+
+        tracing::info!("Successfully downloaded attachment: {} ({} bytes)", self.key, size);
+        Ok(Some((actual_md5, size)))
+    }
+
+    /// Download the file from the WebDAV server Zotero syncs the library's
+    /// files through; it is verified before being stored
+    async fn download_webdav(
+        &self,
+        webdav: &ZoteroWebDav,
+        filesystem: &dyn FileSystem,
+        layout: &super::AttachmentLayout,
+        folder: &str,
+        name: &str,
+    ) -> Result<Option<(String, u64)>> {
+        let Some(archive) = webdav.download(&self.key).await? else {
+            tracing::warn!("Attachment file not found on WebDAV: {}", self.key);
+            return Ok(None);
+        };
+
+        tracing::info!("Downloading attachment from WebDAV: {} -> {}/{}", self.key, folder, name);
+        let filename = self.data.extra_fields.get("filename").and_then(|v| v.as_str()).map(str::to_string);
+        let data = tokio::task::spawn_blocking(move || webdav::extract(&archive, filename.as_deref()))
+            .await
+            .map_err(|e| Error::InvalidData(format!("Cannot unpack attachment archive: {}", e)))??;
+
+        let actual_md5 = format!("{:x}", md5::compute(&data));
+        if let Some(expected_md5) = self.data.extra_fields.get("md5").and_then(|v| v.as_str()) {
             if actual_md5 != expected_md5 {
                 return Err(Error::Validation(format!(
                     "MD5 mismatch for {}: expected {}, got {}",
@@ -236,80 +400,529 @@ impl Item {
             }
         }
 
-        // Upload to S3
-        let folder = format!("attachments/{}", self.key);
-        filesystem.file_put(&folder, filename, &file_data, FilePutOptions::default()).await?;
+        let size = data.len() as u64;
+        let options = self.file_put_options(layout, &data[..data.len().min(SNIFF_LEN)]);
+        filesystem.file_put(folder, name, &data, options).await?;
 
-        tracing::info!("Successfully downloaded attachment: {} ({} bytes)", self.key, file_data.len());
-        Ok(())
+        tracing::info!("Successfully downloaded attachment: {} ({} bytes)", self.key, size);
+        Ok(Some((actual_md5, size)))
     }
 
-    pub async fn upload_file_cloud(
+    /// Unpack the snapshot archive below the attachment's prefix unless its
+    /// main file is already stored; returns the main file's MD5 and size
+    async fn download_snapshot(
         &self,
         client: &super::ZoteroClient,
         filesystem: &dyn FileSystem,
-        file_path: &str,
+    ) -> Result<Option<(String, u64)>> {
+        let layout = client.attachment_layout();
+        let folder = layout.bucket();
+        let prefix = layout.snapshot_prefix(self.library_type, self.library_id, &self.key);
+
+        let cloud_md5 = self.data.extra_fields.get("md5").and_then(|v| v.as_str());
+        if let (Some(expected_md5), Ok(index)) = (cloud_md5, snapshot::load_index(filesystem, folder, &prefix).await) {
+            if let Some(main) = index.main_entry().filter(|main| main.md5 == expected_md5) {
+                tracing::debug!("Snapshot already unpacked with correct MD5: {}/{}", folder, prefix);
+                return Ok(Some((main.md5.clone(), main.size)));
+            }
+        }
+
+        if let Some(webdav) = client.webdav(self.library_type) {
+            let Some(archive) = webdav.download(&self.key).await? else {
+                tracing::warn!("Snapshot not found on WebDAV: {}", self.key);
+                return Ok(None);
+            };
+            tracing::info!("Downloading snapshot from WebDAV: {} -> {}/{}", self.key, folder, prefix);
+            return self.store_snapshot(filesystem, layout, folder, &prefix, archive).await;
+        }
+
+        let download_url = match client.get_attachment_download_url_unified(self.library_id, self.library_type, &self.key).await {
+            Ok(url) => url,
+            Err(Error::Api { code: 404, .. }) => {
+                tracing::warn!("Snapshot not found in Zotero: {}", self.key);
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+
+        tracing::info!("Downloading snapshot: {} -> {}/{}", self.key, folder, prefix);
+        // The archive is unpacked in memory
+        let (download, _) = client.download_file_stream(&download_url).await?;
+        let mut archive = Vec::new();
+        download.take(snapshot::MAX_ARCHIVE_SIZE + 1).read_to_end(&mut archive).await?;
+        if archive.len() as u64 > snapshot::MAX_ARCHIVE_SIZE {
+            return Err(Error::InvalidData(format!(
+                "Snapshot of {} is larger than {} bytes", self.key, snapshot::MAX_ARCHIVE_SIZE
+            )));
+        }
+        self.store_snapshot(filesystem, layout, folder, &prefix, archive).await
+    }
+
+    /// Unpack a downloaded snapshot archive and check its main file
+    async fn store_snapshot(
+        &self,
+        filesystem: &dyn FileSystem,
+        layout: &super::AttachmentLayout,
+        folder: &str,
+        prefix: &str,
+        archive: Vec<u8>,
+    ) -> Result<Option<(String, u64)>> {
+        let cloud_md5 = self.data.extra_fields.get("md5").and_then(|v| v.as_str());
+
+        let filename = self.data.extra_fields.get("filename").and_then(|v| v.as_str());
+        let options = self.file_put_options(layout, &[]);
+        let index = snapshot::store(filesystem, folder, prefix, archive, filename, options).await?;
+        let main = index.main_entry()
+            .ok_or_else(|| Error::InvalidData(format!("Snapshot of {} has no main entry", self.key)))?;
+
+        if let Some(expected_md5) = cloud_md5 {
+            if main.md5 != expected_md5 {
+                return Err(Error::Validation(format!(
+                    "MD5 mismatch for snapshot {}: expected {}, got {}",
+                    self.key, expected_md5, main.md5
+                )));
+            }
+        }
+
+        tracing::info!("Successfully unpacked snapshot: {} ({} files)", self.key, index.entries.len());
+        Ok(Some((main.md5.clone(), main.size)))
+    }
+
+    /// Whether the attachment is a web snapshot, transferred as a ZIP archive
+    pub fn is_snapshot(&self) -> bool {
+        self.data.extra_fields.get("linkMode").and_then(|v| v.as_str()) == Some("imported_url")
+    }
+
+    /// Folder and file name of the attachment file in storage.
+    ///
+    /// With content-addressed storage this is the blob of the stored file's
+    /// MD5, or of the MD5 Zotero reported if none is stored yet. For a
+    /// snapshot it is the main file below the snapshot's prefix.
+    pub fn file_location(&self, layout: &super::AttachmentLayout) -> (String, String) {
+        if self.is_snapshot() {
+            let filename = self.data.extra_fields.get("filename").and_then(|v| v.as_str()).unwrap_or("unknown");
+            let prefix = layout.snapshot_prefix(self.library_type, self.library_id, &self.key);
+            return (layout.bucket().to_string(), format!("{}{}", prefix, filename));
+        }
+
+        if layout.is_content_addressed() {
+            let md5 = self.md5.as_deref()
+                .or_else(|| self.data.extra_fields.get("md5").and_then(|v| v.as_str()))
+                .filter(|md5| blob::is_blob_address(md5));
+            if let Some(md5) = md5 {
+                return layout.blob_location(md5);
+            }
+        }
+
+        let filename = self.data.extra_fields.get("filename").and_then(|v| v.as_str());
+        layout.location(self.library_type, self.library_id, &self.key, filename)
+    }
+
+    /// Storage options for the attachment file: its `contentType`, or one
+    /// guessed from `head`, the first bytes of the file, and the item's key,
+    /// library, MD5 and mtime as metadata.
+    ///
+    /// A content-addressed blob may be shared by several items and only
+    /// records its MD5.
+    pub fn file_put_options(&self, layout: &super::AttachmentLayout, head: &[u8]) -> FilePutOptions {
+        let field = |name: &str| self.data.extra_fields.get(name);
+        let filename = field("filename").and_then(|v| v.as_str()).unwrap_or("");
+        let content_type = field("contentType")
+            .and_then(|v| v.as_str())
+            .filter(|content_type| !content_type.is_empty())
+            .or_else(|| guess_content_type(filename, head))
+            .map(str::to_string);
+
+        let mut metadata = HashMap::new();
+        if let Some(md5) = field("md5").and_then(|v| v.as_str()) {
+            metadata.insert(MD5_METADATA.to_string(), md5.to_string());
+        }
+        if !layout.is_content_addressed() || self.is_snapshot() {
+            metadata.insert(KEY_METADATA.to_string(), self.key.clone());
+            metadata.insert(LIBRARY_METADATA.to_string(), format!("{}/{}", self.library_type, self.library_id));
+            if let Some(mtime) = field("mtime").and_then(|v| v.as_i64()) {
+                metadata.insert(MTIME_METADATA.to_string(), mtime.to_string());
+            }
+        }
+
+        FilePutOptions { content_type, metadata }
+    }
+
+    /// Record which blob holds the file, in content-addressed storage;
+    /// snapshots are always stored per item
+    async fn link_blob(&self, client: &super::ZoteroClient, filesystem: &dyn FileSystem, md5: &str, size: u64) -> Result<()> {
+        let layout = client.attachment_layout();
+        if !layout.is_content_addressed() || self.is_snapshot() || !blob::is_blob_address(md5) {
+            return Ok(());
+        }
+        blob::link(client.db(), client.db_schema(), filesystem, layout, &self.key, self.library_id, self.library_type, md5, size).await
+            .inspect_err(|e| tracing::warn!("Cannot link attachment {} to blob {}: {}", self.key, md5, e))
+    }
+
+    /// Whether the stored file differs from the one Zotero holds.
+    ///
+    /// True for `imported_file` and `imported_url` attachments Zotero has no
+    /// file for yet, and for those whose `md5` no longer matches the hash in
+    /// `data`.
+    pub fn needs_file_upload(&self) -> bool {
+        let link_mode = self.data.extra_fields.get("linkMode").and_then(|v| v.as_str());
+        if self.data.item_type != "attachment" || !matches!(link_mode, Some("imported_file") | Some("imported_url")) {
+            return false;
+        }
+
+        match self.data.extra_fields.get("md5").and_then(|v| v.as_str()) {
+            None => true,
+            Some(remote_md5) => self.md5.as_deref().is_some_and(|md5| md5 != remote_md5),
+        }
+    }
+
+    /// Upload the stored attachment file to Zotero.
+    ///
+    /// Fails with a 412 [`Error::Api`] if the file changed in Zotero since
+    /// the hash recorded in `data`. On success `data` carries the new hash
+    /// and mtime, and `library_version` is advanced.
+    pub async fn upload_file_cloud(
+        &mut self,
+        client: &super::ZoteroClient,
+        filesystem: &dyn FileSystem,
+        library_version: &mut i64,
     ) -> Result<()> {
         // Only process attachment items
         if self.data.item_type != "attachment" {
             return Err(Error::Validation("Item is not an attachment".to_string()));
         }
 
-        // Parse file path to extract folder and filename
-        let path_parts: Vec<&str> = file_path.split('/').collect();
-        if path_parts.len() < 2 {
-            return Err(Error::Validation("Invalid file path format".to_string()));
+        let result = self.upload_file(client, filesystem, library_version).await;
+
+        let layout = client.attachment_layout();
+        let state = match &result {
+            Ok(Some((md5, size))) => {
+                // The file reached Zotero either way, so a failed link is
+                // only logged
+                let _ = self.link_blob(client, filesystem, md5, *size).await;
+                AttachmentState::new(self, layout, TransferStatus::Uploaded).with_file(md5.clone(), *size)
+            }
+            Ok(None) => return Ok(()),
+            Err(e) => AttachmentState::new(self, layout, TransferStatus::UploadFailed).with_error(e),
+        };
+        if let Err(e) = state.save(client.db(), client.db_schema()).await {
+            tracing::warn!("Cannot record attachment state of {}: {}", self.key, e);
         }
-        
-        let folder = path_parts[..path_parts.len()-1].join("/");
-        let filename = path_parts[path_parts.len()-1];
 
-        // Read file from S3
-        let file_data = filesystem.file_get(&folder, filename, FileGetOptions::default()).await?;
-        
-        // Calculate MD5
-        let md5_hash = format!("{:x}", md5::compute(&file_data));
-        
-        // Get file modification time (use current time as fallback)
-        let mtime = chrono::Utc::now().timestamp();
-
-        // Get upload authorization from Zotero
-        let auth = client.get_upload_authorization_unified(
-            self.library_id,
-            self.library_type,
-            &self.key,
-            filename,
-            file_data.len(),
-            &Some(md5_hash.clone()),
-            Some(mtime),
-        ).await?;
-
-        // If file already exists on Zotero, no upload needed
-        if auth.exists {
-            tracing::info!("File already exists in Zotero: {}", self.key);
-            return Ok(());
+        result.map(|_| ())
+    }
+
+    /// Upload the stored file; returns its MD5 and size, or `None` if there
+    /// is no stored file
+    async fn upload_file(
+        &mut self,
+        client: &super::ZoteroClient,
+        filesystem: &dyn FileSystem,
+        library_version: &mut i64,
+    ) -> Result<Option<(String, u64)>> {
+        let (folder, filename) = self.file_location(client.attachment_layout());
+        if !filesystem.file_exists(&folder, &filename).await? {
+            tracing::warn!("No stored file for attachment {} at {}/{}", self.key, folder, filename);
+            return Ok(None);
         }
 
-        // Upload file to Zotero's storage
-        if let (Some(upload_url), Some(upload_key), Some(params)) = 
-            (auth.upload_url, auth.upload_key, auth.params) {
-            
-            tracing::info!("Uploading file to Zotero: {} ({} bytes)", self.key, file_data.len());
-            
-            // Upload file data
-            client.upload_file_to_url(&upload_url, &file_data, &params).await?;
-            
-            // Register upload completion
-            client.register_upload_completion_unified(self.library_id, self.library_type, &self.key, &upload_key).await?;
-            
-            tracing::info!("Successfully uploaded file: {}", self.key);
-        } else {
-            return Err(Error::Api {
-                code: 500,
-                message: "Invalid upload authorization response".to_string(),
-            });
+        // Hash the stored file without loading it into memory
+        let reader = filesystem.file_reader(&folder, &filename, FileGetOptions::default()).await?;
+        let (md5_hash, size) = md5_of(reader).await?;
+
+        let previous_md5 = self.data.extra_fields.get("md5")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+
+        if previous_md5.as_deref() != Some(md5_hash.as_str()) {
+            // Snapshots are sent as a ZIP of all their files
+            let archive = if self.is_snapshot() {
+                let prefix = client.attachment_layout().snapshot_prefix(self.library_type, self.library_id, &self.key);
+                Some(snapshot::archive(filesystem, &folder, &prefix).await?)
+            } else {
+                None
+            };
+            let zip_filename = format!("{}.zip", self.key);
+
+            // Zotero expects the modification time in milliseconds
+            let mtime = match filesystem.file_stat(&folder, &filename, FileStatOptions::default()).await {
+                Ok(info) => info.modified.timestamp_millis(),
+                Err(_) => chrono::Utc::now().timestamp_millis(),
+            };
+
+            if let Some(webdav) = client.webdav(self.library_type) {
+                let properties = WebDavProperties { mtime, hash: md5_hash.clone() };
+                self.upload_webdav(client, webdav, filesystem, archive, &properties, previous_md5.as_deref(), library_version).await?;
+            } else {
+                let auth = client.get_upload_authorization_unified(
+                    self.library_id,
+                    self.library_type,
+                    &self.key,
+                    &filename,
+                    size as usize,
+                    &md5_hash,
+                    mtime,
+                    previous_md5.as_deref(),
+                    archive.as_ref().map(|a| (a.md5.as_str(), zip_filename.as_str())),
+                ).await?;
+
+                if auth.exists {
+                    // Zotero already has this content and attached it to the item
+                    tracing::info!("File already exists in Zotero: {}", self.key);
+                } else if let (Some(upload_url), Some(upload_key), Some(params)) =
+                    (auth.upload_url, auth.upload_key, auth.params) {
+
+                    match archive {
+                        Some(archive) => {
+                            let length = archive.data.len() as u64;
+                            tracing::info!("Uploading snapshot to Zotero: {} ({} bytes zipped)", self.key, length);
+                            let reader = Box::new(std::io::Cursor::new(archive.data));
+                            client.upload_file_stream_to_url(&upload_url, reader, length, &params).await?;
+                        }
+                        None => {
+                            tracing::info!("Uploading file to Zotero: {} ({} bytes)", self.key, size);
+                            let reader = filesystem.file_reader(&folder, &filename, FileGetOptions::default()).await?;
+                            client.upload_file_stream_to_url(&upload_url, reader, size, &params).await?;
+                        }
+                    }
+
+                    let new_version = client
+                        .register_upload_completion_unified(self.library_id, self.library_type, &self.key, &upload_key, previous_md5.as_deref())
+                        .await?;
+                    if let Some(new_version) = new_version {
+                        *library_version = new_version;
+                        self.version = new_version;
+                        self.data.version = new_version;
+                    }
+
+                    tracing::info!("Successfully uploaded file: {}", self.key);
+                } else {
+                    return Err(Error::Api {
+                        code: 500,
+                        message: "Invalid upload authorization response".to_string(),
+                    });
+                }
+
+            }
+
+            self.data.extra_fields.insert("md5".to_string(), serde_json::Value::String(md5_hash.clone()));
+            self.data.extra_fields.insert("mtime".to_string(), serde_json::Value::from(mtime));
+        }
+
+        self.md5 = Some(md5_hash.clone());
+        self.mark_file_synced().await?;
+        Ok(Some((md5_hash, size)))
+    }
+
+    /// Store the file on the WebDAV server and set its hash and mtime on the
+    /// item, as Zotero does for files Zotero Storage never sees.
+    ///
+    /// Fails with a 412 [`Error::Api`] if another client replaced the file
+    /// on the server since `previous_md5`.
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_webdav(
+        &mut self,
+        client: &super::ZoteroClient,
+        webdav: &ZoteroWebDav,
+        filesystem: &dyn FileSystem,
+        archive: Option<snapshot::SnapshotArchive>,
+        properties: &WebDavProperties,
+        previous_md5: Option<&str>,
+        library_version: &mut i64,
+    ) -> Result<()> {
+        if let Some(remote) = webdav.properties(&self.key).await? {
+            if Some(remote.hash.as_str()) != previous_md5 && remote.hash != properties.hash {
+                return Err(Error::Api {
+                    code: 412,
+                    message: format!("File of {} changed on WebDAV", self.key),
+                });
+            }
+        }
+
+        let archive = match archive {
+            Some(archive) => archive.data,
+            None => {
+                let (folder, name) = self.file_location(client.attachment_layout());
+                let data = filesystem.file_get(&folder, &name, FileGetOptions::default()).await?;
+                let filename = self.data.extra_fields.get("filename")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+                    .unwrap_or(name);
+                tokio::task::spawn_blocking(move || webdav::archive(&filename, data))
+                    .await
+                    .map_err(|e| Error::InvalidData(format!("Cannot pack attachment archive: {}", e)))??
+            }
+        };
+
+        tracing::info!("Uploading file to WebDAV: {} ({} bytes zipped)", self.key, archive.len());
+        webdav.upload(&self.key, &archive, properties).await?;
+
+        self.data.extra_fields.insert("md5".to_string(), serde_json::Value::String(properties.hash.clone()));
+        self.data.extra_fields.insert("mtime".to_string(), serde_json::Value::from(properties.mtime));
+        let new_version = client.upload_item_unified(self.library_id, self.library_type, self, *library_version).await?;
+        *library_version = new_version;
+        self.version = new_version;
+        self.data.version = new_version;
+
+        tracing::info!("Successfully uploaded file: {}", self.key);
+        Ok(())
+    }
+
+    /// Persist the file hash together with `data` as the synced state
+    async fn mark_file_synced(&mut self) -> Result<()> {
+        self.synced_data = Some(serde_json::to_value(&self.data)?);
+
+        if let (Some(db), Some(schema)) = (&self.db, &self.db_schema) {
+            let query = format!(
+                "UPDATE {}.items SET data = $1, synced_data = $1, md5 = $2, version = $3 WHERE key = $4 AND library_id = $5 AND library_type = $6",
+                schema
+            );
+            sqlx::query(&query)
+                .bind(&self.synced_data)
+                .bind(&self.md5)
+                .bind(self.version)
+                .bind(&self.key)
+                .bind(self.library_id)
+                .bind(self.library_type)
+                .execute(db)
+                .await?;
         }
 
         Ok(())
     }
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use sqlx::postgres::PgPoolOptions;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::filesystem::InMemoryFileSystem;
+    use crate::filesystem::memory::{FailureKind, FileOp};
+    use crate::zotero::ZoteroClient;
+
+    const KEY: &str = "ABCD2345";
+    const CONTENT: &[u8] = b"%PDF-1.7 attachment";
+
+    /// Client of a stand-in Zotero API that only answers the key check, for
+    /// the personal library with its files on an in-memory WebDAV store; the
+    /// database is never reached
+    async fn client(storage: Arc<InMemoryFileSystem>, webdav: Arc<InMemoryFileSystem>) -> ZoteroClient {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let key = serde_json::json!({
+                "key": "test",
+                "userID": 1,
+                "username": "test",
+                "displayName": "Test",
+                "access": { "user": { "library": true, "files": true }, "groups": { "all": {} } },
+            })
+            .to_string();
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 4096];
+                let _ = socket.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    key.len(),
+                    key
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let db = PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap();
+        let mut client = ZoteroClient::new(&format!("http://{}/", address), "test", db, storage, "public", false)
+            .await
+            .unwrap();
+        client.set_webdav(Some(ZoteroWebDav::new(webdav)));
+        client
+    }
+
+    fn attachment(md5: &str) -> Item {
+        let data: ItemData = serde_json::from_value(serde_json::json!({
+            "key": KEY,
+            "version": 1,
+            "itemType": "attachment",
+            "dateAdded": "2024-01-01T00:00:00Z",
+            "dateModified": "2024-01-01T00:00:00Z",
+            "linkMode": "imported_file",
+            "filename": "paper.pdf",
+            "contentType": "application/pdf",
+            "md5": md5,
+        }))
+        .unwrap();
+
+        Item {
+            key: KEY.to_string(),
+            version: 1,
+            library_id: 1,
+            library_type: LibraryType::User,
+            data,
+            meta: None,
+            trashed: false,
+            deleted: false,
+            sync_status: SyncStatus::Synced,
+            md5: None,
+            synced_data: None,
+            db: None,
+            db_schema: None,
+        }
+    }
+
+    fn webdav_with(data: &[u8]) -> Arc<InMemoryFileSystem> {
+        let webdav = Arc::new(InMemoryFileSystem::new());
+        let archive = webdav::archive("paper.pdf", data.to_vec()).unwrap();
+        webdav.insert(webdav::ZOTERO_FOLDER, &format!("{}.zip", KEY), archive);
+        webdav
+    }
+
+    #[tokio::test]
+    async fn failed_store_is_retried() {
+        let storage = Arc::new(InMemoryFileSystem::new());
+        let client = client(storage.clone(), webdav_with(CONTENT)).await;
+        let item = attachment(&format!("{:x}", md5::compute(CONTENT)));
+        let (folder, name) = item.file_location(client.attachment_layout());
+
+        storage.fail_next(FileOp::FilePut, FailureKind::Transient);
+        assert!(matches!(item.download_file(&client, storage.as_ref()).await, Err(Error::Io(_))));
+        assert_eq!(storage.contents(&folder, &name), None);
+
+        let (md5, size) = item.download_file(&client, storage.as_ref()).await.unwrap().unwrap();
+        assert_eq!(md5, format!("{:x}", md5::compute(CONTENT)));
+        assert_eq!(size, CONTENT.len() as u64);
+        assert_eq!(storage.contents(&folder, &name).as_deref(), Some(CONTENT));
+        assert_eq!(storage.calls_of(FileOp::FilePut).len(), 2);
+
+        let info = storage.file_stat(&folder, &name, FileStatOptions::default()).await.unwrap();
+        assert_eq!(info.content_type.as_deref(), Some("application/pdf"));
+        assert_eq!(info.metadata.get(KEY_METADATA).map(String::as_str), Some(KEY));
+        assert_eq!(info.metadata.get(MD5_METADATA), Some(&md5));
+    }
+
+    #[tokio::test]
+    async fn stored_file_is_not_downloaded_again() {
+        let storage = Arc::new(InMemoryFileSystem::new());
+        let webdav = webdav_with(CONTENT);
+        let client = client(storage.clone(), webdav.clone()).await;
+        let item = attachment(&format!("{:x}", md5::compute(CONTENT)));
+        let (folder, name) = item.file_location(client.attachment_layout());
+        storage.insert(&folder, &name, CONTENT);
+
+        assert!(item.download_file(&client, storage.as_ref()).await.unwrap().is_some());
+        assert!(webdav.calls().is_empty());
+        assert!(storage.calls_of(FileOp::FilePut).is_empty());
+    }
+
+    #[tokio::test]
+    async fn corrupt_file_is_not_stored() {
+        let storage = Arc::new(InMemoryFileSystem::new());
+        let client = client(storage.clone(), webdav_with(b"corrupt")).await;
+        let item = attachment(&format!("{:x}", md5::compute(CONTENT)));
+        let (folder, name) = item.file_location(client.attachment_layout());
+
+        assert!(matches!(item.download_file(&client, storage.as_ref()).await, Err(Error::Validation(_))));
+        assert_eq!(storage.contents(&folder, &name), None);
+    }
+}
@@ -0,0 +1,290 @@
+//! Where attachment files are kept in storage.
+//!
+//! Files go into one configured bucket under an object key built from a
+//! template such as `{library_type}/{library_id}/{item_key}/{filename}`.
+//! Earlier versions stored them as `attachments/{item_key}/{filename}`;
+//! [`LayoutMigration`] moves those into the configured layout.
+//!
+//! In content-addressed mode files are stored once per MD5 instead, under
+//! `blobs/{md5[0..2]}/{md5}` in the same bucket (see [`super::blob`]).
+
+use std::sync::Arc;
+use sqlx::{PgPool, Row};
+use tracing::{info, warn};
+
+use crate::{Error, Result};
+use crate::config::Config;
+use crate::filesystem::{FileSystem, FileGetOptions, FileStatOptions, FolderCreateOptions};
+use super::LibraryType;
+
+/// Bucket used when `[storage] bucket` is not set
+pub const DEFAULT_BUCKET: &str = "attachments";
+
+/// Object key template used when `[storage] key_layout` is not set
+pub const DEFAULT_KEY_LAYOUT: &str = "{library_type}/{library_id}/{item_key}/{filename}";
+
+/// Folder of the layout used before the key template was configurable
+pub const LEGACY_FOLDER: &str = "attachments";
+
+/// Prefix of content-addressed blobs within the bucket
+pub const BLOB_PREFIX: &str = "blobs/";
+
+const PLACEHOLDERS: [&str; 4] = ["library_type", "library_id", "item_key", "filename"];
+
+/// Bucket and object key template for attachment files
+#[derive(Debug, Clone)]
+pub struct AttachmentLayout {
+    bucket: String,
+    key_layout: String,
+    content_addressed: bool,
+    /// Versioning and object lock of the bucket when it is created
+    bucket_options: FolderCreateOptions,
+}
+
+impl Default for AttachmentLayout {
+    fn default() -> Self {
+        Self {
+            bucket: DEFAULT_BUCKET.to_string(),
+            key_layout: DEFAULT_KEY_LAYOUT.to_string(),
+            content_addressed: false,
+            bucket_options: FolderCreateOptions::default(),
+        }
+    }
+}
+
+impl AttachmentLayout {
+    /// Validate the bucket name and key template.
+    ///
+    /// The template may only use the known placeholders and must contain
+    /// `{item_key}`, so that every attachment gets its own object.
+    pub fn new(bucket: &str, key_layout: &str) -> Result<Self> {
+        if bucket.is_empty() || bucket.contains('/') {
+            return Err(Error::InvalidData(format!("Invalid attachment bucket name: {:?}", bucket)));
+        }
+
+        let mut rest = key_layout;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}')
+                .ok_or_else(|| Error::InvalidData(format!("Unclosed placeholder in key layout: {}", key_layout)))?;
+            let name = &rest[start + 1..start + end];
+            if !PLACEHOLDERS.contains(&name) {
+                return Err(Error::InvalidData(format!("Unknown placeholder {{{}}} in key layout: {}", name, key_layout)));
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        if !key_layout.contains("{item_key}") {
+            return Err(Error::InvalidData(format!("Key layout must contain {{item_key}}: {}", key_layout)));
+        }
+        if key_layout.starts_with('/') || key_layout.starts_with(BLOB_PREFIX) {
+            return Err(Error::InvalidData(format!("Key layout must not start with '/' or '{}': {}", BLOB_PREFIX, key_layout)));
+        }
+
+        Ok(Self {
+            bucket: bucket.to_string(),
+            key_layout: key_layout.to_string(),
+            content_addressed: false,
+            bucket_options: FolderCreateOptions::default(),
+        })
+    }
+
+    /// Store files by content instead of per item
+    pub fn with_content_addressed(mut self, content_addressed: bool) -> Self {
+        self.content_addressed = content_addressed;
+        self
+    }
+
+    /// Create the bucket with versioning or object lock
+    pub fn with_bucket_options(mut self, bucket_options: FolderCreateOptions) -> Self {
+        self.bucket_options = bucket_options;
+        self
+    }
+
+    /// Layout from `[storage] bucket` and `[storage] key_layout`
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let storage = config.storage.as_ref();
+        let layout = Self::new(
+            storage.and_then(|s| s.bucket.as_deref()).unwrap_or(DEFAULT_BUCKET),
+            storage.and_then(|s| s.key_layout.as_deref()).unwrap_or(DEFAULT_KEY_LAYOUT),
+        )?;
+
+        let bucket_options = FolderCreateOptions {
+            object_locking: storage.and_then(|s| s.object_locking).unwrap_or(false),
+            versioning: storage.and_then(|s| s.versioning).unwrap_or(false),
+            retention_days: storage.and_then(|s| s.retention_days),
+        };
+        if bucket_options.retention_days.is_some() && !bucket_options.object_locking {
+            return Err(Error::InvalidData("[storage] retention_days requires object_locking".to_string()));
+        }
+
+        Ok(layout
+            .with_content_addressed(storage.and_then(|s| s.content_addressed).unwrap_or(false))
+            .with_bucket_options(bucket_options))
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    pub fn key_layout(&self) -> &str {
+        &self.key_layout
+    }
+
+    pub fn is_content_addressed(&self) -> bool {
+        self.content_addressed
+    }
+
+    /// Object key of an attachment file within the bucket
+    pub fn object_key(&self, library_type: LibraryType, library_id: i64, item_key: &str, filename: Option<&str>) -> String {
+        // A slash in the filename would add a level below the item
+        let filename = filename.unwrap_or("unknown").replace('/', "_");
+
+        self.key_layout
+            .replace("{library_type}", &library_type.to_string())
+            .replace("{library_id}", &library_id.to_string())
+            .replace("{item_key}", item_key)
+            .replace("{filename}", &filename)
+    }
+
+    /// Object key prefix of the files unpacked from a snapshot archive: the
+    /// key of the attachment with an empty `{filename}`
+    pub fn snapshot_prefix(&self, library_type: LibraryType, library_id: i64, item_key: &str) -> String {
+        let mut prefix = self.key_layout
+            .replace("{library_type}", &library_type.to_string())
+            .replace("{library_id}", &library_id.to_string())
+            .replace("{item_key}", item_key)
+            .replace("{filename}", "");
+        if !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        prefix
+    }
+
+    /// Folder and file name to pass to [`FileSystem`] for an attachment
+    pub fn location(&self, library_type: LibraryType, library_id: i64, item_key: &str, filename: Option<&str>) -> (String, String) {
+        (self.bucket.clone(), self.object_key(library_type, library_id, item_key, filename))
+    }
+
+    /// Folder and file name of the blob holding content with this MD5
+    pub fn blob_location(&self, md5: &str) -> (String, String) {
+        (self.bucket.clone(), format!("{}{}/{}", BLOB_PREFIX, &md5[..2.min(md5.len())], md5))
+    }
+
+    /// Create the bucket unless it exists
+    pub async fn ensure_bucket(&self, filesystem: &dyn FileSystem) -> Result<()> {
+        if !filesystem.folder_exists(&self.bucket).await? {
+            info!("Creating attachment bucket {}", self.bucket);
+            filesystem.folder_create(&self.bucket, self.bucket_options.clone()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Folder and file name of an attachment in the legacy layout
+pub fn legacy_location(item_key: &str, filename: Option<&str>) -> (String, String) {
+    (
+        LEGACY_FOLDER.to_string(),
+        format!("{}/{}", item_key, filename.unwrap_or("unknown")),
+    )
+}
+
+/// Outcome of a layout migration
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// Attachment items examined
+    pub scanned: usize,
+    /// Files moved, or that would be moved in a dry run
+    pub moved: usize,
+    /// Bytes moved
+    pub bytes: u64,
+    /// Items with no file in the legacy layout
+    pub missing: usize,
+    /// Files that could not be moved
+    pub failed: usize,
+}
+
+/// Moves attachment files from the legacy layout into the configured one
+pub struct LayoutMigration {
+    db: PgPool,
+    schema: String,
+    filesystem: Arc<dyn FileSystem>,
+    layout: AttachmentLayout,
+}
+
+impl LayoutMigration {
+    pub fn new(db: PgPool, schema: String, filesystem: Arc<dyn FileSystem>, layout: AttachmentLayout) -> Self {
+        Self { db, schema, filesystem, layout }
+    }
+
+    /// Copy every legacy file to its new location and delete the original.
+    ///
+    /// A file is only deleted once its copy is written, so an interrupted
+    /// run can be repeated.
+    pub async fn run(&self, dry_run: bool) -> Result<MigrationReport> {
+        let query = format!(
+            r#"
+            SELECT key, library_id, library_type, data->>'filename' AS filename
+            FROM {}.items
+            WHERE data->>'itemType' = 'attachment'
+            ORDER BY library_type, library_id, key
+            "#,
+            self.schema
+        );
+
+        let rows = sqlx::query(&query).fetch_all(&self.db).await?;
+        let mut report = MigrationReport {
+            scanned: rows.len(),
+            ..Default::default()
+        };
+
+        if !dry_run {
+            self.layout.ensure_bucket(self.filesystem.as_ref()).await?;
+        }
+
+        for row in rows {
+            let key: String = row.get("key");
+            let library_id: i64 = row.get("library_id");
+            let library_type: LibraryType = row.get("library_type");
+            let filename: Option<String> = row.get("filename");
+
+            let (old_folder, old_name) = legacy_location(&key, filename.as_deref());
+            let (new_folder, new_name) = self.layout.location(library_type, library_id, &key, filename.as_deref());
+            if old_folder == new_folder && old_name == new_name {
+                continue;
+            }
+
+            if !self.filesystem.file_exists(&old_folder, &old_name).await? {
+                report.missing += 1;
+                continue;
+            }
+
+            if dry_run {
+                info!("Would move {}/{} -> {}/{}", old_folder, old_name, new_folder, new_name);
+                report.moved += 1;
+                continue;
+            }
+
+            match self.move_file(&old_folder, &old_name, &new_folder, &new_name).await {
+                Ok(bytes) => {
+                    info!("Moved {}/{} -> {}/{}", old_folder, old_name, new_folder, new_name);
+                    report.moved += 1;
+                    report.bytes += bytes;
+                }
+                Err(e) => {
+                    warn!("Cannot move {}/{}: {}", old_folder, old_name, e);
+                    report.failed += 1;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn move_file(&self, old_folder: &str, old_name: &str, new_folder: &str, new_name: &str) -> Result<u64> {
+        let options = self.filesystem.file_stat(old_folder, old_name, FileStatOptions::default()).await?.put_options();
+        let mut reader = self.filesystem.file_reader(old_folder, old_name, FileGetOptions::default()).await?;
+        let bytes = self.filesystem.file_put_stream(new_folder, new_name, &mut reader, options).await?;
+        self.filesystem.file_delete(old_folder, old_name).await?;
+        Ok(bytes)
+    }
+}
@@ -1,9 +1,12 @@
+use std::collections::HashSet;
 use serde::{Deserialize, Serialize};
 use sqlx::{Row, PgPool};
 use chrono::{DateTime, Utc};
 use crate::{Result, Error};
-use super::{SyncMode, LibraryType, GroupData, UserData, ZoteroClient};
+use super::{SyncMode, ConflictPolicy, ConflictResolver, LibraryType, GroupData, UserData, ZoteroClient, AttachmentState};
 use crate::filesystem::FileSystem;
+use super::client::WRITE_BATCH_LIMIT;
+use super::blob;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Library {
@@ -20,7 +23,9 @@ pub struct Library {
     pub deleted: bool,
     pub item_version: i64,
     pub collection_version: i64,
+    pub search_version: i64,
     pub tag_version: i64,
+    pub fulltext_version: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gitlab: Option<DateTime<Utc>>,
 
@@ -29,6 +34,7 @@ pub struct Library {
     pub incoming_sync: SyncMode,
     pub outgoing_sync: SyncMode,
     pub sync_tags: bool,
+    pub conflict_policy: ConflictPolicy,
 
     // Computed/helper fields
     pub is_modified: bool,
@@ -59,12 +65,15 @@ impl Library {
             deleted: row.try_get("deleted")?,
             item_version: row.try_get("item_version")?,
             collection_version: row.try_get("collection_version")?,
+            search_version: row.try_get("search_version")?,
             tag_version: row.try_get("tag_version")?,
+            fulltext_version: row.try_get("fulltext_version")?,
             gitlab: row.try_get("gitlab")?,
             active: row.try_get("active")?,
             incoming_sync: row.try_get("incoming_sync")?,
             outgoing_sync: row.try_get("outgoing_sync")?,
             sync_tags: row.try_get("tags")?,
+            conflict_policy: row.try_get("conflict_policy")?,
             is_modified: false,
             client: None,
             db: None,
@@ -84,12 +93,15 @@ impl Library {
             deleted: false,
             item_version: 0,
             collection_version: 0,
+            search_version: 0,
             tag_version: 0,
+            fulltext_version: 0,
             gitlab: None,
             active: true,
             incoming_sync: SyncMode::Manual,
             outgoing_sync: SyncMode::Disabled,
             sync_tags: false,
+            conflict_policy: ConflictPolicy::default(),
             is_modified: false,
             client: None,
             db: None,
@@ -109,12 +121,15 @@ impl Library {
             deleted: false,
             item_version: 0,
             collection_version: 0,
+            search_version: 0,
             tag_version: 0,
+            fulltext_version: 0,
             gitlab: None,
             active: true,
             incoming_sync: SyncMode::Manual,
             outgoing_sync: SyncMode::Disabled,
             sync_tags: false,
+            conflict_policy: ConflictPolicy::default(),
             is_modified: false,
             client: None,
             db: None,
@@ -261,7 +276,7 @@ impl Library {
 
         // Clear library versions
         let query = format!(
-            "UPDATE {}.libraries SET version=0, item_version=0, collection_version=0, tag_version=0 WHERE id=$1 AND library_type=$2",
+            "UPDATE {}.libraries SET version=0, item_version=0, collection_version=0, search_version=0, tag_version=0, fulltext_version=0 WHERE id=$1 AND library_type=$2",
             schema
         );
         sqlx::query(&query)
@@ -283,6 +298,13 @@ impl Library {
             .bind(self.library_type)
             .execute(db).await?;
 
+        // Clear saved searches
+        let query = format!("DELETE FROM {}.searches WHERE library_id=$1 AND library_type=$2", schema);
+        sqlx::query(&query)
+            .bind(self.id)
+            .bind(self.library_type)
+            .execute(db).await?;
+
         // Clear tags
         let query = format!("DELETE FROM {}.tags WHERE library_id=$1 AND library_type=$2", schema);
         sqlx::query(&query)
@@ -294,7 +316,9 @@ impl Library {
         self.version = 0;
         self.item_version = 0;
         self.collection_version = 0;
+        self.search_version = 0;
         self.tag_version = 0;
+        self.fulltext_version = 0;
 
         Ok(())
     }
@@ -312,6 +336,11 @@ impl Library {
         tracing::info!("Starting sync_collections for {} library {}", self.library_type, self.id);
         let (_, collection_version) = self.sync_collections().await?;
         tracing::info!("Completed sync_collections for {} library {}", self.library_type, self.id);
+
+        // Sync saved searches
+        tracing::info!("Starting sync_searches for {} library {}", self.library_type, self.id);
+        let (_, search_version) = self.sync_searches().await?;
+        tracing::info!("Completed sync_searches for {} library {}", self.library_type, self.id);
         
         // Upload modified items
         tracing::info!("Starting upload_items for {} library {}", self.library_type, self.id);
@@ -322,6 +351,18 @@ impl Library {
         tracing::info!("Starting download_items for {} library {}", self.library_type, self.id);
         let (_, item_version) = self.download_items().await?;
         tracing::info!("Completed download_items for {} library {}", self.library_type, self.id);
+
+        // Retry attachment downloads that failed in earlier syncs; the files
+        // are tried again next time, so this does not fail the sync
+        if let Err(e) = self.retry_failed_downloads().await {
+            tracing::error!("Cannot retry failed attachment downloads for {} library {}: {}", self.library_type, self.id, e);
+        }
+
+        // Sync full-text content (after items, since rows reference them)
+        tracing::info!("Starting sync_fulltext for {} library {}", self.library_type, self.id);
+        let (_, fulltext_version) = self.sync_fulltext().await?;
+        self.fulltext_version = fulltext_version;
+        tracing::info!("Completed sync_fulltext for {} library {}", self.library_type, self.id);
         
         // Sync tags
         if self.sync_tags {
@@ -336,9 +377,13 @@ impl Library {
         self.version = self.sync_deleted().await?;
         tracing::info!("Completed sync_deleted for {} library {}", self.library_type, self.id);
 
+        // Deleted attachments may have dropped the last reference to a blob
+        self.release_blobs().await;
+
         // Update local versions
         self.item_version = item_version;
         self.collection_version = collection_version;
+        self.search_version = search_version;
 
         // Update library in database
         self.update_local().await?;
@@ -382,7 +427,58 @@ impl Library {
             for chunk in collections_to_update.chunks(50) {
                 let (collections, _) = client.get_collections_cloud_unified(self.id, self.library_type, chunk).await?;
                 for collection in collections {
-                    self.update_collection_local(&collection).await?;
+                    if self.update_collection_local(&collection).await? {
+                        counter += 1;
+                    } else {
+                        tracing::info!("Keeping local changes of collection {} until they are uploaded or their conflict is resolved", collection.key);
+                    }
+                }
+            }
+        }
+
+        Ok((counter, last_modified_version))
+    }
+
+    async fn sync_searches(&self) -> Result<(i64, i64)> {
+        let client = self.client.as_ref().ok_or_else(|| Error::InvalidData("Client not set".to_string()))?;
+
+        let mut counter = 0i64;
+        let mut last_modified_version = self.search_version;
+
+        // Upload modified searches if we can upload
+        if self.can_upload() {
+            counter += self.sync_modified_searches().await?;
+        }
+
+        // Download saved searches from cloud if we can download
+        if self.can_download() {
+            let (versions, cloud_version) = client.get_searches_version_cloud_unified(self.id, self.library_type, self.search_version).await?;
+
+            if cloud_version > last_modified_version {
+                last_modified_version = cloud_version;
+            }
+
+            let pending = self.get_pending_search_keys().await?;
+            let mut searches_to_update = Vec::new();
+            for (search_key, version) in versions {
+                if pending.contains(&search_key) {
+                    tracing::info!("Keeping local changes of search {} until they are uploaded", search_key);
+                    continue;
+                }
+                let local_version = self.get_search_version_local(&search_key).await?;
+                if local_version < version {
+                    searches_to_update.push(search_key);
+                }
+            }
+
+            // Fetch searches in batches of 50
+            for chunk in searches_to_update.chunks(50) {
+                let searches = client.get_searches_cloud_unified(self.id, self.library_type, chunk).await?;
+                for mut search in searches {
+                    if let (Some(db), Some(schema)) = (&self.db, &self.db_schema) {
+                        search.set_db(db.clone(), schema.clone());
+                    }
+                    search.update_local().await?;
                     counter += 1;
                 }
             }
@@ -406,9 +502,11 @@ impl Library {
         // Query for items that need to be uploaded
         let query = format!(
             r#"
-            SELECT key, version, data, meta, trashed, deleted, sync::TEXT as sync, md5
+            SELECT key, version, data, meta, trashed, deleted, sync::TEXT as sync, md5, synced_data
             FROM {}.items
-            WHERE library_id = $1 AND library_type = $2 AND (sync = 'new' OR sync = 'modified')
+            WHERE library_id = $1 AND library_type = $2
+              AND (sync = 'new' OR sync = 'modified'
+                   OR (sync = 'synced' AND md5 IS NOT NULL AND md5 IS DISTINCT FROM data->>'md5'))
             ORDER BY key
             "#,
             schema
@@ -420,7 +518,7 @@ impl Library {
             .fetch_all(db)
             .await?;
 
-        let mut counter = 0i64;
+        let mut items = Vec::with_capacity(rows.len());
 
         for row in rows {
             let key: String = row.get("key");
@@ -431,15 +529,16 @@ impl Library {
             let deleted: bool = row.get("deleted");
             let sync_status: String = row.get("sync");
             let md5: Option<String> = row.get("md5");
+            let synced_data: Option<serde_json::Value> = row.get("synced_data");
 
             // Parse the item data
             let item_data: super::ItemData = serde_json::from_value(data_value)?;
             let item_meta: Option<super::item::ItemMeta> = meta_value
-                .map(|v| serde_json::from_value(v))
+                .map(serde_json::from_value)
                 .transpose()?;
 
-            let mut item = super::Item {
-                key: key.clone(),
+            items.push(super::Item {
+                key,
                 version,
                 library_id: self.id,
                 library_type: self.library_type,
@@ -453,22 +552,152 @@ impl Library {
                     _ => super::SyncStatus::Synced,
                 },
                 md5,
+                synced_data,
                 db: Some(db.clone()),
                 db_schema: Some(schema.clone()),
-            };
+            });
+        }
 
-            // Upload to cloud
-            if let Err(e) = item.update_cloud(client, &mut last_modified_version).await {
-                tracing::error!("Failed to upload item {}: {}", key, e);
-                continue;
+        let mut counter = 0i64;
+
+        // Deletions go in multi-key requests, everything else in multi-object writes
+        let (mut deleted, pending): (Vec<_>, Vec<_>) = items.into_iter().partition(|item| item.deleted);
+
+        for chunk in deleted.chunks_mut(WRITE_BATCH_LIMIT) {
+            match super::Item::delete_cloud_batch(client, chunk, &mut last_modified_version).await {
+                Ok(()) => counter += chunk.len() as i64,
+                // Find out which items changed remotely one by one
+                Err(e) if e.is_precondition_failed() => {
+                    for item in chunk.iter_mut() {
+                        if self.upload_item(item, &mut last_modified_version).await {
+                            counter += 1;
+                        }
+                    }
+                }
+                Err(e) => tracing::error!("Failed to delete batch of {} items: {}", chunk.len(), e),
             }
+        }
 
-            counter += 1;
+        // Rows whose metadata is in sync only need their attachment file uploaded
+        let (files, mut pending): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|item| item.sync_status == super::SyncStatus::Synced);
+
+        for chunk in pending.chunks_mut(WRITE_BATCH_LIMIT) {
+            match super::Item::update_cloud_batch(client, chunk, &mut last_modified_version).await {
+                Ok(outcomes) => {
+                    for (item, outcome) in chunk.iter_mut().zip(outcomes) {
+                        match outcome {
+                            Ok(()) => {
+                                counter += 1;
+                                self.upload_file(item, &mut last_modified_version).await;
+                            }
+                            Err(e) if e.is_precondition_failed() => {
+                                if self.resolve_item_conflict(item, &mut last_modified_version).await {
+                                    counter += 1;
+                                }
+                            }
+                            Err(e) => tracing::error!("Failed to upload item {}: {}", item.key, e),
+                        }
+                    }
+                }
+                // The library moved on since our version; retry item by item so
+                // each one goes through conflict resolution
+                Err(e) if e.is_precondition_failed() => {
+                    for item in chunk.iter_mut() {
+                        if self.upload_item(item, &mut last_modified_version).await {
+                            counter += 1;
+                        }
+                    }
+                }
+                Err(e) => tracing::error!("Failed to upload batch of {} items: {}", chunk.len(), e),
+            }
+        }
+
+        for mut item in files {
+            if self.upload_file(&mut item, &mut last_modified_version).await {
+                counter += 1;
+            }
         }
 
         Ok((counter, last_modified_version))
     }
 
+    /// Upload a single item, resolving conflicts if Zotero rejects the version
+    async fn upload_item(&self, item: &mut super::Item, library_version: &mut i64) -> bool {
+        let Some(client) = self.client.as_ref() else {
+            return false;
+        };
+
+        match item.update_cloud(client, library_version).await {
+            Ok(()) => {
+                self.upload_file(item, library_version).await;
+                true
+            }
+            Err(e) if e.is_precondition_failed() => self.resolve_item_conflict(item, library_version).await,
+            Err(e) => {
+                tracing::error!("Failed to upload item {}: {}", item.key, e);
+                false
+            }
+        }
+    }
+
+    async fn resolve_item_conflict(&self, item: &mut super::Item, library_version: &mut i64) -> bool {
+        let (Some(client), Some(db), Some(schema)) = (&self.client, &self.db, &self.db_schema) else {
+            return false;
+        };
+
+        let resolver = ConflictResolver::new(client, db, schema, self.conflict_policy);
+        match resolver.resolve_item(item, library_version).await {
+            Ok(outcome) => {
+                tracing::info!("Resolved conflict for item {}: {}", item.key, outcome);
+                self.upload_file(item, library_version).await;
+                true
+            }
+            Err(e) => {
+                tracing::error!("Failed to resolve conflict for item {}: {}", item.key, e);
+                false
+            }
+        }
+    }
+
+    /// Upload the attachment file of a synced item if it changed, resolving
+    /// conflicts if Zotero holds a different file than we last saw
+    async fn upload_file(&self, item: &mut super::Item, library_version: &mut i64) -> bool {
+        if item.sync_status != super::SyncStatus::Synced || !item.needs_file_upload() {
+            return true;
+        }
+
+        let (Some(client), Some(db), Some(schema)) = (&self.client, &self.db, &self.db_schema) else {
+            return false;
+        };
+        let Some(filesystem) = &self.filesystem else {
+            tracing::warn!("Filesystem not configured, skipping attachment upload for item {}", item.key);
+            return false;
+        };
+
+        match item.upload_file_cloud(client, filesystem.as_ref(), library_version).await {
+            Ok(()) => true,
+            Err(e) if e.is_precondition_failed() => {
+                let resolver = ConflictResolver::new(client, db, schema, self.conflict_policy);
+                match resolver.resolve_file(item, filesystem.as_ref(), library_version).await {
+                    Ok(outcome) => {
+                        tracing::info!("Resolved file conflict for item {}: {}", item.key, outcome);
+                        true
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to resolve file conflict for item {}: {}", item.key, e);
+                        false
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to upload attachment file for item {}: {}", item.key, e);
+                false
+            }
+        }
+    }
+
     async fn download_items(&self) -> Result<(i64, i64)> {
         if !self.can_download() {
             return Ok((0, self.item_version));
@@ -498,13 +727,18 @@ impl Library {
             // Fetch items in batches of 50
             for chunk in items_to_update.chunks(50) {
                 let items = client.get_items_cloud_unified(self.id, self.library_type, chunk).await?;
+                let mut stored = Vec::with_capacity(items.len());
                 for item in &items {
-                    self.update_item_local(item).await?;
-                    counter += 1;
+                    if self.update_item_local(item).await? {
+                        stored.push(item);
+                        counter += 1;
+                    } else {
+                        tracing::info!("Keeping local changes of item {} until they are uploaded or their conflict is resolved", item.key);
+                    }
                 }
                 
                 // Download attachment files for imported_file attachments
-                for item in &items {
+                for item in stored {
                     if item.data.item_type == "attachment" {
                         if let Some(filesystem) = &self.filesystem {
                             if let Err(e) = item.download_attachment_cloud(client, filesystem.as_ref()).await {
@@ -522,6 +756,91 @@ impl Library {
         Ok((counter, last_modified_version))
     }
 
+    /// Download again the attachment files whose last download failed
+    async fn retry_failed_downloads(&self) -> Result<()> {
+        if !self.can_download() {
+            return Ok(());
+        }
+
+        let client = self.client.as_ref().ok_or_else(|| Error::InvalidData("Client not set".to_string()))?;
+        let Some(filesystem) = &self.filesystem else {
+            return Ok(());
+        };
+
+        let keys = AttachmentState::failed_downloads(client.db(), client.db_schema(), self.id, self.library_type).await?;
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        tracing::info!("Retrying {} failed attachment downloads for {} library {}", keys.len(), self.library_type, self.id);
+        for chunk in keys.chunks(50) {
+            // Fetch current data, the file may have changed since the failure
+            let items = client.get_items_cloud_unified(self.id, self.library_type, chunk).await?;
+            for item in &items {
+                if let Err(e) = item.download_attachment_cloud(client, filesystem.as_ref()).await {
+                    tracing::error!("Failed to download attachment for item {}: {}", item.key, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delete blobs no longer referenced, with content-addressed storage
+    async fn release_blobs(&self) {
+        let (Some(client), Some(filesystem)) = (&self.client, &self.filesystem) else {
+            return;
+        };
+        if !client.attachment_layout().is_content_addressed() {
+            return;
+        }
+
+        match blob::release_unreferenced(client.db(), client.db_schema(), filesystem.as_ref(), client.attachment_layout()).await {
+            Ok(0) => {}
+            Ok(released) => tracing::info!("Released {} unreferenced blobs", released),
+            Err(e) => tracing::error!("Cannot release unreferenced blobs: {}", e),
+        }
+    }
+
+    async fn sync_fulltext(&self) -> Result<(i64, i64)> {
+        if !self.can_download() {
+            return Ok((0, self.fulltext_version));
+        }
+
+        let client = self.client.as_ref().ok_or_else(|| Error::InvalidData("Client not set".to_string()))?;
+
+        let (versions, last_modified_version) = client.get_fulltext_versions_cloud_unified(self.id, self.library_type, self.fulltext_version).await?;
+
+        let mut counter = 0i64;
+        // Oldest version of content skipped for now, which the cursor must not pass
+        let mut oldest_skipped: Option<i64> = None;
+        for (item_key, version) in versions {
+            // Full-text rows reference items, so skip content for items we don't have
+            if self.get_item_version_local(&item_key).await? == 0 {
+                tracing::debug!("Skipping full-text for unknown item {}", item_key);
+                oldest_skipped = Some(oldest_skipped.map_or(version, |oldest| oldest.min(version)));
+                continue;
+            }
+
+            if self.get_fulltext_version_local(&item_key).await? >= version {
+                continue;
+            }
+
+            if let Some(fulltext) = client.get_item_fulltext_cloud_unified(self.id, self.library_type, &item_key).await? {
+                self.update_fulltext_local(&item_key, version, &fulltext).await?;
+                counter += 1;
+            }
+        }
+
+        // Fetch skipped content again once its item has been stored
+        let cursor = match oldest_skipped {
+            Some(version) => (version - 1).min(last_modified_version),
+            None => last_modified_version,
+        };
+
+        Ok((counter, cursor))
+    }
+
     async fn sync_tags(&self) -> Result<(i64, i64)> {
         if !self.can_download() {
             return Ok((0, self.tag_version));
@@ -565,6 +884,12 @@ impl Library {
             counter += 1;
         }
         
+        // Delete saved searches
+        for search_key in deletions.searches {
+            self.try_delete_search_local(&search_key, last_modified_version).await?;
+            counter += 1;
+        }
+        
         // Delete tags
         for tag_name in deletions.tags {
             self.delete_tag_local(&tag_name).await?;
@@ -594,6 +919,21 @@ impl Library {
         Ok(result.map(|row| row.get::<i64, _>("version")).unwrap_or(0))
     }
 
+    async fn get_search_version_local(&self, search_key: &str) -> Result<i64> {
+        let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
+        let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
+
+        let query = format!("SELECT version FROM {}.searches WHERE key = $1 AND library_id = $2 AND library_type = $3", schema);
+        let result = sqlx::query(&query)
+            .bind(search_key)
+            .bind(self.id)
+            .bind(self.library_type)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(result.map(|row| row.get::<i64, _>("version")).unwrap_or(0))
+    }
+
     async fn get_item_version_local(&self, item_key: &str) -> Result<i64> {
         let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
         let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
@@ -609,6 +949,21 @@ impl Library {
         Ok(result.map(|row| row.get::<i64, _>("version")).unwrap_or(0))
     }
 
+    async fn get_fulltext_version_local(&self, item_key: &str) -> Result<i64> {
+        let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
+        let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
+
+        let query = format!("SELECT version FROM {}.fulltext WHERE item_key = $1 AND library_id = $2 AND library_type = $3", schema);
+        let result = sqlx::query(&query)
+            .bind(item_key)
+            .bind(self.id)
+            .bind(self.library_type)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(result.map(|row| row.get::<i64, _>("version")).unwrap_or(0))
+    }
+
     async fn sync_modified_collections(&self) -> Result<i64> {
         let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
         let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
@@ -620,7 +975,7 @@ impl Library {
         // Query for collections that need to be uploaded
         let query = format!(
             r#"
-            SELECT key, version, data, meta, deleted, sync
+            SELECT key, version, data, meta, deleted, sync::TEXT as sync, synced_data
             FROM {}.collections
             WHERE library_id = $1 AND library_type = $2 AND (sync = 'new' OR sync = 'modified')
             "#,
@@ -633,24 +988,25 @@ impl Library {
             .fetch_all(db)
             .await?;
 
-        let mut counter = 0i64;
+        let mut collections = Vec::with_capacity(rows.len());
 
         for row in rows {
             let key: String = row.get("key");
             let version: i64 = row.get("version");
-            let data_json: String = row.get("data");
-            let meta_json: Option<String> = row.get("meta");
+            let data_value: serde_json::Value = row.get("data");
+            let meta_value: Option<serde_json::Value> = row.get("meta");
             let deleted: bool = row.get("deleted");
             let sync_status: String = row.get("sync");
+            let synced_data: Option<serde_json::Value> = row.get("synced_data");
 
             // Parse the collection data
-            let collection_data: super::CollectionData = serde_json::from_str(&data_json)?;
-            let collection_meta: Option<super::collection::CollectionMeta> = meta_json
-                .map(|json| serde_json::from_str(&json))
+            let collection_data: super::CollectionData = serde_json::from_value(data_value)?;
+            let collection_meta: Option<super::collection::CollectionMeta> = meta_value
+                .map(serde_json::from_value)
                 .transpose()?;
 
-            let mut collection = super::Collection {
-                key: key.clone(),
+            collections.push(super::Collection {
+                key,
                 version,
                 library_id: self.id,
                 library_type: self.library_type,
@@ -662,18 +1018,143 @@ impl Library {
                     "modified" => super::SyncStatus::Modified,
                     _ => super::SyncStatus::Synced,
                 },
+                synced_data,
+                db: Some(db.clone()),
+                db_schema: Some(schema.clone()),
+            });
+        }
+
+        let mut counter = 0i64;
+
+        // Uploads go one by one, deletions in multi-key requests
+        let (deleted, pending): (Vec<_>, Vec<_>) = collections.into_iter().partition(|collection| collection.deleted);
+
+        for mut collection in pending {
+            if self.upload_collection(&mut collection, &mut library_version).await {
+                counter += 1;
+            }
+        }
+
+        for chunk in deleted.chunks(WRITE_BATCH_LIMIT) {
+            match super::Collection::delete_cloud_batch(client, chunk, library_version).await {
+                Ok(new_version) => {
+                    library_version = new_version;
+                    counter += chunk.len() as i64;
+                }
+                // Find out which collections changed remotely one by one
+                Err(e) if e.is_precondition_failed() => {
+                    for collection in chunk {
+                        if self.upload_collection(&mut collection.clone(), &mut library_version).await {
+                            counter += 1;
+                        }
+                    }
+                }
+                Err(e) => tracing::error!("Failed to delete batch of {} collections: {}", chunk.len(), e),
+            }
+        }
+
+        Ok(counter)
+    }
+
+    /// Upload or delete a single collection, resolving conflicts if Zotero rejects the version
+    async fn upload_collection(&self, collection: &mut super::Collection, library_version: &mut i64) -> bool {
+        let (Some(client), Some(db), Some(schema)) = (&self.client, &self.db, &self.db_schema) else {
+            return false;
+        };
+
+        match collection.update_cloud(client, *library_version).await {
+            Ok(new_version) => {
+                *library_version = new_version;
+                true
+            }
+            Err(e) if e.is_precondition_failed() => {
+                let resolver = ConflictResolver::new(client, db, schema, self.conflict_policy);
+                match resolver.resolve_collection(collection, library_version).await {
+                    Ok(outcome) => {
+                        tracing::info!("Resolved conflict for collection {}: {}", collection.key, outcome);
+                        true
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to resolve conflict for collection {}: {}", collection.key, e);
+                        false
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to upload collection {}: {}", collection.key, e);
+                false
+            }
+        }
+    }
+
+    async fn sync_modified_searches(&self) -> Result<i64> {
+        let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
+        let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
+        let client = self.client.as_ref().ok_or_else(|| Error::InvalidData("Client not set".to_string()))?;
+
+        // Collection and item writes earlier in this sync move the library
+        // version past `search_version`, so start from the current one
+        let (_, mut library_version) = client.get_searches_version_cloud_unified(self.id, self.library_type, self.search_version).await?;
+
+        // Query for searches that need to be uploaded
+        let query = format!(
+            r#"
+            SELECT key, version, data, deleted, sync::TEXT as sync
+            FROM {}.searches
+            WHERE library_id = $1 AND library_type = $2 AND (sync = 'new' OR sync = 'modified')
+            "#,
+            schema
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(self.id)
+            .bind(self.library_type)
+            .fetch_all(db)
+            .await?;
+
+        let mut counter = 0i64;
+
+        for row in rows {
+            let key: String = row.get("key");
+            let version: i64 = row.get("version");
+            let data_value: serde_json::Value = row.get("data");
+            let deleted: bool = row.get("deleted");
+            let sync_status: String = row.get("sync");
+
+            let search_data: super::SearchData = serde_json::from_value(data_value)?;
+
+            let mut search = super::Search {
+                key: key.clone(),
+                version,
+                library_id: self.id,
+                library_type: self.library_type,
+                data: search_data,
+                deleted,
+                sync_status: match sync_status.as_str() {
+                    "new" => super::SyncStatus::New,
+                    "modified" => super::SyncStatus::Modified,
+                    _ => super::SyncStatus::Synced,
+                },
                 db: Some(db.clone()),
                 db_schema: Some(schema.clone()),
             };
 
-            // Upload to cloud using the unified client methods
-            match collection.update_cloud(client, library_version).await {
+            let mut result = search.update_cloud(client, library_version).await;
+            if matches!(&result, Err(e) if e.is_precondition_failed()) {
+                // Someone else wrote to the library meanwhile; saved searches
+                // carry no conflict handling, so refresh the version and retry
+                let (_, current_version) = client.get_searches_version_cloud_unified(self.id, self.library_type, library_version).await?;
+                library_version = library_version.max(current_version);
+                result = search.update_cloud(client, library_version).await;
+            }
+
+            match result {
                 Ok(new_version) => {
                     library_version = new_version;
                     counter += 1;
                 }
                 Err(e) => {
-                    tracing::error!("Failed to upload collection {}: {}", key, e);
+                    tracing::error!("Failed to upload search {}, keeping the local change for the next sync: {}", key, e);
                     continue;
                 }
             }
@@ -682,30 +1163,54 @@ impl Library {
         Ok(counter)
     }
 
-    async fn update_collection_local(&self, collection: &super::Collection) -> Result<()> {
+    /// Keys of saved searches with local changes that are not uploaded yet
+    async fn get_pending_search_keys(&self) -> Result<HashSet<String>> {
+        let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
+        let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
+
+        let query = format!(
+            "SELECT key FROM {}.searches WHERE library_id = $1 AND library_type = $2 AND (sync = 'new' OR sync = 'modified')",
+            schema
+        );
+
+        let keys: Vec<String> = sqlx::query_scalar(&query)
+            .bind(self.id)
+            .bind(self.library_type)
+            .fetch_all(db)
+            .await?;
+
+        Ok(keys.into_iter().collect())
+    }
+
+    /// Store a downloaded collection; rows with local changes that are not
+    /// uploaded yet, or parked by an unresolved conflict, are left alone and
+    /// `false` is returned
+    async fn update_collection_local(&self, collection: &super::Collection) -> Result<bool> {
         let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
         let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
 
         let data_value = serde_json::to_value(&collection.data)?;
         let meta_value = collection.meta.as_ref()
-            .map(|m| serde_json::to_value(m))
+            .map(serde_json::to_value)
             .transpose()?;
 
         let query = format!(
             r#"
-            INSERT INTO {}.collections (key, version, library_id, library_type, data, meta, deleted, sync)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            INSERT INTO {}.collections (key, version, library_id, library_type, data, meta, deleted, sync, synced_data)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $5)
             ON CONFLICT (key, library_id, library_type) DO UPDATE SET
                 version = EXCLUDED.version,
                 data = EXCLUDED.data,
                 meta = EXCLUDED.meta,
                 deleted = EXCLUDED.deleted,
-                sync = EXCLUDED.sync
+                sync = EXCLUDED.sync,
+                synced_data = EXCLUDED.synced_data
+            WHERE {}.collections.sync = 'synced'
             "#,
-            schema
+            schema, schema
         );
 
-        sqlx::query(&query)
+        let result = sqlx::query(&query)
             .bind(&collection.key)
             .bind(collection.version)
             .bind(self.id)
@@ -717,22 +1222,25 @@ impl Library {
             .execute(db)
             .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
 
-    async fn update_item_local(&self, item: &super::Item) -> Result<()> {
+    /// Store a downloaded item; rows with local changes that are not
+    /// uploaded yet, or parked by an unresolved conflict, are left alone and
+    /// `false` is returned
+    async fn update_item_local(&self, item: &super::Item) -> Result<bool> {
         let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
         let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
 
         let data_value = serde_json::to_value(&item.data)?;
         let meta_value = item.meta.as_ref()
-            .map(|m| serde_json::to_value(m))
+            .map(serde_json::to_value)
             .transpose()?;
 
         let query = format!(
             r#"
-            INSERT INTO {}.items (key, version, library_id, library_type, data, meta, trashed, deleted, sync, md5)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            INSERT INTO {}.items (key, version, library_id, library_type, data, meta, trashed, deleted, sync, md5, synced_data)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $5)
             ON CONFLICT (key, library_id, library_type) DO UPDATE SET
                 version = EXCLUDED.version,
                 data = EXCLUDED.data,
@@ -740,12 +1248,14 @@ impl Library {
                 trashed = EXCLUDED.trashed,
                 deleted = EXCLUDED.deleted,
                 sync = EXCLUDED.sync,
-                md5 = EXCLUDED.md5
+                md5 = EXCLUDED.md5,
+                synced_data = EXCLUDED.synced_data
+            WHERE {}.items.sync = 'synced'
             "#,
-            schema
+            schema, schema
         );
 
-        sqlx::query(&query)
+        let result = sqlx::query(&query)
             .bind(&item.key)
             .bind(item.version)
             .bind(self.id)
@@ -759,6 +1269,42 @@ impl Library {
             .execute(db)
             .await?;
 
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn update_fulltext_local(&self, item_key: &str, version: i64, fulltext: &super::FullTextData) -> Result<()> {
+        let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
+        let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
+
+        let query = format!(
+            r#"
+            INSERT INTO {}.fulltext (item_key, library_id, library_type, version, content, indexed_pages, total_pages, indexed_chars, total_chars, modified)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW())
+            ON CONFLICT (item_key, library_id, library_type) DO UPDATE SET
+                version = EXCLUDED.version,
+                content = EXCLUDED.content,
+                indexed_pages = EXCLUDED.indexed_pages,
+                total_pages = EXCLUDED.total_pages,
+                indexed_chars = EXCLUDED.indexed_chars,
+                total_chars = EXCLUDED.total_chars,
+                modified = EXCLUDED.modified
+            "#,
+            schema
+        );
+
+        sqlx::query(&query)
+            .bind(item_key)
+            .bind(self.id)
+            .bind(self.library_type)
+            .bind(version)
+            .bind(&fulltext.content)
+            .bind(fulltext.indexed_pages)
+            .bind(fulltext.total_pages)
+            .bind(fulltext.indexed_chars)
+            .bind(fulltext.total_chars)
+            .execute(db)
+            .await?;
+
         Ok(())
     }
 
@@ -912,6 +1458,65 @@ impl Library {
         Ok(())
     }
 
+    async fn try_delete_search_local(&self, search_key: &str, last_modified_version: i64) -> Result<()> {
+        let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
+        let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
+
+        // Check if search exists and get its current sync status
+        let query = format!("SELECT sync, deleted FROM {}.searches WHERE key = $1 AND library_id = $2 AND library_type = $3", schema);
+        let result = sqlx::query(&query)
+            .bind(search_key)
+            .bind(self.id)
+            .bind(self.library_type)
+            .fetch_optional(db)
+            .await?;
+
+        if let Some(row) = result {
+            let sync_status: String = row.get("sync");
+            let already_deleted: bool = row.get("deleted");
+
+            if already_deleted {
+                return Ok(()); // Already deleted
+            }
+
+            // Determine action based on sync status and direction
+            let should_delete = match sync_status.as_str() {
+                "synced" => true, // Safe to delete
+                _ if self.can_download() => true, // Cloud leads, delete locally
+                _ => {
+                    // Local leads, mark as synced with cloud version
+                    let query = format!(
+                        "UPDATE {}.searches SET version = $1, sync = 'synced' WHERE key = $2 AND library_id = $3 AND library_type = $4",
+                        schema
+                    );
+                    sqlx::query(&query)
+                        .bind(last_modified_version)
+                        .bind(search_key)
+                        .bind(self.id)
+                        .bind(self.library_type)
+                        .execute(db)
+                        .await?;
+                    false
+                }
+            };
+
+            if should_delete {
+                let query = format!(
+                    "UPDATE {}.searches SET deleted = true WHERE key = $1 AND library_id = $2 AND library_type = $3",
+                    schema
+                );
+                sqlx::query(&query)
+                    .bind(search_key)
+                    .bind(self.id)
+                    .bind(self.library_type)
+                    .execute(db)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn delete_tag_local(&self, tag_name: &str) -> Result<()> {
         let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
         let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
@@ -932,7 +1537,7 @@ impl Library {
         let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
 
         let query = format!(
-            "UPDATE {}.libraries SET version=$1, item_version=$2, collection_version=$3, tag_version=$4, modified=NOW() WHERE id=$5 AND library_type=$6",
+            "UPDATE {}.libraries SET version=$1, item_version=$2, collection_version=$3, search_version=$4, tag_version=$5, fulltext_version=$6, modified=NOW() WHERE id=$7 AND library_type=$8",
             schema
         );
         
@@ -940,7 +1545,9 @@ impl Library {
             .bind(self.version)
             .bind(self.item_version)
             .bind(self.collection_version)
+            .bind(self.search_version)
             .bind(self.tag_version)
+            .bind(self.fulltext_version)
             .bind(self.id)
             .bind(self.library_type)
             .execute(db)
@@ -8,22 +8,46 @@ pub mod types;
 pub mod library;
 pub mod item;
 pub mod collection;
+pub mod search;
 pub mod tag;
 pub mod user;
 pub mod sync;
 pub mod sync_queue;
 pub mod sync_worker;
-
-pub use client::ZoteroClient;
+pub mod conflict;
+pub mod diff;
+pub mod history;
+pub mod gc;
+pub mod layout;
+pub mod attachment;
+pub mod blob;
+pub mod snapshot;
+pub mod verify;
+pub mod versions;
+pub mod webdav;
+pub mod download;
+
+pub use client::{ZoteroClient, RetryPolicy};
 pub use types::*;
 pub use library::Library;
 pub use item::{Item, ItemType};
 pub use collection::Collection;
+pub use search::Search;
 pub use tag::Tag;
 pub use user::User;
-pub use sync::{SyncDirection, SyncMode, SyncStatus, LibraryType};
+pub use sync::{SyncDirection, SyncMode, SyncStatus, LibraryType, ConflictPolicy};
 pub use sync_queue::{SyncQueue, SyncQueueEntry, QueueStats};
 pub use sync_worker::{SyncWorker, SyncWorkerConfig};
+pub use conflict::{ConflictResolver, ConflictOutcome, SyncConflicts, RecordedConflict, Resolution};
+pub use diff::{FieldChange, ChangeKind};
+pub use history::{ChangeSource, History};
+pub use gc::{AttachmentGc, GcReport};
+pub use layout::{AttachmentLayout, LayoutMigration, MigrationReport};
+pub use attachment::{AttachmentState, TransferStatus};
+pub use verify::{StorageVerifier, VerifyReport, DamagedFile, Damage};
+pub use versions::AttachmentVersions;
+pub use webdav::{ZoteroWebDav, WebDavProperties};
+pub use download::{AttachmentDownloads, ApiClaims};
 
 lazy_static! {
     static ref TEXT_VARIABLES_REGEX: Regex = Regex::new(r#"([a-zA-Z0-9_]+:([^ \n<"]+|"[^"]+"))"#).unwrap();
@@ -57,17 +81,21 @@ pub fn text_no_meta(text: &str) -> String {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ItemCollectionCreateResultFailed {
+    #[serde(default)]
     pub key: String,
     pub code: i64,
     pub message: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Response of a multi-object write, keyed by the index of the object in the request
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ItemCollectionCreateResult {
     pub success: HashMap<String, String>,
     pub unchanged: HashMap<String, String>,
     pub failed: HashMap<String, ItemCollectionCreateResultFailed>,
-    pub successful: HashMap<String, Item>,
+    /// Full API objects (key, version, library, data, ...) written successfully
+    pub successful: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use crate::{Result, Error};
+use super::{SearchData, SyncStatus, LibraryType};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Search {
+    pub key: String,
+    pub version: i64,
+    pub library_id: i64,
+    pub library_type: LibraryType,
+    pub data: SearchData,
+    pub deleted: bool,
+    pub sync_status: SyncStatus,
+
+    #[serde(skip)]
+    pub db: Option<PgPool>,
+    #[serde(skip)]
+    pub db_schema: Option<String>,
+}
+
+impl Search {
+    pub fn set_db(&mut self, db: PgPool, db_schema: String) {
+        self.db = Some(db);
+        self.db_schema = Some(db_schema);
+    }
+
+    pub async fn update_local(&self) -> Result<()> {
+        let db = self.db.as_ref().ok_or_else(|| Error::InvalidData("Database not set".to_string()))?;
+        let schema = self.db_schema.as_ref().ok_or_else(|| Error::InvalidData("Schema not set".to_string()))?;
+
+        let data_value = serde_json::to_value(&self.data)?;
+
+        let query = format!(
+            r#"
+            INSERT INTO {}.searches (key, version, library_id, library_type, data, deleted, sync)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (key, library_id, library_type) DO UPDATE SET
+                version = EXCLUDED.version,
+                data = EXCLUDED.data,
+                deleted = EXCLUDED.deleted,
+                sync = EXCLUDED.sync
+            "#,
+            schema
+        );
+
+        sqlx::query(&query)
+            .bind(&self.key)
+            .bind(self.version)
+            .bind(self.library_id)
+            .bind(self.library_type)
+            .bind(&data_value)
+            .bind(self.deleted)
+            .bind(self.sync_status)
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_cloud(&mut self, client: &super::ZoteroClient, library_version: i64) -> Result<i64> {
+        // Check if search is marked for deletion
+        if self.deleted {
+            // Delete search from Zotero API
+            let new_version = client.delete_search_unified(self.library_id, self.library_type, &self.key, library_version).await?;
+
+            // Remove from local database
+            if let (Some(db), Some(schema)) = (&self.db, &self.db_schema) {
+                let query = format!(
+                    "DELETE FROM {}.searches WHERE key = $1 AND library_id = $2 AND library_type = $3",
+                    schema
+                );
+                sqlx::query(&query)
+                    .bind(&self.key)
+                    .bind(self.library_id)
+                    .bind(self.library_type)
+                    .execute(db)
+                    .await?;
+            }
+
+            return Ok(new_version);
+        }
+
+        match self.sync_status {
+            SyncStatus::New | SyncStatus::Modified => {
+                // Upload search to Zotero API
+                let new_version = client.upload_search_unified(self.library_id, self.library_type, self, library_version).await?;
+
+                // Update local status
+                self.sync_status = SyncStatus::Synced;
+                self.version = new_version;
+
+                // Update local database
+                if let (Some(db), Some(schema)) = (&self.db, &self.db_schema) {
+                    let query = format!(
+                        "UPDATE {}.searches SET sync = 'synced', version = $1 WHERE key = $2 AND library_id = $3 AND library_type = $4",
+                        schema
+                    );
+                    sqlx::query(&query)
+                        .bind(self.version)
+                        .bind(&self.key)
+                        .bind(self.library_id)
+                        .bind(self.library_type)
+                        .execute(db)
+                        .await?;
+                }
+
+                Ok(new_version)
+            }
+
+            SyncStatus::Synced => {
+                // Already synchronized, nothing to do
+                tracing::debug!("Search {} already synchronized", self.key);
+                Ok(library_version)
+            }
+
+            SyncStatus::Incomplete => {
+                // Handle incomplete sync - might need to retry
+                tracing::warn!("Search {} has incomplete sync status", self.key);
+                Ok(library_version)
+            }
+        }
+    }
+}
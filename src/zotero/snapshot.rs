@@ -0,0 +1,275 @@
+//! Zotero web snapshots (`imported_url` attachments).
+//!
+//! Zotero transfers a snapshot as one ZIP archive holding the saved page and
+//! its resources. Downloads are unpacked into one object per archive entry
+//! below the attachment's prefix, next to an index naming the main HTML
+//! entry; uploads pack those objects into an archive again.
+//!
+//! Archives are held in memory while they are unpacked or packed, so their
+//! size and that of their contents is capped.
+
+use std::io::{Cursor, Read, Write};
+use std::path::{Component, Path};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+use crate::filesystem::{FileSystem, FileGetOptions, FilePutOptions, MD5_METADATA};
+use super::attachment::{self, MTIME_METADATA};
+
+/// Name of the index object below a snapshot's prefix
+pub const INDEX_NAME: &str = ".snapshot.json";
+/// Largest snapshot archive downloaded
+pub const MAX_ARCHIVE_SIZE: u64 = 256 * 1024 * 1024;
+/// Largest uncompressed size of one archive entry
+pub const MAX_ENTRY_SIZE: u64 = 256 * 1024 * 1024;
+/// Largest uncompressed size of all entries of an archive together
+pub const MAX_UNPACKED_SIZE: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    /// Path within the archive, `/`-separated
+    pub name: String,
+    pub size: u64,
+    pub md5: String,
+}
+
+/// Contents of an unpacked snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotIndex {
+    /// Entry holding the saved page
+    pub main: String,
+    pub entries: Vec<SnapshotEntry>,
+    /// MD5 of the archive the entries were unpacked from
+    pub zip_md5: String,
+}
+
+impl SnapshotIndex {
+    pub fn main_entry(&self) -> Option<&SnapshotEntry> {
+        self.entries.iter().find(|e| e.name == self.main)
+    }
+}
+
+/// Archive packed from a snapshot's stored files, ready for upload
+#[derive(Debug)]
+pub struct SnapshotArchive {
+    pub data: Vec<u8>,
+    pub md5: String,
+}
+
+fn archive_error(e: impl std::fmt::Display) -> Error {
+    Error::InvalidData(format!("Invalid snapshot archive: {}", e))
+}
+
+/// Files of a snapshot archive; directories are skipped, and entries that
+/// would escape the snapshot's prefix or unpack to more than
+/// [`MAX_ENTRY_SIZE`], or [`MAX_UNPACKED_SIZE`] in all, are rejected
+pub fn unpack(archive: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    unpack_within(archive, MAX_ENTRY_SIZE, MAX_UNPACKED_SIZE)
+}
+
+fn unpack_within(archive: &[u8], max_entry: u64, max_total: u64) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(archive)).map_err(archive_error)?;
+    let mut files = Vec::new();
+    let mut unpacked = 0u64;
+
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(archive_error)?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        let name = entry.name().replace('\\', "/");
+        let safe = Path::new(&name).components().all(|c| matches!(c, Component::Normal(_)));
+        if !safe || name == INDEX_NAME {
+            return Err(archive_error(format!("unsafe entry name {:?}", name)));
+        }
+
+        // The sizes in the archive are not trusted; reading one byte past
+        // the limit tells whether it was exceeded
+        let limit = max_entry.min(max_total - unpacked);
+        let mut data = Vec::new();
+        (&mut entry).take(limit + 1).read_to_end(&mut data)?;
+        if data.len() as u64 > limit {
+            return Err(archive_error(format!("entry {:?} unpacks to more than {} bytes", name, limit)));
+        }
+        unpacked += data.len() as u64;
+        files.push((name, data));
+    }
+
+    Ok(files)
+}
+
+/// ZIP archive of a snapshot's files
+pub fn pack(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    for (name, data) in files {
+        zip.start_file(name.as_str(), options).map_err(archive_error)?;
+        zip.write_all(data)?;
+    }
+
+    Ok(zip.finish().map_err(archive_error)?.into_inner())
+}
+
+/// Pick the main entry: `preferred` (the attachment's filename) if the
+/// archive has it, else the least nested HTML file
+fn main_entry(names: &[&str], preferred: Option<&str>) -> Option<String> {
+    if let Some(preferred) = preferred.filter(|p| names.contains(p)) {
+        return Some(preferred.to_string());
+    }
+
+    let is_html = |name: &str| {
+        let lower = name.to_ascii_lowercase();
+        lower.ends_with(".html") || lower.ends_with(".htm")
+    };
+    names.iter()
+        .copied()
+        .filter(|name| is_html(name))
+        .min_by_key(|name| (name.matches('/').count(), name.len()))
+        .or_else(|| names.first().copied())
+        .map(str::to_string)
+}
+
+/// Unpack a downloaded archive below `prefix`, replacing any earlier
+/// version of the snapshot, and write its index.
+///
+/// Every entry is stored with the metadata of `options` and its own MD5.
+/// The main entry keeps the content type and mtime of `options`; the type
+/// of the others is guessed.
+pub async fn store(
+    filesystem: &dyn FileSystem,
+    folder: &str,
+    prefix: &str,
+    archive: Vec<u8>,
+    preferred_main: Option<&str>,
+    options: FilePutOptions,
+) -> Result<SnapshotIndex> {
+    let zip_md5 = format!("{:x}", md5::compute(&archive));
+    let files = tokio::task::spawn_blocking(move || unpack(&archive))
+        .await
+        .map_err(archive_error)??;
+
+    let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+    let main = main_entry(&names, preferred_main)
+        .ok_or_else(|| archive_error("archive has no files"))?;
+
+    let previous = filesystem.file_list(folder, prefix).await?;
+
+    let mut entries = Vec::with_capacity(files.len());
+    for (name, data) in &files {
+        let md5 = format!("{:x}", md5::compute(data));
+        let mut entry_options = options.clone();
+        if *name != main {
+            entry_options.content_type = None;
+            entry_options.metadata.remove(MTIME_METADATA);
+        }
+        if entry_options.content_type.is_none() {
+            let head = &data[..data.len().min(attachment::SNIFF_LEN)];
+            entry_options.content_type = attachment::guess_content_type(name, head).map(str::to_string);
+        }
+        entry_options.metadata.insert(MD5_METADATA.to_string(), md5.clone());
+
+        filesystem.file_put(folder, &format!("{}{}", prefix, name), data, entry_options).await?;
+        entries.push(SnapshotEntry {
+            name: name.clone(),
+            size: data.len() as u64,
+            md5,
+        });
+    }
+
+    // Drop files of the previous version that the new one no longer has
+    for file in previous {
+        let Some(name) = file.name.strip_prefix(prefix) else { continue };
+        if name != INDEX_NAME && !entries.iter().any(|e| e.name == name) {
+            filesystem.file_delete(folder, &file.name).await?;
+        }
+    }
+
+    let index = SnapshotIndex { main, entries, zip_md5 };
+    let json = serde_json::to_vec_pretty(&index)?;
+    let index_options = FilePutOptions {
+        content_type: Some("application/json".to_string()),
+        ..Default::default()
+    };
+    filesystem.file_put(folder, &format!("{}{}", prefix, INDEX_NAME), &json, index_options).await?;
+
+    Ok(index)
+}
+
+/// Index of the snapshot unpacked below `prefix`
+pub async fn load_index(filesystem: &dyn FileSystem, folder: &str, prefix: &str) -> Result<SnapshotIndex> {
+    let json = filesystem.file_get(folder, &format!("{}{}", prefix, INDEX_NAME), FileGetOptions::default()).await?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Pack the files currently stored below `prefix` into an archive
+pub async fn archive(filesystem: &dyn FileSystem, folder: &str, prefix: &str) -> Result<SnapshotArchive> {
+    let mut files = Vec::new();
+    for file in filesystem.file_list(folder, prefix).await? {
+        let Some(name) = file.name.strip_prefix(prefix) else { continue };
+        if name == INDEX_NAME {
+            continue;
+        }
+        let data = filesystem.file_get(folder, &file.name, FileGetOptions::default()).await?;
+        files.push((name.to_string(), data));
+    }
+
+    if files.is_empty() {
+        return Err(Error::NotFound(format!("No snapshot files at {}/{}", folder, prefix)));
+    }
+
+    let data = tokio::task::spawn_blocking(move || pack(&files))
+        .await
+        .map_err(archive_error)??;
+    let md5 = format!("{:x}", md5::compute(&data));
+
+    Ok(SnapshotArchive { data, md5 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(names: &[&str]) -> Vec<(String, Vec<u8>)> {
+        names.iter().map(|name| (name.to_string(), name.as_bytes().to_vec())).collect()
+    }
+
+    #[test]
+    fn pack_and_unpack() {
+        let files = files(&["index.html", "index_files/style.css"]);
+        assert_eq!(unpack(&pack(&files).unwrap()).unwrap(), files);
+    }
+
+    #[test]
+    fn entries_escaping_the_prefix_are_rejected() {
+        for name in ["../evil.html", "a/../../evil.html", "/etc/passwd", "..\\evil.html", "./index.html", INDEX_NAME] {
+            let archive = pack(&files(&["index.html", name])).unwrap();
+            assert!(
+                matches!(unpack(&archive), Err(Error::InvalidData(_))),
+                "{:?} was unpacked",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn uncompressed_size_is_capped() {
+        let archive = pack(&[
+            ("a.html".to_string(), vec![b'a'; 100]),
+            ("b.html".to_string(), vec![b'b'; 100]),
+        ]).unwrap();
+
+        assert_eq!(unpack_within(&archive, 100, 200).unwrap().len(), 2);
+        assert!(unpack_within(&archive, 99, 1000).is_err());
+        assert!(unpack_within(&archive, 100, 199).is_err());
+    }
+
+    #[test]
+    fn main_entry_prefers_filename_then_shallow_html() {
+        let names = ["res/page.html", "snapshot.html", "image.png"];
+        assert_eq!(main_entry(&names, Some("res/page.html")).as_deref(), Some("res/page.html"));
+        assert_eq!(main_entry(&names, Some("missing.html")).as_deref(), Some("snapshot.html"));
+        assert_eq!(main_entry(&["image.png"], None).as_deref(), Some("image.png"));
+    }
+}
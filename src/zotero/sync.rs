@@ -1,21 +1,16 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "library_type")]
 #[sqlx(rename_all = "lowercase")]
 pub enum LibraryType {
     #[serde(rename = "user")]
     User,
+    #[default]
     #[serde(rename = "group")]
     Group,
 }
 
-impl Default for LibraryType {
-    fn default() -> Self {
-        LibraryType::Group
-    }
-}
-
 impl std::fmt::Display for LibraryType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -37,10 +32,11 @@ impl std::str::FromStr for LibraryType {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "syncdirection")]
 #[sqlx(rename_all = "lowercase")]
 pub enum SyncDirection {
+    #[default]
     #[serde(rename = "none")]
     None,
     #[serde(rename = "tocloud")]
@@ -55,16 +51,11 @@ pub enum SyncDirection {
     BothManual,
 }
 
-impl Default for SyncDirection {
-    fn default() -> Self {
-        SyncDirection::None
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "syncstatus")]
 #[sqlx(rename_all = "lowercase")]
 pub enum SyncStatus {
+    #[default]
     #[serde(rename = "new")]
     New,
     #[serde(rename = "synced")]
@@ -75,17 +66,12 @@ pub enum SyncStatus {
     Incomplete,
 }
 
-impl Default for SyncStatus {
-    fn default() -> Self {
-        SyncStatus::New
-    }
-}
-
 /// Sync mode for independent incoming/outgoing control
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "syncmode")]
 #[sqlx(rename_all = "lowercase")]
 pub enum SyncMode {
+    #[default]
     #[serde(rename = "disabled")]
     Disabled,
     #[serde(rename = "manual")]
@@ -95,12 +81,6 @@ pub enum SyncMode {
     // Future: Automatic, OnDemand
 }
 
-impl Default for SyncMode {
-    fn default() -> Self {
-        SyncMode::Disabled
-    }
-}
-
 impl std::fmt::Display for SyncMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -109,4 +89,35 @@ impl std::fmt::Display for SyncMode {
             SyncMode::EventDriven => write!(f, "event_driven"),
         }
     }
-} 
\ No newline at end of file
+} 
+
+/// How an upload rejected with 412 Precondition Failed is resolved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "conflictpolicy")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Re-send the local version on top of the remote one
+    #[serde(rename = "local_wins")]
+    LocalWins,
+    /// Discard the local change and keep what Zotero has
+    #[serde(rename = "remote_wins")]
+    RemoteWins,
+    /// Three-way merge; overlapping edits are recorded for review
+    #[serde(rename = "merge")]
+    Merge,
+    /// Record every conflict for review
+    #[default]
+    #[serde(rename = "manual")]
+    Manual,
+}
+
+impl std::fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConflictPolicy::LocalWins => write!(f, "local_wins"),
+            ConflictPolicy::RemoteWins => write!(f, "remote_wins"),
+            ConflictPolicy::Merge => write!(f, "merge"),
+            ConflictPolicy::Manual => write!(f, "manual"),
+        }
+    }
+}
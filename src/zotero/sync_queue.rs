@@ -1,8 +1,8 @@
 //! Sync queue management for event-driven outgoing sync.
 //!
 //! This module provides functionality to manage the sync_queue table which
-//! stores pending sync operations created by PostgreSQL triggers when items
-//! or collections are modified.
+//! stores pending sync operations created by PostgreSQL triggers when items,
+//! collections or saved searches are modified.
 
 use chrono::{DateTime, Utc};
 use sqlx::PgPool;
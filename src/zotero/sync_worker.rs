@@ -12,8 +12,9 @@ use tracing::{info, warn, error, debug};
 use crate::{Result, Error};
 use crate::filesystem::FileSystem;
 use super::{
-    ZoteroClient, LibraryType, SyncStatus,
-    Item, Collection, ItemData, CollectionData,
+    ZoteroClient, ConflictResolver, LibraryType, SyncStatus,
+    Item, Collection, Search, ItemData, CollectionData, SearchData,
+    client::WRITE_BATCH_LIMIT,
     sync_queue::{SyncQueue, SyncQueueEntry},
 };
 
@@ -47,7 +48,6 @@ pub struct SyncWorker {
     db: PgPool,
     schema: String,
     queue: SyncQueue,
-    #[allow(dead_code)] // Reserved for future attachment upload support
     filesystem: Arc<dyn FileSystem>,
     config: SyncWorkerConfig,
 }
@@ -151,21 +151,31 @@ impl SyncWorker {
         // Get current library version for API calls
         let mut library_version = self.get_library_version(library_id, library_type).await?;
 
-        // Process entries by type (collections first, then items)
-        let (collection_entries, item_entries): (Vec<_>, Vec<_>) = entries
+        // Process entries by type (collections first, then searches, then items)
+        let (collection_entries, other_entries): (Vec<_>, Vec<_>) = entries
             .into_iter()
             .partition(|e| e.entity_type == "collection");
+        let (search_entries, item_entries): (Vec<_>, Vec<_>) = other_entries
+            .into_iter()
+            .partition(|e| e.entity_type == "search");
 
-        // Process collections first (items may reference them)
+        // Process collections first (items may reference them), deleting in batches
+        let (collection_deletes, collection_entries): (Vec<_>, Vec<_>) = collection_entries
+            .into_iter()
+            .partition(|e| e.operation == "delete");
         for entry in collection_entries {
             self.process_entry(&entry, &mut library_version).await;
         }
+        self.process_delete_entries(collection_deletes, &mut library_version).await;
 
-        // Process items
-        for entry in item_entries {
+        // Process saved searches
+        for entry in search_entries {
             self.process_entry(&entry, &mut library_version).await;
         }
 
+        // Process items, uploading creates and updates in batches
+        self.process_item_entries(item_entries, &mut library_version).await;
+
         // Update library version in database
         self.update_library_version(library_id, library_type, library_version).await?;
 
@@ -182,12 +192,18 @@ impl SyncWorker {
         let result = match entry.entity_type.as_str() {
             "item" => self.sync_item(entry, library_version).await,
             "collection" => self.sync_collection(entry, library_version).await,
+            "search" => self.sync_search(entry, library_version).await,
             _ => Err(Error::InvalidData(format!(
                 "Unknown entity type: {}",
                 entry.entity_type
             ))),
         };
 
+        self.finish_entry(entry, result).await;
+    }
+
+    /// Mark a queue entry completed or failed depending on its sync result
+    async fn finish_entry(&self, entry: &SyncQueueEntry, result: Result<()>) {
         match result {
             Ok(()) => {
                 debug!("Successfully synced {} {}", entry.entity_type, entry.entity_key);
@@ -207,6 +223,133 @@ impl SyncWorker {
         }
     }
 
+    /// Process item entries, sending new and modified items in multi-object writes
+    async fn process_item_entries(&self, entries: Vec<SyncQueueEntry>, library_version: &mut i64) {
+        let mut batch_entries = Vec::new();
+        let mut batch_items = Vec::new();
+        let mut delete_entries = Vec::new();
+
+        for entry in entries {
+            if entry.operation == "delete" {
+                delete_entries.push(entry);
+                continue;
+            }
+
+            match self.load_item(&entry.entity_key, entry.library_id, entry.library_type).await {
+                Ok(item) if !item.deleted && matches!(item.sync_status, SyncStatus::New | SyncStatus::Modified) => {
+                    batch_entries.push(entry);
+                    batch_items.push(item);
+                }
+                // Deleted, parked or already synced rows take the single-entry path
+                Ok(_) => self.process_entry(&entry, library_version).await,
+                Err(e) => self.finish_entry(&entry, Err(e)).await,
+            }
+        }
+
+        for (entries, items) in batch_entries
+            .chunks(WRITE_BATCH_LIMIT)
+            .zip(batch_items.chunks_mut(WRITE_BATCH_LIMIT))
+        {
+            debug!("Uploading batch of {} items", items.len());
+
+            match Item::update_cloud_batch(&self.client, items, library_version).await {
+                Ok(outcomes) => {
+                    for ((entry, item), outcome) in entries.iter().zip(items.iter_mut()).zip(outcomes) {
+                        let result = match outcome {
+                            Ok(()) => self.upload_file(entry, item, library_version).await,
+                            Err(e) if e.is_precondition_failed() => {
+                                self.resolve_item_conflict(entry, item, library_version).await
+                            }
+                            Err(e) => Err(e),
+                        };
+                        self.finish_entry(entry, result).await;
+                    }
+                }
+                // The library moved on since our version; retry item by item so
+                // each one goes through conflict resolution
+                Err(e) if e.is_precondition_failed() => {
+                    for (entry, item) in entries.iter().zip(items.iter_mut()) {
+                        let result = self.upload_item(entry, item, library_version).await;
+                        self.finish_entry(entry, result).await;
+                    }
+                }
+                Err(e) => {
+                    for entry in entries {
+                        let result = Err(Error::Sync(format!("Batch upload failed: {}", e)));
+                        self.finish_entry(entry, result).await;
+                    }
+                }
+            }
+        }
+
+        self.process_delete_entries(delete_entries, library_version).await;
+    }
+
+    /// Process delete entries of one entity type, deleting up to
+    /// [`WRITE_BATCH_LIMIT`] keys per request
+    async fn process_delete_entries(&self, entries: Vec<SyncQueueEntry>, library_version: &mut i64) {
+        for chunk in entries.chunks(WRITE_BATCH_LIMIT) {
+            let first = &chunk[0];
+            let keys: Vec<String> = chunk.iter().map(|e| e.entity_key.clone()).collect();
+            debug!("Deleting batch of {} {}s", keys.len(), first.entity_type);
+
+            let (result, table) = match first.entity_type.as_str() {
+                "item" => (
+                    self.client.delete_items_unified(first.library_id, first.library_type, &keys, *library_version).await,
+                    "items",
+                ),
+                "collection" => (
+                    self.client.delete_collections_unified(first.library_id, first.library_type, &keys, *library_version).await,
+                    "collections",
+                ),
+                _ => {
+                    for entry in chunk {
+                        self.process_entry(entry, library_version).await;
+                    }
+                    continue;
+                }
+            };
+
+            match result {
+                Ok(new_version) => {
+                    *library_version = new_version;
+
+                    // Remove from local database
+                    let query = format!(
+                        "DELETE FROM {}.{} WHERE key = ANY($1) AND library_id = $2 AND library_type = $3",
+                        self.schema, table
+                    );
+                    let removed = sqlx::query(&query)
+                        .bind(&keys)
+                        .bind(first.library_id)
+                        .bind(first.library_type)
+                        .execute(&self.db)
+                        .await;
+
+                    for entry in chunk {
+                        let result = match &removed {
+                            Ok(_) => Ok(()),
+                            Err(e) => Err(Error::Sync(format!("Failed to remove local row: {}", e))),
+                        };
+                        self.finish_entry(entry, result).await;
+                    }
+                }
+                // Retry entry by entry so only the conflicting ones fail
+                Err(e) if e.is_precondition_failed() => {
+                    for entry in chunk {
+                        self.process_entry(entry, library_version).await;
+                    }
+                }
+                Err(e) => {
+                    for entry in chunk {
+                        let result = Err(Error::Sync(format!("Batch delete failed: {}", e)));
+                        self.finish_entry(entry, result).await;
+                    }
+                }
+            }
+        }
+    }
+
     /// Sync a single item to Zotero
     async fn sync_item(&self, entry: &SyncQueueEntry, library_version: &mut i64) -> Result<()> {
         // Handle delete operations specially
@@ -234,10 +377,44 @@ impl SyncWorker {
         // Load item from database
         let mut item = self.load_item(&entry.entity_key, entry.library_id, entry.library_type).await?;
 
-        // Upload to Zotero
-        item.update_cloud(&self.client, library_version).await?;
+        self.upload_item(entry, &mut item, library_version).await
+    }
 
-        Ok(())
+    /// Upload a single item, resolving conflicts if Zotero rejects the version
+    async fn upload_item(&self, entry: &SyncQueueEntry, item: &mut Item, library_version: &mut i64) -> Result<()> {
+        match item.update_cloud(&self.client, library_version).await {
+            Ok(()) => self.upload_file(entry, item, library_version).await,
+            Err(e) if e.is_precondition_failed() => self.resolve_item_conflict(entry, item, library_version).await,
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn resolve_item_conflict(&self, entry: &SyncQueueEntry, item: &mut Item, library_version: &mut i64) -> Result<()> {
+        let policy = ConflictResolver::load_policy(&self.db, &self.schema, entry.library_id, entry.library_type).await?;
+        let outcome = ConflictResolver::new(&self.client, &self.db, &self.schema, policy)
+            .resolve_item(item, library_version)
+            .await?;
+        info!("Resolved conflict for item {}: {}", entry.entity_key, outcome);
+        self.upload_file(entry, item, library_version).await
+    }
+
+    /// Upload the attachment file of a synced item if it changed
+    async fn upload_file(&self, entry: &SyncQueueEntry, item: &mut Item, library_version: &mut i64) -> Result<()> {
+        if item.sync_status != SyncStatus::Synced || !item.needs_file_upload() {
+            return Ok(());
+        }
+
+        match item.upload_file_cloud(&self.client, self.filesystem.as_ref(), library_version).await {
+            Err(e) if e.is_precondition_failed() => {
+                let policy = ConflictResolver::load_policy(&self.db, &self.schema, entry.library_id, entry.library_type).await?;
+                let outcome = ConflictResolver::new(&self.client, &self.db, &self.schema, policy)
+                    .resolve_file(item, self.filesystem.as_ref(), library_version)
+                    .await?;
+                info!("Resolved file conflict for item {}: {}", entry.entity_key, outcome);
+                Ok(())
+            }
+            other => other,
+        }
     }
 
     /// Sync a single collection to Zotero
@@ -267,8 +444,51 @@ impl SyncWorker {
         // Load collection from database
         let mut collection = self.load_collection(&entry.entity_key, entry.library_id, entry.library_type).await?;
 
+        // Upload to Zotero, resolving conflicts if Zotero rejects the version
+        match collection.update_cloud(&self.client, *library_version).await {
+            Ok(new_version) => *library_version = new_version,
+            Err(e) if e.is_precondition_failed() => {
+                let policy = ConflictResolver::load_policy(&self.db, &self.schema, entry.library_id, entry.library_type).await?;
+                let outcome = ConflictResolver::new(&self.client, &self.db, &self.schema, policy)
+                    .resolve_collection(&mut collection, library_version)
+                    .await?;
+                info!("Resolved conflict for collection {}: {}", entry.entity_key, outcome);
+            }
+            Err(e) => return Err(e),
+        }
+
+        Ok(())
+    }
+
+    /// Sync a single saved search to Zotero
+    async fn sync_search(&self, entry: &SyncQueueEntry, library_version: &mut i64) -> Result<()> {
+        // Handle delete operations specially
+        if entry.operation == "delete" {
+            let new_version = self.client
+                .delete_search_unified(entry.library_id, entry.library_type, &entry.entity_key, *library_version)
+                .await?;
+            *library_version = new_version;
+
+            // Remove from local database
+            let query = format!(
+                "DELETE FROM {}.searches WHERE key = $1 AND library_id = $2 AND library_type = $3",
+                self.schema
+            );
+            sqlx::query(&query)
+                .bind(&entry.entity_key)
+                .bind(entry.library_id)
+                .bind(entry.library_type)
+                .execute(&self.db)
+                .await?;
+
+            return Ok(());
+        }
+
+        // Load search from database
+        let mut search = self.load_search(&entry.entity_key, entry.library_id, entry.library_type).await?;
+
         // Upload to Zotero
-        let new_version = collection.update_cloud(&self.client, *library_version).await?;
+        let new_version = search.update_cloud(&self.client, *library_version).await?;
         *library_version = new_version;
 
         Ok(())
@@ -278,7 +498,7 @@ impl SyncWorker {
     async fn load_item(&self, key: &str, library_id: i64, library_type: LibraryType) -> Result<Item> {
         let query = format!(
             r#"
-            SELECT key, version, data, meta, trashed, deleted, sync::TEXT as sync, md5
+            SELECT key, version, data, meta, trashed, deleted, sync::TEXT as sync, md5, synced_data
             FROM {}.items
             WHERE key = $1 AND library_id = $2 AND library_type = $3
             "#,
@@ -302,10 +522,11 @@ impl SyncWorker {
         let deleted: bool = row.get("deleted");
         let sync_status: String = row.get("sync");
         let md5: Option<String> = row.get("md5");
+        let synced_data: Option<serde_json::Value> = row.get("synced_data");
 
         let item_data: ItemData = serde_json::from_value(data_value)?;
         let item_meta: Option<super::item::ItemMeta> = meta_value
-            .map(|v| serde_json::from_value(v))
+            .map(serde_json::from_value)
             .transpose()?;
 
         Ok(Item {
@@ -324,6 +545,7 @@ impl SyncWorker {
                 _ => SyncStatus::Synced,
             },
             md5,
+            synced_data,
             db: Some(self.db.clone()),
             db_schema: Some(self.schema.clone()),
         })
@@ -333,7 +555,7 @@ impl SyncWorker {
     async fn load_collection(&self, key: &str, library_id: i64, library_type: LibraryType) -> Result<Collection> {
         let query = format!(
             r#"
-            SELECT key, version, data, meta, deleted, sync::TEXT as sync
+            SELECT key, version, data, meta, deleted, sync::TEXT as sync, synced_data
             FROM {}.collections
             WHERE key = $1 AND library_id = $2 AND library_type = $3
             "#,
@@ -355,10 +577,11 @@ impl SyncWorker {
         let meta_value: Option<serde_json::Value> = row.get("meta");
         let deleted: bool = row.get("deleted");
         let sync_status: String = row.get("sync");
+        let synced_data: Option<serde_json::Value> = row.get("synced_data");
 
         let collection_data: CollectionData = serde_json::from_value(data_value)?;
         let collection_meta: Option<super::collection::CollectionMeta> = meta_value
-            .map(|v| serde_json::from_value(v))
+            .map(serde_json::from_value)
             .transpose()?;
 
         Ok(Collection {
@@ -375,6 +598,53 @@ impl SyncWorker {
                 "incomplete" => SyncStatus::Incomplete,
                 _ => SyncStatus::Synced,
             },
+            synced_data,
+            db: Some(self.db.clone()),
+            db_schema: Some(self.schema.clone()),
+        })
+    }
+
+    /// Load a saved search from the database
+    async fn load_search(&self, key: &str, library_id: i64, library_type: LibraryType) -> Result<Search> {
+        let query = format!(
+            r#"
+            SELECT key, version, data, deleted, sync::TEXT as sync
+            FROM {}.searches
+            WHERE key = $1 AND library_id = $2 AND library_type = $3
+            "#,
+            self.schema
+        );
+
+        let row = sqlx::query(&query)
+            .bind(key)
+            .bind(library_id)
+            .bind(library_type)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("Search {} not found", key)))?;
+
+        use sqlx::Row;
+        let key: String = row.get("key");
+        let version: i64 = row.get("version");
+        let data_value: serde_json::Value = row.get("data");
+        let deleted: bool = row.get("deleted");
+        let sync_status: String = row.get("sync");
+
+        let search_data: SearchData = serde_json::from_value(data_value)?;
+
+        Ok(Search {
+            key,
+            version,
+            library_id,
+            library_type,
+            data: search_data,
+            deleted,
+            sync_status: match sync_status.as_str() {
+                "new" => SyncStatus::New,
+                "modified" => SyncStatus::Modified,
+                "incomplete" => SyncStatus::Incomplete,
+                _ => SyncStatus::Synced,
+            },
             db: Some(self.db.clone()),
             db_schema: Some(self.schema.clone()),
         })
@@ -88,6 +88,19 @@ pub struct ItemData {
     pub extra_fields: serde_json::Map<String, serde_json::Value>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FullTextData {
+    pub content: String,
+    #[serde(rename = "indexedPages", skip_serializing_if = "Option::is_none")]
+    pub indexed_pages: Option<i32>,
+    #[serde(rename = "totalPages", skip_serializing_if = "Option::is_none")]
+    pub total_pages: Option<i32>,
+    #[serde(rename = "indexedChars", skip_serializing_if = "Option::is_none")]
+    pub indexed_chars: Option<i32>,
+    #[serde(rename = "totalChars", skip_serializing_if = "Option::is_none")]
+    pub total_chars: Option<i32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TagData {
     pub tag: String,
@@ -114,6 +127,22 @@ pub struct CollectionData {
     pub relations: Option<serde_json::Value>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchCondition {
+    pub condition: String,
+    pub operator: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchData {
+    pub key: String,
+    pub version: i64,
+    pub name: String,
+    #[serde(default)]
+    pub conditions: Vec<SearchCondition>,
+}
+
 // API response structure for collections from Zotero
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CollectionApiResponse {
@@ -125,6 +154,16 @@ pub struct CollectionApiResponse {
     pub data: CollectionData,
 }
 
+// API response structure for saved searches from Zotero
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchApiResponse {
+    pub key: String,
+    pub version: i64,
+    pub library: LibraryInfo,
+    pub links: serde_json::Value,
+    pub data: SearchData,
+}
+
 // API response structure for items from Zotero
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ItemApiResponse {
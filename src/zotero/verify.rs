@@ -0,0 +1,255 @@
+//! Integrity scrub of stored attachment files.
+//!
+//! Every attachment item with an `md5` in its data is looked up in storage,
+//! stat'ed and hashed. Missing, truncated and corrupt files are reported and
+//! can be downloaded again from Zotero.
+
+use std::sync::Arc;
+use sqlx::{PgPool, Row};
+use tracing::{info, warn};
+
+use crate::{Error, Result};
+use crate::filesystem::{FileSystem, FileGetOptions, FileStatOptions};
+use crate::filesystem::stream::md5_of;
+use super::{Item, ItemData, LibraryType, SyncStatus, ZoteroClient, AttachmentLayout};
+use super::snapshot;
+
+/// What is wrong with a stored file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Damage {
+    /// No object at the expected location
+    Missing,
+    /// Object is shorter than the size recorded at its last transfer
+    Truncated { expected: u64, actual: u64 },
+    /// Object's MD5 differs from the expected one
+    Mismatch { expected: String, actual: String },
+    /// Object could not be read
+    Unreadable(String),
+}
+
+impl std::fmt::Display for Damage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Damage::Missing => write!(f, "missing"),
+            Damage::Truncated { expected, actual } => write!(f, "truncated ({} of {} bytes)", actual, expected),
+            Damage::Mismatch { expected, actual } => write!(f, "MD5 mismatch (expected {}, got {})", expected, actual),
+            Damage::Unreadable(e) => write!(f, "unreadable: {}", e),
+        }
+    }
+}
+
+/// A damaged attachment file
+#[derive(Debug, Clone)]
+pub struct DamagedFile {
+    pub item_key: String,
+    pub library_id: i64,
+    pub library_type: LibraryType,
+    pub folder: String,
+    pub name: String,
+    pub damage: Damage,
+    /// Set if the file was downloaded again successfully
+    pub repaired: bool,
+}
+
+/// Outcome of a verification run
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Number of attachment files checked
+    pub checked: usize,
+    /// Bytes hashed
+    pub bytes: u64,
+    pub damaged: Vec<DamagedFile>,
+}
+
+impl VerifyReport {
+    pub fn repaired(&self) -> usize {
+        self.damaged.iter().filter(|d| d.repaired).count()
+    }
+}
+
+pub struct StorageVerifier {
+    db: PgPool,
+    schema: String,
+    filesystem: Arc<dyn FileSystem>,
+    layout: AttachmentLayout,
+}
+
+impl StorageVerifier {
+    pub fn new(db: PgPool, schema: String, filesystem: Arc<dyn FileSystem>, layout: AttachmentLayout) -> Self {
+        Self { db, schema, filesystem, layout }
+    }
+
+    /// Check the files of all libraries, or of one, and download damaged
+    /// files again if a client is given.
+    ///
+    /// Files with a local change not yet uploaded are checked against
+    /// `items.md5` and never replaced.
+    pub async fn run(&self, library: Option<(i64, LibraryType)>, repair_with: Option<&ZoteroClient>) -> Result<VerifyReport> {
+        let query = format!(
+            r#"
+            SELECT i.key, i.library_id, i.library_type, i.version, i.data, i.trashed, i.deleted,
+                   i.sync::TEXT as sync, i.md5, a.size AS recorded_size
+            FROM {schema}.items i
+            LEFT JOIN {schema}.attachments a
+              ON a.item_key = i.key AND a.library_id = i.library_id AND a.library_type = i.library_type
+            WHERE i.data->>'itemType' = 'attachment'
+              AND i.data->>'md5' IS NOT NULL
+              AND NOT i.deleted
+              AND ($1::bigint IS NULL OR (i.library_id = $1 AND i.library_type = $2))
+            ORDER BY i.library_type, i.library_id, i.key
+            "#,
+            schema = self.schema
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(library.map(|(id, _)| id))
+            .bind(library.map(|(_, library_type)| library_type))
+            .fetch_all(&self.db)
+            .await?;
+
+        let mut report = VerifyReport::default();
+
+        for row in rows {
+            let data_value: serde_json::Value = row.get("data");
+            let sync_status: String = row.get("sync");
+            let recorded_size: Option<i64> = row.get("recorded_size");
+
+            let item = Item {
+                key: row.get("key"),
+                version: row.get("version"),
+                library_id: row.get("library_id"),
+                library_type: row.get("library_type"),
+                data: serde_json::from_value::<ItemData>(data_value)?,
+                meta: None,
+                trashed: row.get("trashed"),
+                deleted: row.get("deleted"),
+                sync_status: match sync_status.as_str() {
+                    "new" => SyncStatus::New,
+                    "modified" => SyncStatus::Modified,
+                    "incomplete" => SyncStatus::Incomplete,
+                    _ => SyncStatus::Synced,
+                },
+                md5: row.get("md5"),
+                synced_data: None,
+                db: Some(self.db.clone()),
+                db_schema: Some(self.schema.clone()),
+            };
+
+            let Some(remote_md5) = item.data.extra_fields.get("md5").and_then(|v| v.as_str()) else { continue };
+            // A stored file awaiting upload is newer than Zotero's
+            let local_change = item.md5.as_deref().filter(|md5| *md5 != remote_md5);
+            let expected_md5 = local_change.unwrap_or(remote_md5).to_string();
+
+            let (folder, name) = item.file_location(&self.layout);
+            let recorded_size = recorded_size.map(|size| size as u64);
+            report.checked += 1;
+
+            let checked = if item.is_snapshot() {
+                self.check_snapshot(&item, &expected_md5, recorded_size).await
+            } else {
+                self.check(&folder, &name, &expected_md5, recorded_size).await
+            };
+            let damage = match checked {
+                Ok(bytes) => {
+                    report.bytes += bytes;
+                    continue;
+                }
+                Err(damage) => damage,
+            };
+            warn!("Attachment {} of {} library {} at {}/{}: {}", item.key, item.library_type, item.library_id, folder, name, damage);
+
+            let mut repaired = false;
+            if let Some(client) = repair_with {
+                if local_change.is_some() {
+                    warn!("Not downloading {} again, it has a local change awaiting upload", item.key);
+                } else {
+                    if item.is_snapshot() {
+                        // The download is skipped while the index lists the expected main file
+                        let prefix = self.layout.snapshot_prefix(item.library_type, item.library_id, &item.key);
+                        let _ = self.filesystem.file_delete(self.layout.bucket(), &format!("{}{}", prefix, snapshot::INDEX_NAME)).await;
+                    }
+                    match item.download_attachment_cloud(client, self.filesystem.as_ref()).await {
+                        Ok(()) => {
+                            // Zotero may have no file to download; check the result
+                            repaired = if item.is_snapshot() {
+                                self.check_snapshot(&item, &expected_md5, None).await.is_ok()
+                            } else {
+                                self.check(&folder, &name, &expected_md5, None).await.is_ok()
+                            };
+                            if repaired {
+                                info!("Downloaded {} again", item.key);
+                            }
+                        }
+                        Err(e) => warn!("Cannot download {} again: {}", item.key, e),
+                    }
+                }
+            }
+
+            report.damaged.push(DamagedFile {
+                item_key: item.key,
+                library_id: item.library_id,
+                library_type: item.library_type,
+                folder,
+                name,
+                damage,
+                repaired,
+            });
+        }
+
+        Ok(report)
+    }
+
+    /// Stat and hash one object; returns its size if intact
+    async fn check(&self, folder: &str, name: &str, expected_md5: &str, recorded_size: Option<u64>) -> std::result::Result<u64, Damage> {
+        match self.filesystem.file_exists(folder, name).await {
+            Ok(true) => {}
+            Ok(false) => return Err(Damage::Missing),
+            Err(e) => return Err(Damage::Unreadable(e.to_string())),
+        }
+        let info = self.filesystem.file_stat(folder, name, FileStatOptions::default())
+            .await
+            .map_err(|e| match e {
+                Error::NotFound(_) => Damage::Missing,
+                e => Damage::Unreadable(e.to_string()),
+            })?;
+
+        if let Some(expected) = recorded_size.filter(|expected| info.size < *expected) {
+            return Err(Damage::Truncated { expected, actual: info.size });
+        }
+
+        let reader = self.filesystem.file_reader(folder, name, FileGetOptions::default())
+            .await
+            .map_err(|e| Damage::Unreadable(e.to_string()))?;
+        let (actual, size) = md5_of(reader).await.map_err(|e| Damage::Unreadable(e.to_string()))?;
+
+        if actual != expected_md5 {
+            return Err(Damage::Mismatch { expected: expected_md5.to_string(), actual });
+        }
+        Ok(size)
+    }
+
+    /// Check an unpacked snapshot: its main file against the attachment's
+    /// MD5 and every other file against the index; returns the bytes hashed
+    async fn check_snapshot(&self, item: &Item, expected_md5: &str, recorded_size: Option<u64>) -> std::result::Result<u64, Damage> {
+        let folder = self.layout.bucket();
+        let prefix = self.layout.snapshot_prefix(item.library_type, item.library_id, &item.key);
+
+        match self.filesystem.file_exists(folder, &format!("{}{}", prefix, snapshot::INDEX_NAME)).await {
+            Ok(true) => {}
+            Ok(false) => return Err(Damage::Missing),
+            Err(e) => return Err(Damage::Unreadable(e.to_string())),
+        }
+        let index = snapshot::load_index(self.filesystem.as_ref(), folder, &prefix)
+            .await
+            .map_err(|e| Damage::Unreadable(format!("snapshot index: {}", e)))?;
+        let Some(main) = index.main_entry() else {
+            return Err(Damage::Unreadable("snapshot index has no main entry".to_string()));
+        };
+
+        let mut bytes = self.check(folder, &format!("{}{}", prefix, main.name), expected_md5, recorded_size).await?;
+        for entry in index.entries.iter().filter(|e| e.name != index.main) {
+            bytes += self.check(folder, &format!("{}{}", prefix, entry.name), &entry.md5, Some(entry.size)).await?;
+        }
+        Ok(bytes)
+    }
+}
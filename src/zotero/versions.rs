@@ -0,0 +1,106 @@
+//! Earlier versions of attachment files.
+//!
+//! With `[storage] versioning` or `object_locking`, replacing or deleting a
+//! file keeps its previous contents in the bucket. Restoring a version makes
+//! it the current file again and marks the attachment for upload, like a
+//! locally replaced file.
+
+use std::sync::Arc;
+use sqlx::{PgPool, Row};
+use tracing::info;
+
+use crate::{Error, Result};
+use crate::filesystem::{FileSystem, FileGetOptions, FileVersion};
+use crate::filesystem::stream::md5_of;
+use super::{AttachmentLayout, LibraryType};
+
+pub struct AttachmentVersions {
+    db: PgPool,
+    schema: String,
+    filesystem: Arc<dyn FileSystem>,
+    layout: AttachmentLayout,
+}
+
+impl AttachmentVersions {
+    pub fn new(db: PgPool, schema: String, filesystem: Arc<dyn FileSystem>, layout: AttachmentLayout) -> Self {
+        Self { db, schema, filesystem, layout }
+    }
+
+    /// Folder and file name of an attachment's file.
+    ///
+    /// Blobs of content-addressed storage are never replaced, and snapshots
+    /// consist of several files, so neither has versions to restore.
+    async fn location(&self, library_id: i64, library_type: LibraryType, item_key: &str) -> Result<(String, String)> {
+        if self.layout.is_content_addressed() {
+            return Err(Error::InvalidData("Content-addressed attachment files have no versions".to_string()));
+        }
+
+        let query = format!(
+            r#"
+            SELECT data->>'filename' AS filename, data->>'linkMode' AS link_mode
+            FROM {}.items
+            WHERE key = $1 AND library_id = $2 AND library_type = $3
+              AND data->>'itemType' = 'attachment'
+            "#,
+            self.schema
+        );
+
+        let row = sqlx::query(&query)
+            .bind(item_key)
+            .bind(library_id)
+            .bind(library_type)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("Attachment {} of {} library {}", item_key, library_type, library_id)))?;
+
+        let filename: Option<String> = row.get("filename");
+        let link_mode: Option<String> = row.get("link_mode");
+        if link_mode.as_deref() == Some("imported_url") {
+            return Err(Error::InvalidData(format!("Snapshot {} has no file versions", item_key)));
+        }
+
+        Ok(self.layout.location(library_type, library_id, item_key, filename.as_deref()))
+    }
+
+    /// Stored versions of an attachment's file, newest first
+    pub async fn list(&self, library_id: i64, library_type: LibraryType, item_key: &str) -> Result<Vec<FileVersion>> {
+        let (folder, name) = self.location(library_id, library_type, item_key).await?;
+        self.filesystem.file_versions(&folder, &name).await
+    }
+
+    /// Make a version the attachment's current file and mark it for upload;
+    /// returns the MD5 of the restored file
+    pub async fn restore(&self, library_id: i64, library_type: LibraryType, item_key: &str, version_id: &str) -> Result<String> {
+        let (folder, name) = self.location(library_id, library_type, item_key).await?;
+
+        let versions = self.filesystem.file_versions(&folder, &name).await?;
+        match versions.iter().find(|v| v.version_id == version_id) {
+            None => return Err(Error::NotFound(format!("Version {} of {}/{}", version_id, folder, name))),
+            Some(v) if v.is_delete_marker => {
+                return Err(Error::InvalidData(format!("Version {} of {}/{} is a deletion", version_id, folder, name)));
+            }
+            Some(_) => {}
+        }
+
+        self.filesystem.file_restore_version(&folder, &name, version_id).await?;
+        let reader = self.filesystem.file_reader(&folder, &name, FileGetOptions::default()).await?;
+        let (md5, size) = md5_of(reader).await?;
+
+        // A stored MD5 different from Zotero's makes the next outgoing sync
+        // upload the file
+        let query = format!(
+            "UPDATE {}.items SET md5 = $1 WHERE key = $2 AND library_id = $3 AND library_type = $4",
+            self.schema
+        );
+        sqlx::query(&query)
+            .bind(&md5)
+            .bind(item_key)
+            .bind(library_id)
+            .bind(library_type)
+            .execute(&self.db)
+            .await?;
+
+        info!("Restored version {} of {}/{} ({} bytes, MD5 {})", version_id, folder, name, size, md5);
+        Ok(md5)
+    }
+}
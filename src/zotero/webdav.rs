@@ -0,0 +1,109 @@
+//! Attachment files synced through WebDAV instead of Zotero Storage.
+//!
+//! Zotero clients can keep the files of the personal library on a WebDAV
+//! server. Each attachment is stored in the `zotero/` collection below the
+//! configured URL as `{KEY}.zip`, holding the attachment's files, next to
+//! `{KEY}.prop`, recording the main file's MD5 and modification time. The
+//! same hash and mtime are set on the attachment item through the API, as
+//! Zotero Storage never sees the file.
+
+use std::sync::Arc;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{Error, Result};
+use crate::config::WebDavConfig;
+use crate::filesystem::{FileSystem, FileGetOptions, FilePutOptions, WebDavFileSystem};
+use super::snapshot;
+
+/// Collection below the configured URL that holds the files
+pub const ZOTERO_FOLDER: &str = "zotero";
+
+lazy_static! {
+    static ref MTIME: Regex = Regex::new(r"<mtime>\s*(\d+)\s*</mtime>").unwrap();
+    static ref HASH: Regex = Regex::new(r"<hash>\s*([0-9a-fA-F]{32})\s*</hash>").unwrap();
+}
+
+/// Contents of a `{KEY}.prop` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebDavProperties {
+    /// Modification time of the main file, in milliseconds
+    pub mtime: i64,
+    /// MD5 of the main file
+    pub hash: String,
+}
+
+impl WebDavProperties {
+    pub fn parse(xml: &str) -> Result<Self> {
+        let invalid = || Error::InvalidData(format!("Invalid WebDAV properties: {}", xml));
+        let mtime = MTIME.captures(xml).and_then(|c| c[1].parse().ok()).ok_or_else(invalid)?;
+        let hash = HASH.captures(xml).map(|c| c[1].to_ascii_lowercase()).ok_or_else(invalid)?;
+        Ok(Self { mtime, hash })
+    }
+
+    pub fn to_xml(&self) -> String {
+        format!(r#"<properties version="1"><mtime>{}</mtime><hash>{}</hash></properties>"#, self.mtime, self.hash)
+    }
+}
+
+/// A Zotero WebDAV file store
+#[derive(Debug, Clone)]
+pub struct ZoteroWebDav {
+    filesystem: Arc<dyn FileSystem>,
+}
+
+impl ZoteroWebDav {
+    /// Store on `filesystem`, whose root is the URL configured in Zotero
+    pub fn new(filesystem: Arc<dyn FileSystem>) -> Self {
+        Self { filesystem }
+    }
+
+    pub fn from_config(config: &WebDavConfig) -> Result<Self> {
+        let filesystem = WebDavFileSystem::new(&config.url, config.username.as_deref(), config.password.as_deref())?;
+        Ok(Self::new(Arc::new(filesystem)))
+    }
+
+    /// Properties of an attachment's stored file; `None` if there is none
+    pub async fn properties(&self, item_key: &str) -> Result<Option<WebDavProperties>> {
+        let name = format!("{}.prop", item_key);
+        if !self.filesystem.file_exists(ZOTERO_FOLDER, &name).await? {
+            return Ok(None);
+        }
+        let xml = self.filesystem.file_get(ZOTERO_FOLDER, &name, FileGetOptions::default()).await?;
+        WebDavProperties::parse(&String::from_utf8_lossy(&xml)).map(Some)
+    }
+
+    /// ZIP archive of an attachment's files; `None` if there is none
+    pub async fn download(&self, item_key: &str) -> Result<Option<Vec<u8>>> {
+        let name = format!("{}.zip", item_key);
+        if !self.filesystem.file_exists(ZOTERO_FOLDER, &name).await? {
+            return Ok(None);
+        }
+        self.filesystem.file_get(ZOTERO_FOLDER, &name, FileGetOptions::default()).await.map(Some)
+    }
+
+    /// Store an attachment's archive and then its properties, in the order
+    /// Zotero writes them
+    pub async fn upload(&self, item_key: &str, archive: &[u8], properties: &WebDavProperties) -> Result<()> {
+        if !self.filesystem.folder_exists(ZOTERO_FOLDER).await? {
+            self.filesystem.folder_create(ZOTERO_FOLDER, Default::default()).await?;
+        }
+        self.filesystem.file_put(ZOTERO_FOLDER, &format!("{}.zip", item_key), archive, FilePutOptions::default()).await?;
+        self.filesystem.file_put(ZOTERO_FOLDER, &format!("{}.prop", item_key), properties.to_xml().as_bytes(), FilePutOptions::default()).await
+    }
+}
+
+/// The file named `filename` in an attachment archive, or its only file
+pub fn extract(archive: &[u8], filename: Option<&str>) -> Result<Vec<u8>> {
+    let mut files = snapshot::unpack(archive)?;
+    let index = filename
+        .and_then(|filename| files.iter().position(|(name, _)| name == filename))
+        .or(if files.len() == 1 { Some(0) } else { None })
+        .ok_or_else(|| Error::InvalidData(format!("Attachment archive has no file {}", filename.unwrap_or("?"))))?;
+    Ok(files.swap_remove(index).1)
+}
+
+/// Archive holding a single attachment file, as Zotero packs it
+pub fn archive(filename: &str, data: Vec<u8>) -> Result<Vec<u8>> {
+    snapshot::pack(&[(filename.to_string(), data)])
+}